@@ -133,6 +133,7 @@ export function make_mis_scene({
   name,
   nsamples = 100,
   mis_weight,
+  mis_heuristic = "mixture",
   board_material,
 }) {
   return make_project({
@@ -144,6 +145,7 @@ export function make_mis_scene({
       nsamples,
       max_depth: 40,
       mis_weight,
+      mis_heuristic,
     },
     scenes: [
       {
@@ -14,9 +14,89 @@ export type AcceleratorConfig =
       kind: "bvh";
       [k: string]: unknown | undefined;
     };
+export type IntegratorConfig =
+  | {
+      kind: "path";
+      [k: string]: unknown | undefined;
+    }
+  | {
+      kind: "direct_lighting";
+      [k: string]: unknown | undefined;
+    }
+  | {
+      kind: "bdpt";
+      [k: string]: unknown | undefined;
+    }
+  | {
+      kind: "sppm";
+      [k: string]: unknown | undefined;
+    }
+  | {
+      bootstrap_samples?: number;
+      chains?: number;
+      kind: "pssmlt";
+      large_step_probability?: number;
+      sigma?: number;
+      [k: string]: unknown | undefined;
+    }
+  | {
+      kind: "ambient_occlusion";
+      max_distance?: number | null;
+      nsamples?: number;
+      [k: string]: unknown | undefined;
+    }
+  | {
+      kind: "normal";
+      [k: string]: unknown | undefined;
+    }
+  | {
+      kind: "uv";
+      [k: string]: unknown | undefined;
+    }
+  | {
+      kind: "albedo";
+      [k: string]: unknown | undefined;
+    }
+  | {
+      far?: number | null;
+      kind: "depth";
+      near?: number | null;
+      [k: string]: unknown | undefined;
+    };
+export type FilterConfig =
+  | {
+      kind: "box";
+      radius?: number;
+      [k: string]: unknown | undefined;
+    }
+  | {
+      kind: "tent";
+      radius?: number;
+      [k: string]: unknown | undefined;
+    }
+  | {
+      kind: "gaussian";
+      radius?: number;
+      sigma?: number;
+      [k: string]: unknown | undefined;
+    }
+  | {
+      b?: number;
+      c?: number;
+      kind: "mitchell";
+      radius?: number;
+      [k: string]: unknown | undefined;
+    }
+  | {
+      kind: "lanczos";
+      radius?: number;
+      tau?: number;
+      [k: string]: unknown | undefined;
+    };
 export type SceneConfig =
   | {
       kind: "uri";
+      material?: MaterialConfig | null;
       transforms?: TransformConfig[];
       uri: string;
       [k: string]: unknown | undefined;
@@ -52,7 +132,8 @@ export type PrimitiveConfig =
       area_light?: AreaLightConfig | null;
       flip_face?: boolean;
       kind: "geom";
-      material: MaterialConfig;
+      material?: MaterialConfig | null;
+      medium?: MediumInterfaceConfig | null;
       shape: ShapeConfig;
       transforms?: TransformConfig[];
       [k: string]: unknown | undefined;
@@ -62,6 +143,12 @@ export type PrimitiveConfig =
       kind: "container";
       transforms?: TransformConfig[];
       [k: string]: unknown | undefined;
+    }
+  | {
+      kind: "volume";
+      medium: MediumConfig;
+      transforms?: TransformConfig[];
+      [k: string]: unknown | undefined;
     };
 export type MaterialConfig =
   | {
@@ -76,7 +163,7 @@ export type MaterialConfig =
       [k: string]: unknown | undefined;
     }
   | {
-      ir: number;
+      ir: AorBFor_IorConfigAndFloat;
       kind: "dielectric";
       [k: string]: unknown | undefined;
     }
@@ -87,7 +174,7 @@ export type MaterialConfig =
     }
   | {
       albedo: AorBFor_TextureConfigFor_JVec3FAnd_JVec3F;
-      eta: number;
+      eta: AorBFor_IorConfigAndFloat;
       kind: "transparent";
       roughness: AorBFor_TextureConfigForFloatAndFloat;
       [k: string]: unknown | undefined;
@@ -100,6 +187,14 @@ export type MaterialConfig =
       metallic: AorBFor_TextureConfigForFloatAndFloat;
       roughness: AorBFor_TextureConfigForFloatAndFloat;
       [k: string]: unknown | undefined;
+    }
+  | {
+      albedo: AorBFor_TextureConfigFor_JVec3FAnd_JVec3F;
+      eta?: number;
+      g?: number;
+      kind: "subsurface";
+      mean_free_path: JVec3F;
+      [k: string]: unknown | undefined;
     };
 export type AorBFor_TextureConfigFor_JVec3FAnd_JVec3F = TextureConfigFor_JVec3F | JVec3F;
 export type TextureConfigFor_JVec3F =
@@ -109,6 +204,7 @@ export type TextureConfigFor_JVec3F =
       [k: string]: unknown | undefined;
     }
   | {
+      color_space?: TextureColorSpace | null;
       kind: "image_texture";
       uri: string;
       [k: string]: unknown | undefined;
@@ -119,6 +215,21 @@ export type TextureConfigFor_JVec3F =
       odd: AorBFor_TextureConfigFor_JVec3FAnd_JVec3F;
       [k: string]: unknown | undefined;
     };
+export type TextureColorSpace = "srgb" | "linear" | "raw";
+export type AorBFor_IorConfigAndFloat = IorConfig | number;
+export type IorConfig =
+  | {
+      a: number;
+      b: number;
+      kind: "cauchy";
+      [k: string]: unknown | undefined;
+    }
+  | {
+      b: number[];
+      c: number[];
+      kind: "sellmeier";
+      [k: string]: unknown | undefined;
+    };
 export type AorBFor_TextureConfigForFloatAndFloat = TextureConfigForFloat | number;
 export type TextureConfigForFloat =
   | {
@@ -127,6 +238,7 @@ export type TextureConfigForFloat =
       [k: string]: unknown | undefined;
     }
   | {
+      color_space?: TextureColorSpace | null;
       kind: "image_texture";
       uri: string;
       [k: string]: unknown | undefined;
@@ -199,9 +311,95 @@ export type ShapeConfig =
       [k: string]: unknown | undefined;
     };
 export type JVec2F = [number, number];
+export type MediumConfig =
+  | {
+      g?: number;
+      kind: "homogeneous";
+      scale?: number;
+      sigma_a: JVec3F;
+      sigma_s: JVec3F;
+      [k: string]: unknown | undefined;
+    }
+  | {
+      albedo: JVec3F;
+      g?: number;
+      kind: "grid";
+      p_max?: JVec3F;
+      p_min?: JVec3F;
+      scale?: number;
+      uri: string;
+      [k: string]: unknown | undefined;
+    }
+  | {
+      albedo: JVec3F;
+      cutoff?: number;
+      ellipsoid?: boolean;
+      frequency?: number;
+      g?: number;
+      kind: "noise";
+      octaves?: number;
+      p_max?: JVec3F;
+      p_min?: JVec3F;
+      scale?: number;
+      seed?: number;
+      [k: string]: unknown | undefined;
+    };
+export type Aov =
+  | "depth"
+  | "normal"
+  | "albedo"
+  | "uv"
+  | "primitive_id"
+  | "material_id"
+  | "direct"
+  | "indirect";
+export type MisHeuristic = "mixture" | "balance" | "power";
+export type OutputFormat = "png" | "exr" | "pfm" | "hdr";
+export type SamplerConfig =
+  | {
+      kind: "independent";
+      [k: string]: unknown | undefined;
+    }
+  | {
+      kind: "stratified";
+      [k: string]: unknown | undefined;
+    }
+  | {
+      kind: "halton";
+      [k: string]: unknown | undefined;
+    }
+  | {
+      kind: "sobol";
+      [k: string]: unknown | undefined;
+    };
+export type ToneMapConfig =
+  | {
+      kind: "clamp";
+      [k: string]: unknown | undefined;
+    }
+  | {
+      kind: "reinhard";
+      [k: string]: unknown | undefined;
+    }
+  | {
+      kind: "extended_reinhard";
+      white?: number | null;
+      [k: string]: unknown | undefined;
+    }
+  | {
+      kind: "aces";
+      [k: string]: unknown | undefined;
+    }
+  | {
+      kind: "agx";
+      [k: string]: unknown | undefined;
+    };
+export type WorkingSpace = "rec709" | "rec2020" | "acescg";
 
 export interface ProjectConfig {
   accelerator?: AcceleratorConfig;
+  filter?: FilterConfig;
+  integrator?: IntegratorConfig;
   name: string;
   scenes: SceneConfig[];
   settings: Settings;
@@ -213,6 +411,7 @@ export interface CameraConfig {
   focus_dist?: number;
   look_at?: JVec3F;
   look_from?: JVec3F;
+  medium?: MediumConfig | null;
   time0?: number;
   time1?: number;
   vertical_fov?: number;
@@ -226,12 +425,57 @@ export interface EnvironmentConfig {
 export interface AreaLightConfig {
   [k: string]: unknown | undefined;
 }
+export interface MediumInterfaceConfig {
+  inside?: MediumConfig | null;
+  outside?: MediumConfig | null;
+  [k: string]: unknown | undefined;
+}
 export interface Settings {
+  adaptive?: AdaptiveSettings | null;
+  aovs?: Aov[];
+  denoise?: DenoiseSettings | null;
+  exposure?: number;
   height: number;
   max_depth: number;
+  mis_heuristic?: MisHeuristic;
   mis_weight?: number;
   nsamples: number;
   output_dir: string;
+  output_format?: OutputFormat;
+  photon_mapping?: PhotonMappingSettings;
+  progressive?: ProgressiveSettings | null;
+  sampler?: SamplerConfig;
+  seed?: number;
+  spectral?: boolean;
+  tone_map?: ToneMapConfig;
   width: number;
+  working_space?: WorkingSpace;
+  [k: string]: unknown | undefined;
+}
+export interface AdaptiveSettings {
+  heatmap?: boolean;
+  max_samples?: number | null;
+  min_samples?: number;
+  threshold?: number;
+  [k: string]: unknown | undefined;
+}
+export interface DenoiseSettings {
+  albedo_sigma?: number;
+  keep_noisy?: boolean;
+  normal_sigma?: number;
+  patch_radius?: number;
+  radius?: number;
+  strength?: number;
+  [k: string]: unknown | undefined;
+}
+export interface PhotonMappingSettings {
+  alpha?: number;
+  initial_radius?: number | null;
+  photons_per_pass?: number;
+  [k: string]: unknown | undefined;
+}
+export interface ProgressiveSettings {
+  samples_per_pass?: number;
+  time_budget?: number | null;
   [k: string]: unknown | undefined;
 }
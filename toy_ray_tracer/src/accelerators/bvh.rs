@@ -2,6 +2,7 @@ use crate::core::PrimitiveContainer;
 use crate::core::Ray;
 use crate::core::AABB;
 use crate::core::{PrimitivePtr, SurfaceInteraction};
+use crate::stats::{self, Counter};
 use crate::utils::random;
 use std::cmp::Ordering;
 
@@ -83,19 +84,43 @@ impl BVHAccel {
     }
 }
 
-impl PrimitiveContainer for BVHAccel {
-    fn intersect(&self, ray: &Ray, t_min: f32, mut t_max: f32) -> Option<SurfaceInteraction> {
+// nodes whose box a query tested and primitives it tested, summed into the stats once per query
+#[derive(Default)]
+struct Traversal {
+    nodes: u64,
+    primitives: u64,
+}
+
+impl Traversal {
+    fn finish(self) {
+        stats::add(Counter::BvhNodeVisits, self.nodes);
+        stats::add(Counter::PrimitiveTests, self.primitives);
+    }
+}
+
+impl BVHAccel {
+    fn intersect_impl(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        mut t_max: f32,
+        traversal: &mut Traversal,
+    ) -> Option<SurfaceInteraction<'_>> {
+        traversal.nodes += 1;
         if !self.bbox.hit(&ray, t_min, t_max) {
             return None;
         }
         match &self.tree {
-            BVHNode::Leaf(leaf) => leaf.intersect(&ray, t_min, t_max),
+            BVHNode::Leaf(leaf) => {
+                traversal.primitives += 1;
+                leaf.intersect(&ray, t_min, t_max)
+            }
             BVHNode::Branch { left, right } => {
-                let left = left.intersect(&ray, t_min, t_max);
+                let left = left.intersect_impl(&ray, t_min, t_max, traversal);
                 if let Some(l) = &left {
                     t_max = l.t_hit
                 };
-                let right = right.intersect(&ray, t_min, t_max);
+                let right = right.intersect_impl(&ray, t_min, t_max, traversal);
                 if right.is_some() {
                     right
                 } else {
@@ -105,6 +130,45 @@ impl PrimitiveContainer for BVHAccel {
         }
     }
 
+    fn intersect_p_impl(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        traversal: &mut Traversal,
+    ) -> bool {
+        traversal.nodes += 1;
+        if !self.bbox.hit(ray, t_min, t_max) {
+            return false;
+        }
+        match &self.tree {
+            BVHNode::Leaf(leaf) => {
+                traversal.primitives += 1;
+                leaf.intersect_p(ray, t_min, t_max)
+            }
+            BVHNode::Branch { left, right } => {
+                left.intersect_p_impl(ray, t_min, t_max, traversal)
+                    || right.intersect_p_impl(ray, t_min, t_max, traversal)
+            }
+        }
+    }
+}
+
+impl PrimitiveContainer for BVHAccel {
+    fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<SurfaceInteraction> {
+        let mut traversal = Traversal::default();
+        let si = self.intersect_impl(ray, t_min, t_max, &mut traversal);
+        traversal.finish();
+        si
+    }
+
+    fn intersect_p(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        let mut traversal = Traversal::default();
+        let hit = self.intersect_p_impl(ray, t_min, t_max, &mut traversal);
+        traversal.finish();
+        hit
+    }
+
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
         Some(self.bbox)
     }
@@ -1,3 +1,5 @@
+pub mod validation;
+
 use std::f32::consts::{FRAC_1_PI, PI};
 
 use nalgebra::Matrix3;
@@ -35,7 +37,11 @@ impl Bxdf for LambertianReflection {
     }
 
     // $P_d / \pi$
-    fn f(&self, _wi: &Vec3f, _wo: &Vec3f, _normal: &Vec3f) -> Vec3f {
+    fn f(&self, wi: &Vec3f, wo: &Vec3f, normal: &Vec3f) -> Vec3f {
+        // reflection only
+        if !vec3::is_same_hemisphere(wi, wo, normal) {
+            return Vec3f::zeros();
+        }
         return self.albedo * FRAC_1_PI;
     }
 
@@ -48,6 +54,46 @@ impl Bxdf for LambertianReflection {
     }
 }
 
+// light leaving a dielectric boundary diffusely from below, e.g. at the end of a subsurface
+// walk. The Fresnel transmission is normalized to keep the energy, see pbrt's `SeparableBSSRDF`
+pub struct NormalizedFresnelTransmission {
+    eta: f32,
+    // $1 - 2 F_{m1}(1 / \eta)$, the cosine weighted transmission over the hemisphere
+    c: f32,
+}
+
+impl NormalizedFresnelTransmission {
+    pub fn new(eta: f32) -> Self {
+        Self {
+            eta,
+            c: 1.0 - 2.0 * fresnel_moment1(1.0 / eta),
+        }
+    }
+}
+
+impl Bxdf for NormalizedFresnelTransmission {
+    fn is_delta(&self) -> bool {
+        false
+    }
+
+    // $(1 - F_r(\cos\theta_i)) / (c \pi)$
+    fn f(&self, wi: &Vec3f, wo: &Vec3f, normal: &Vec3f) -> Vec3f {
+        if !vec3::is_same_hemisphere(wi, wo, normal) {
+            return Vec3f::zeros();
+        }
+        let cos_wi = wi.dot(normal).abs();
+        vec3::scalar((1.0 - fresnel_dielectric(self.eta, cos_wi)) / (self.c * PI))
+    }
+
+    fn sample_wi(&self, _wo: &Vec3f, normal: &Vec3f) -> Vec3f {
+        sample::sample_hemisphere_cos_wi(normal)
+    }
+
+    fn sample_pdf(&self, wi: &Vec3f, _wo: &Vec3f, normal: &Vec3f) -> f32 {
+        sample::sample_hemisphere_cos_pdf(normal, wi)
+    }
+}
+
 pub struct NaiveDielectric {
     ni_over_nt: f32,
 }
@@ -63,8 +109,9 @@ impl Bxdf for NaiveDielectric {
         true
     }
 
-    fn f(&self, _wi: &Vec3f, _wo: &Vec3f, _normal: &Vec3f) -> Vec3f {
-        Vec3f::new(1.0, 1.0, 1.0)
+    // all light is reflected or refracted, cancels the cosine of `Bsdf::f_cos`
+    fn f(&self, wi: &Vec3f, _wo: &Vec3f, normal: &Vec3f) -> Vec3f {
+        vec3::scalar(1.0 / wi.dot(normal).abs())
     }
 
     fn sample_wi(&self, wo: &Vec3f, normal: &Vec3f) -> Vec3f {
@@ -74,7 +121,7 @@ impl Bxdf for NaiveDielectric {
         let cannot_refract = self.ni_over_nt * sin_theta > 1.0;
 
         let wi = if cannot_refract || vec3::schlick(cos_theta, self.ni_over_nt) > random::f32() {
-            vec3::reflect(&unit_direction, &normal)
+            vec3::reflect(&-unit_direction, &normal)
         } else {
             vec3::refract(&unit_direction, &normal, self.ni_over_nt)
         };
@@ -103,17 +150,18 @@ impl Bxdf for NaiveSpecularReflection {
     }
 
     fn f(&self, wi: &Vec3f, _wo: &Vec3f, normal: &Vec3f) -> Vec3f {
-        if wi.dot(normal) <= 0.0 {
+        let cos_wi = wi.dot(normal);
+        if cos_wi <= 0.0 {
             return Vec3f::zeros();
         }
 
-        self.albedo
+        self.albedo / cos_wi
     }
 
     fn sample_wi(&self, wo: &Vec3f, normal: &Vec3f) -> Vec3f {
         let mut reflected = vec3::reflect(&wo, normal);
         if self.fuzz > 0.0 {
-            reflected += self.fuzz * vec3::random_in_unit_sphere();
+            reflected = (reflected + self.fuzz * vec3::random_in_unit_sphere()).normalize();
         }
         reflected
     }
@@ -245,11 +293,12 @@ impl Bxdf for DeltaTransparentTransmission {
         let same_hemisphere = vec3::is_same_hemisphere(wi, wo, normal);
         let normal = get_up_normal(wo, normal);
 
+        // the directions are the mirrored and the straight through one, |cos wi| = cos wo
+        let cos_wo = normal.dot(wo);
         if same_hemisphere {
-            return vec3::scalar(1.0) * fresnel_dielectric(self.eta, wo.dot(&normal));
+            return vec3::scalar(1.0) * fresnel_dielectric(self.eta, cos_wo) / cos_wo;
         } else {
-            let cos_wo = normal.dot(wo);
-            return self.albedo * (1.0 - fresnel_dielectric(self.eta, cos_wo));
+            return self.albedo * (1.0 - fresnel_dielectric(self.eta, cos_wo)) / cos_wo;
         }
     }
 
@@ -315,6 +364,10 @@ impl Bxdf for GltfPbrBxdf {
 
         let cos_wi = normal.dot(wi);
         let cos_wo = normal.dot(&wo);
+        // grazing directions, the specular term would be 0 / 0
+        if cos_wi <= 0.0 || cos_wo <= 0.0 {
+            return Vec3f::zeros();
+        }
         let halfway = (wi + wo).normalize();
         let cos_half_wi = halfway.dot(wi);
         let cos_half = halfway.dot(&normal);
@@ -353,12 +406,19 @@ impl Bxdf for GltfPbrBxdf {
         return f_diffuse + f_specular;
     }
 
+    // the specular lobe with the probability of its fresnel term, like `sample_pdf`
     fn sample_wi(&self, wo: &Vec3f, normal: &Vec3f) -> Vec3f {
         let normal = get_up_normal(wo, normal);
 
+        let f = fresnel_schlick_v(&self.get_mixed_rf0(), normal.dot(wo)).mean();
+        if random::f32() >= f {
+            return sample::sample_hemisphere_cos_wi(&normal);
+        }
+
         let halfway = sample_microfacet(self.roughness, &normal);
         let wi = reflect(&wo, &halfway);
-        if !vec3::is_same_hemisphere(&wi, wo, &normal) {
+        // `f` is 0 on the horizon too
+        if wi.dot(&normal) <= 0.0 {
             return Vec3f::zeros();
         }
         return wi;
@@ -423,6 +483,16 @@ pub fn fresnel_dielectric(eta: f32, cos_wo: f32) -> f32 {
     return (rs * rs + rp * rp) / 2.0;
 }
 
+// $\int_0^1 F_r(\eta, \mu) \mu d\mu$, polynomial fit of pbrt
+pub fn fresnel_moment1(eta: f32) -> f32 {
+    let (eta2, eta3, eta4, eta5) = (eta.powi(2), eta.powi(3), eta.powi(4), eta.powi(5));
+    if eta < 1.0 {
+        0.45966 - 1.73965 * eta + 3.37668 * eta2 - 3.904945 * eta3 + 2.49277 * eta4 - 0.68441 * eta5
+    } else {
+        -4.61686 + 11.1136 * eta - 10.4646 * eta2 + 5.11455 * eta3 - 1.27198 * eta4 + 0.12746 * eta5
+    }
+}
+
 // microfacet distribution evaluation
 // @see http://graphicrants.blogspot.com/2013/08/specular-brdf-reference.html
 // @param halfway (wi + wo).normalize()
@@ -464,12 +534,12 @@ pub fn microfacet_shadowing1(roughness: f32, cos_d: f32, cos_half_d: f32) -> f32
     return 2.0 * cos_d.abs() / (cos_d.abs() + (roughness2 + (1.0 - roughness2) * cos_d2).sqrt());
 }
 
+// a halfway vector distributed like D(h) cos theta_h, see `sample_microfacet_pdf`
 pub fn sample_microfacet(roughness: f32, normal: &Vec3f) -> Vec3f {
-    let rand_x = random::f32();
-    let rand_y = random::f32();
+    let (rand_x, rand_y) = random::f32_2d();
 
     let phi = 2.0 * PI * rand_x;
-    let theta = f32::atan(roughness * (rand_y / (1.0 - rand_y).sqrt()));
+    let theta = f32::atan(roughness * (rand_y / (1.0 - rand_y)).sqrt());
     let local_half_vector = Vec3f::new(
         phi.cos() * theta.sin(),
         phi.sin() * theta.sin(),
@@ -0,0 +1,490 @@
+use std::f64::consts::PI;
+
+use nalgebra::Matrix3;
+
+use crate::core::{false_color, vec3, Bxdf, BxdfPtr, Image, Vec3f};
+use crate::utils::random;
+
+use super::{
+    DeltaTransparentTransmission, GltfPbrBxdf, LambertianReflection, NaiveDielectric,
+    NaiveSpecularReflection, NormalizedFresnelTransmission, TransparentTransmission,
+};
+
+// Statistical checks of the bxdfs. The chi-square test histograms the directions drawn by
+// `sample_wi` and compares them with `sample_pdf` integrated over the bins, the white furnace
+// test estimates the albedo of a white bxdf, which can't be above 1 and has to agree with the
+// albedo estimated from uniformly distributed directions
+
+// the sphere around the normal is divided evenly in cos theta and phi, all bins have the
+// same solid angle
+const COS_THETA_RES: usize = 16;
+const PHI_RES: usize = 32;
+// midpoints per bin and dimension to integrate the pdf
+const PDF_SUBDIV: usize = 16;
+// bins expecting fewer samples are pooled, chi-square is inaccurate for them
+const MIN_EXPECTED: f64 = 5.0;
+
+// the angles of wo tested, as cos theta
+pub const COS_WO: [f32; 3] = [0.95, 0.6, 0.2];
+
+pub struct BxdfCase {
+    pub name: &'static str,
+    pub bxdf: BxdfPtr,
+    // reflects or transmits all light, its albedo has to be 1
+    pub lossless: bool,
+}
+
+// every bxdf, white and with a few roughnesses
+pub fn cases() -> Vec<BxdfCase> {
+    let white = vec3::scalar(1.0);
+    let case = |name, bxdf: BxdfPtr, lossless| BxdfCase {
+        name,
+        bxdf,
+        lossless,
+    };
+
+    vec![
+        case(
+            "lambertian",
+            Box::new(LambertianReflection::new(white)),
+            true,
+        ),
+        case(
+            "subsurface_exit",
+            Box::new(NormalizedFresnelTransmission::new(1.33)),
+            true,
+        ),
+        case(
+            "naive_dielectric_enter",
+            Box::new(NaiveDielectric::new(1.0 / 1.5)),
+            true,
+        ),
+        case(
+            "naive_dielectric_exit",
+            Box::new(NaiveDielectric::new(1.5)),
+            true,
+        ),
+        case(
+            "naive_metal",
+            Box::new(NaiveSpecularReflection::new(white, 0.0)),
+            true,
+        ),
+        case(
+            "naive_metal_fuzz",
+            Box::new(NaiveSpecularReflection::new(white, 0.3)),
+            false,
+        ),
+        case(
+            "delta_transparent",
+            Box::new(DeltaTransparentTransmission::new(1.5, white)),
+            true,
+        ),
+        case(
+            "transparent_0.3",
+            Box::new(TransparentTransmission::new(1.5, 0.3, white)),
+            false,
+        ),
+        case(
+            "transparent_0.7",
+            Box::new(TransparentTransmission::new(1.5, 0.7, white)),
+            false,
+        ),
+        case(
+            "gltf_pbr_dielectric",
+            Box::new(GltfPbrBxdf::new(1.5, white, 0.5, 0.0)),
+            false,
+        ),
+        case(
+            "gltf_pbr_metal",
+            Box::new(GltfPbrBxdf::new(1.5, white, 0.3, 1.0)),
+            false,
+        ),
+        case(
+            "gltf_pbr_mixed",
+            Box::new(GltfPbrBxdf::new(1.5, white, 0.8, 0.5)),
+            false,
+        ),
+    ]
+}
+
+// wo at `cos_theta` to the normal
+pub fn wo(cos_theta: f32) -> Vec3f {
+    frame() * Vec3f::new((1.0 - cos_theta * cos_theta).sqrt(), 0.0, cos_theta)
+}
+
+// tilted, bxdfs that only work around +z fail
+fn normal() -> Vec3f {
+    Vec3f::new(0.48, -0.6, 0.64)
+}
+
+// local directions around the normal to world ones
+fn frame() -> Matrix3<f32> {
+    vec3::onb_fromz(&normal())
+}
+
+pub struct ChiSquareResult {
+    // samples per bin, row by row from cos theta = 1 and phi = 0
+    pub observed: Vec<f64>,
+    pub expected: Vec<f64>,
+    pub chi2: f64,
+    pub dof: usize,
+    // 0 when samples fall where the pdf is 0
+    pub p_value: f64,
+}
+
+impl ChiSquareResult {
+    pub fn histogram_image(&self) -> Image {
+        bins_image(&self.observed, self.max())
+    }
+
+    pub fn pdf_image(&self) -> Image {
+        bins_image(&self.expected, self.max())
+    }
+
+    // both images use the same scale
+    fn max(&self) -> f64 {
+        self.observed
+            .iter()
+            .chain(&self.expected)
+            .fold(0.0, |a, b| a.max(*b))
+    }
+}
+
+pub fn chi_square(bxdf: &dyn Bxdf, wo: &Vec3f, nsamples: usize) -> ChiSquareResult {
+    let normal = normal();
+    let frame = frame();
+    let to_local = frame.transpose();
+
+    let mut observed = vec![0.0; COS_THETA_RES * PHI_RES];
+    for _ in 0..nsamples {
+        let wi = bxdf.sample_wi(wo, &normal);
+        if vec3::is_near_zero(&wi) {
+            continue;
+        }
+        observed[bin(&(to_local * wi.normalize()))] += 1.0;
+    }
+
+    let mut expected = vec![0.0; COS_THETA_RES * PHI_RES];
+    let cos_step = 2.0 / (COS_THETA_RES * PDF_SUBDIV) as f64;
+    let phi_step = 2.0 * PI / (PHI_RES * PDF_SUBDIV) as f64;
+    for i in 0..COS_THETA_RES * PDF_SUBDIV {
+        for j in 0..PHI_RES * PDF_SUBDIV {
+            let cos_theta = 1.0 - (i as f64 + 0.5) * cos_step;
+            let phi = (j as f64 + 0.5) * phi_step;
+            let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+            let wi = frame
+                * Vec3f::new(
+                    (sin_theta * phi.cos()) as f32,
+                    (sin_theta * phi.sin()) as f32,
+                    cos_theta as f32,
+                );
+            let pdf = bxdf.sample_pdf(&wi, wo, &normal) as f64;
+            expected[(i / PDF_SUBDIV) * PHI_RES + j / PDF_SUBDIV] +=
+                pdf * cos_step * phi_step * nsamples as f64;
+        }
+    }
+
+    let mut chi2 = 0.0;
+    let mut cells = 0;
+    let mut outside = false;
+    let (mut pooled_observed, mut pooled_expected) = (0.0, 0.0);
+    for (o, e) in observed.iter().zip(&expected) {
+        if *e <= 0.0 {
+            outside |= *o > 0.0;
+        } else if *e < MIN_EXPECTED {
+            pooled_observed += o;
+            pooled_expected += e;
+        } else {
+            chi2 += (o - e) * (o - e) / e;
+            cells += 1;
+        }
+    }
+    if pooled_expected > 0.0 {
+        chi2 += (pooled_observed - pooled_expected).powi(2) / pooled_expected;
+        cells += 1;
+    }
+
+    let dof = cells.max(2) - 1;
+    let p_value = if outside {
+        0.0
+    } else {
+        gamma_q(dof as f64 / 2.0, chi2 / 2.0)
+    };
+    ChiSquareResult {
+        observed,
+        expected,
+        chi2,
+        dof,
+        p_value,
+    }
+}
+
+// the smallest p-value accepted when running `ntests` tests, so that all of them pass with
+// a probability of 99% (Sidak correction)
+pub fn significance(ntests: usize) -> f64 {
+    1.0 - 0.99_f64.powf(1.0 / ntests.max(1) as f64)
+}
+
+// `wi` around +z
+fn bin(wi: &Vec3f) -> usize {
+    // in f64, 1 - cos theta of directions just above the horizon would round to 1 in f32
+    let i = ((1.0 - wi.z as f64) / 2.0 * COS_THETA_RES as f64) as usize;
+    let mut phi = (wi.y as f64).atan2(wi.x as f64);
+    if phi < 0.0 {
+        phi += 2.0 * PI;
+    }
+    let j = (phi / (2.0 * PI) * PHI_RES as f64) as usize;
+    i.min(COS_THETA_RES - 1) * PHI_RES + j.min(PHI_RES - 1)
+}
+
+fn bins_image(bins: &[f64], max: f64) -> Image {
+    const SCALE: usize = 8;
+    let (width, height) = (PHI_RES * SCALE, COS_THETA_RES * SCALE);
+    let mut img = Image::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let v = bins[(y / SCALE) * PHI_RES + x / SCALE] / max.max(1e-12);
+            img.set_pixel(y * width + x, false_color(v as f32));
+        }
+    }
+    img
+}
+
+// the regularized upper incomplete gamma function Q(a, x), after Numerical Recipes
+fn gamma_q(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let prefactor = (-x + a * x.ln() - ln_gamma(a)).exp();
+
+    if x < a + 1.0 {
+        // series of P(a, x)
+        let (mut ap, mut del) = (a, 1.0 / a);
+        let mut sum = del;
+        for _ in 0..1000 {
+            ap += 1.0;
+            del *= x / ap;
+            sum += del;
+            if del.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        (1.0 - sum * prefactor).max(0.0)
+    } else {
+        // continued fraction with the modified Lentz method
+        const TINY: f64 = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / TINY;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..1000 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < TINY {
+                d = TINY;
+            }
+            c = b + an / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            d = 1.0 / d;
+            let del = d * c;
+            h *= del;
+            if (del - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        prefactor * h
+    }
+}
+
+// Lanczos approximation of ln(gamma(x))
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFS: [f64; 6] = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.001_208_650_973_866_179,
+        -0.000_005_395_239_384_953,
+    ];
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let mut ser = 1.000_000_000_190_015;
+    let mut y = x;
+    for c in COEFFS {
+        y += 1.0;
+        ser += c / y;
+    }
+    -tmp + (2.506_628_274_631_000_5 * ser / x).ln()
+}
+
+pub struct FurnaceResult {
+    // albedo estimated with `sample_wi` and `sample_pdf`, and its standard error
+    pub albedo: f64,
+    pub std_error: f64,
+    // estimated from uniformly distributed directions, not for delta bxdfs
+    pub uniform: Option<(f64, f64)>,
+}
+
+impl FurnaceResult {
+    pub fn passed(&self, lossless: bool) -> bool {
+        // a few standard errors and a bit for the float precision of the bxdfs
+        let tolerance = |std_error: f64| 4.0 * std_error + 1e-3;
+
+        let conserves_energy = self.albedo <= 1.0 + tolerance(self.std_error);
+        let lossless_ok = !lossless || (self.albedo - 1.0).abs() <= tolerance(self.std_error);
+        let uniform_ok = match self.uniform {
+            Some((albedo, std_error)) => {
+                (self.albedo - albedo).abs() <= tolerance(self.std_error.hypot(std_error))
+            }
+            None => true,
+        };
+        conserves_energy && lossless_ok && uniform_ok
+    }
+}
+
+pub fn white_furnace(bxdf: &dyn Bxdf, wo: &Vec3f, nsamples: usize) -> FurnaceResult {
+    let normal = normal();
+
+    let (albedo, std_error) = mean_and_error(nsamples, || {
+        let wi = bxdf.sample_wi(wo, &normal);
+        if vec3::is_near_zero(&wi) {
+            return 0.0;
+        }
+        let wi = wi.normalize();
+        let pdf = bxdf.sample_pdf(&wi, wo, &normal);
+        if pdf <= 0.0 {
+            return 0.0;
+        }
+        bxdf.f(&wi, wo, &normal).mean() * wi.dot(&normal).abs() / pdf
+    });
+
+    let uniform = if bxdf.is_delta() {
+        None
+    } else {
+        Some(mean_and_error(nsamples, || {
+            let wi = vec3::random_unit_vector();
+            bxdf.f(&wi, wo, &normal).mean() * wi.dot(&normal).abs() * 4.0 * PI as f32
+        }))
+    };
+
+    FurnaceResult {
+        albedo,
+        std_error,
+        uniform,
+    }
+}
+
+fn mean_and_error(nsamples: usize, mut estimate: impl FnMut() -> f32) -> (f64, f64) {
+    let (mut sum, mut sum_sq) = (0.0, 0.0);
+    for _ in 0..nsamples {
+        let v = estimate() as f64;
+        sum += v;
+        sum_sq += v * v;
+    }
+    let n = nsamples.max(2) as f64;
+    let mean = sum / n;
+    let variance = (sum_sq / n - mean * mean).max(0.0) * n / (n - 1.0);
+    (mean, (variance / n).sqrt())
+}
+
+pub struct BxdfReport {
+    pub name: String,
+    pub cos_wo: f32,
+    pub lossless: bool,
+    // not for delta bxdfs, their directions can't be histogrammed
+    pub chi_square: Option<ChiSquareResult>,
+    pub furnace: FurnaceResult,
+}
+
+impl BxdfReport {
+    pub fn passed(&self, significance: f64) -> bool {
+        let chi_square_ok = match &self.chi_square {
+            Some(chi_square) => chi_square.p_value >= significance,
+            None => true,
+        };
+        chi_square_ok && self.furnace.passed(self.lossless)
+    }
+
+    // one line, e.g. for the console
+    pub fn format(&self, significance: f64) -> String {
+        let chi_square = match &self.chi_square {
+            Some(c) => format!("chi2 {:>8.1} dof {:>3} p {:.4}", c.chi2, c.dof, c.p_value),
+            None => format!("{:<28}", "delta"),
+        };
+        let uniform = match self.furnace.uniform {
+            Some((albedo, std_error)) => format!("  uniform {:.4} ± {:.4}", albedo, std_error),
+            None => String::new(),
+        };
+        format!(
+            "{:<24} cos wo {:.2}  {}  {}  albedo {:.4} ± {:.4}{}",
+            self.name,
+            self.cos_wo,
+            if self.passed(significance) {
+                "ok  "
+            } else {
+                "FAIL"
+            },
+            chi_square,
+            self.furnace.albedo,
+            self.furnace.std_error,
+            uniform
+        )
+    }
+}
+
+// the tests of the cases whose name contains `filter` at every angle of `COS_WO`, the random
+// numbers are seeded, the results only depend on `nsamples`
+pub fn run(filter: Option<&str>, nsamples: usize) -> Vec<BxdfReport> {
+    random::seed(0);
+
+    let mut reports = Vec::new();
+    for case in cases() {
+        if filter.is_some_and(|filter| !case.name.contains(filter)) {
+            continue;
+        }
+        for cos_wo in COS_WO {
+            let wo = wo(cos_wo);
+            let chi_square = if case.bxdf.is_delta() {
+                None
+            } else {
+                Some(chi_square(case.bxdf.as_ref(), &wo, nsamples))
+            };
+            reports.push(BxdfReport {
+                name: case.name.to_string(),
+                cos_wo,
+                lossless: case.lossless,
+                chi_square,
+                furnace: white_furnace(case.bxdf.as_ref(), &wo, nsamples),
+            });
+        }
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{gamma_q, run, significance};
+
+    #[test]
+    fn test_gamma_q() {
+        // p-values of chi-square with 1 and 10 degrees of freedom
+        assert!((gamma_q(0.5, 3.841 / 2.0) - 0.05).abs() < 1e-4);
+        assert!((gamma_q(5.0, 18.307 / 2.0) - 0.05).abs() < 1e-4);
+        assert!((gamma_q(5.0, 2.558 / 2.0) - 0.99).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_bxdfs() {
+        let reports = run(None, 100_000);
+        let significance = significance(reports.len());
+        let failed: Vec<String> = reports
+            .iter()
+            .filter(|r| !r.passed(significance))
+            .map(|r| r.format(significance))
+            .collect();
+        assert!(failed.is_empty(), "\n{}", failed.join("\n"));
+    }
+}
@@ -0,0 +1,391 @@
+use std::f32::consts::PI;
+use std::path::Path;
+
+use anyhow::{ensure, Result};
+
+use crate::core::{
+    false_color, spectral, srgb_eotf, srgb_oetf, vec3, Color3, HdrImage, Image, OutputFormat, Vec3f,
+};
+
+// pixels per degree of visual angle FLIP assumes, a 0.7 m wide 4k monitor seen from 0.7 m
+const PIXELS_PER_DEGREE: f32 = 67.0;
+
+// Errors of an image against a reference. MSE, RMSE and relMSE are computed on the linear
+// values, PSNR, SSIM and FLIP on the displayed ones, clamped to [0, 1] and sRGB encoded
+#[derive(Debug, Clone, Copy)]
+pub struct ImageMetrics {
+    pub mse: f32,
+    pub rmse: f32,
+    // squared errors relative to the squared reference values, noise in dark regions counts
+    // as much as in bright ones
+    pub rel_mse: f32,
+    pub psnr: f32,
+    pub ssim: f32,
+    pub flip: f32,
+}
+
+// linear values of an image file, 8 and 16 bit images are taken as sRGB encoded
+pub fn load_linear<P: AsRef<Path>>(path: P) -> Result<HdrImage> {
+    let path = path.as_ref();
+    let mut image = HdrImage::load(path)?;
+    let linear = matches!(
+        OutputFormat::from_path(path).ok(),
+        Some(OutputFormat::Exr) | Some(OutputFormat::Pfm) | Some(OutputFormat::Hdr)
+    );
+    if !linear {
+        for idx in 0..image.width() * image.height() {
+            image.set_pixel(idx, image.get_pixel(idx).map(srgb_eotf));
+        }
+    }
+    Ok(image)
+}
+
+// the metrics of `test` and its per pixel FLIP error, 0 where it can't be told from `reference`
+pub fn compare(test: &HdrImage, reference: &HdrImage) -> Result<(ImageMetrics, Vec<f32>)> {
+    let (width, height) = (reference.width(), reference.height());
+    ensure!(
+        test.width() == width && test.height() == height,
+        "image is {}x{}, the reference {}x{}",
+        test.width(),
+        test.height(),
+        width,
+        height
+    );
+    ensure!(width * height > 0, "images are empty");
+
+    let n = width * height;
+    let (mut se, mut rel_se) = (0.0f64, 0.0f64);
+    for idx in 0..n {
+        let (t, r) = (test.get_pixel(idx), reference.get_pixel(idx));
+        for c in 0..3 {
+            let d = (t[c] - r[c]) as f64;
+            se += d * d;
+            rel_se += d * d / (r[c] as f64 * r[c] as f64 + 1e-2);
+        }
+    }
+    let mse = (se / (3 * n) as f64) as f32;
+    let rel_mse = (rel_se / (3 * n) as f64) as f32;
+
+    let display = |image: &HdrImage| -> Vec<Color3> {
+        (0..n)
+            .map(|idx| image.get_pixel(idx).map(|v| srgb_oetf(v.clamp(0.0, 1.0))))
+            .collect()
+    };
+    let (test, reference) = (display(test), display(reference));
+
+    let mut display_se = 0.0f64;
+    for (t, r) in test.iter().zip(reference.iter()) {
+        display_se += (t - r).norm_squared() as f64;
+    }
+    let display_mse = display_se / (3 * n) as f64;
+    let psnr = (10.0 * (1.0 / display_mse).log10()) as f32;
+
+    let mut ssim = 0.0;
+    for c in 0..3 {
+        let t: Vec<f32> = test.iter().map(|p| p[c]).collect();
+        let r: Vec<f32> = reference.iter().map(|p| p[c]).collect();
+        ssim += mean_ssim(&t, &r, width, height) / 3.0;
+    }
+
+    let flip = flip_error(&test, &reference, width, height);
+    let metrics = ImageMetrics {
+        mse,
+        rmse: mse.sqrt(),
+        rel_mse,
+        psnr,
+        ssim,
+        flip: flip.iter().map(|&e| e as f64).sum::<f64>() as f32 / n as f32,
+    };
+    Ok((metrics, flip))
+}
+
+// the per pixel errors in [0, 1] in false color, blue for none and red for the largest
+pub fn error_image(errors: &[f32], width: usize, height: usize) -> Image {
+    let mut img = Image::new(width, height);
+    for (idx, e) in errors.iter().enumerate() {
+        img.set_pixel(idx, false_color(*e));
+    }
+    img
+}
+
+// `data` convolved with `kx` along the rows and `ky` along the columns, the borders are
+// extended. Kernels have an odd length and are centered
+fn convolve(data: &[f32], width: usize, height: usize, kx: &[f32], ky: &[f32]) -> Vec<f32> {
+    let (rx, ry) = ((kx.len() / 2) as isize, (ky.len() / 2) as isize);
+    let clamp = |v: isize, n: usize| v.clamp(0, n as isize - 1) as usize;
+
+    let mut rows = vec![0.0; data.len()];
+    for y in 0..height {
+        for x in 0..width {
+            rows[y * width + x] = kx
+                .iter()
+                .enumerate()
+                .map(|(k, w)| w * data[y * width + clamp(x as isize + k as isize - rx, width)])
+                .sum();
+        }
+    }
+    let mut out = vec![0.0; data.len()];
+    for y in 0..height {
+        for x in 0..width {
+            out[y * width + x] = ky
+                .iter()
+                .enumerate()
+                .map(|(k, w)| w * rows[clamp(y as isize + k as isize - ry, height) * width + x])
+                .sum();
+        }
+    }
+    out
+}
+
+fn gaussian_kernel(sigma: f32, radius: usize) -> Vec<f32> {
+    let kernel: Vec<f32> = (0..=2 * radius)
+        .map(|k| {
+            let x = k as f32 - radius as f32;
+            (-x * x / (2.0 * sigma * sigma)).exp()
+        })
+        .collect();
+    let sum: f32 = kernel.iter().sum();
+    kernel.iter().map(|w| w / sum).collect()
+}
+
+// Wang et al. 2004, over 11x11 gaussian windows of sigma 1.5
+fn mean_ssim(t: &[f32], r: &[f32], width: usize, height: usize) -> f32 {
+    const C1: f32 = 0.01 * 0.01;
+    const C2: f32 = 0.03 * 0.03;
+
+    let k = gaussian_kernel(1.5, 5);
+    let blur = |v: Vec<f32>| convolve(&v, width, height, &k, &k);
+    let mu_t = blur(t.to_vec());
+    let mu_r = blur(r.to_vec());
+    let tt = blur(t.iter().map(|v| v * v).collect());
+    let rr = blur(r.iter().map(|v| v * v).collect());
+    let tr = blur(t.iter().zip(r.iter()).map(|(a, b)| a * b).collect());
+
+    let mut sum = 0.0f64;
+    for i in 0..t.len() {
+        let (mt, mr) = (mu_t[i], mu_r[i]);
+        let var_t = tt[i] - mt * mt;
+        let var_r = rr[i] - mr * mr;
+        let cov = tr[i] - mt * mr;
+        let s = ((2.0 * mt * mr + C1) * (2.0 * cov + C2))
+            / ((mt * mt + mr * mr + C1) * (var_t + var_r + C2));
+        sum += s as f64;
+    }
+    (sum / t.len() as f64) as f32
+}
+
+fn rgb_to_xyz(c: &Color3) -> Vec3f {
+    Vec3f::new(
+        0.412_456_4 * c.x + 0.357_576_1 * c.y + 0.180_437_5 * c.z,
+        0.212_672_9 * c.x + 0.715_152_2 * c.y + 0.072_175 * c.z,
+        0.019_333_9 * c.x + 0.119_192 * c.y + 0.950_304_1 * c.z,
+    )
+}
+
+// D65
+const WHITE_XYZ: [f32; 3] = [0.950_47, 1.0, 1.088_83];
+
+// CIELAB with the Hunt adjustment of FLIP, colors look less saturated when they're dark
+fn hunt_lab(xyz: &Vec3f) -> Vec3f {
+    let f = |t: f32| {
+        const DELTA: f32 = 6.0 / 29.0;
+        if t > DELTA * DELTA * DELTA {
+            t.cbrt()
+        } else {
+            t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+        }
+    };
+    let (fx, fy, fz) = (
+        f(xyz.x / WHITE_XYZ[0]),
+        f(xyz.y / WHITE_XYZ[1]),
+        f(xyz.z / WHITE_XYZ[2]),
+    );
+    let l = 116.0 * fy - 16.0;
+    Vec3f::new(
+        l,
+        0.01 * l * 500.0 * (fx - fy),
+        0.01 * l * 200.0 * (fy - fz),
+    )
+}
+
+fn hyab(a: &Vec3f, b: &Vec3f) -> f32 {
+    (a.x - b.x).abs() + ((a.y - b.y).powi(2) + (a.z - b.z).powi(2)).sqrt()
+}
+
+// LDR-FLIP of Andersson et al. 2020, simplified: the images are filtered by the contrast
+// sensitivity of the eye per opponent channel and compared in the Hunt adjusted HyAB color
+// distance, which is then amplified where edges and points differ
+fn flip_error(test: &[Color3], reference: &[Color3], width: usize, height: usize) -> Vec<f32> {
+    const QC: f32 = 0.7;
+    const QF: f32 = 0.5;
+    const PC: f32 = 0.4;
+    const PT: f32 = 0.95;
+
+    // the contrast sensitivity filters of the achromatic, red-green and blue-yellow channels,
+    // sums of gaussians a * sqrt(pi / b) * exp(-pi^2 x^2 / b), x in degrees
+    let csf: [&[(f32, f32)]; 3] = [
+        &[(1.0, 0.0047)],
+        &[(1.0, 0.0053)],
+        &[(34.1, 0.04), (13.5, 0.025)],
+    ];
+    let radius = (3.0 * (0.04 / (2.0 * PI * PI)).sqrt() * PIXELS_PER_DEGREE).ceil() as usize;
+
+    let filter = |image: &[Color3]| -> Vec<Vec3f> {
+        // linearized YCxCz
+        let ycxcz: Vec<Vec3f> = image
+            .iter()
+            .map(|c| {
+                let xyz = rgb_to_xyz(&c.map(srgb_eotf));
+                let (x, y, z) = (
+                    xyz.x / WHITE_XYZ[0],
+                    xyz.y / WHITE_XYZ[1],
+                    xyz.z / WHITE_XYZ[2],
+                );
+                Vec3f::new(116.0 * y - 16.0, 500.0 * (x - y), 200.0 * (y - z))
+            })
+            .collect();
+
+        let mut filtered = vec![Vec3f::zeros(); image.len()];
+        for (c, gaussians) in csf.iter().enumerate() {
+            let channel: Vec<f32> = ycxcz.iter().map(|v| v[c]).collect();
+            let mut sum = vec![0.0; image.len()];
+            let mut norm = 0.0;
+            for &(a, b) in gaussians.iter() {
+                let k: Vec<f32> = (0..=2 * radius)
+                    .map(|i| {
+                        let x = (i as f32 - radius as f32) / PIXELS_PER_DEGREE;
+                        (-PI * PI * x * x / b).exp()
+                    })
+                    .collect();
+                let weight = a * (PI / b).sqrt();
+                norm += weight * k.iter().sum::<f32>().powi(2);
+                for (s, v) in sum
+                    .iter_mut()
+                    .zip(convolve(&channel, width, height, &k, &k))
+                {
+                    *s += weight * v;
+                }
+            }
+            for (f, s) in filtered.iter_mut().zip(sum) {
+                f[c] = s / norm;
+            }
+        }
+
+        filtered
+            .iter()
+            .map(|v| {
+                let y = (v.x + 16.0) / 116.0;
+                let xyz = Vec3f::new(
+                    (v.y / 500.0 + y) * WHITE_XYZ[0],
+                    y * WHITE_XYZ[1],
+                    (y - v.z / 200.0) * WHITE_XYZ[2],
+                );
+                let rgb = spectral::xyz_to_rgb(&xyz).map(|v| v.clamp(0.0, 1.0));
+                hunt_lab(&rgb_to_xyz(&rgb))
+            })
+            .collect()
+    };
+    let (test_lab, reference_lab) = (filter(test), filter(reference));
+
+    // the largest distance is the one of green and blue
+    let green = hunt_lab(&rgb_to_xyz(&Color3::new(0.0, 1.0, 0.0)));
+    let blue = hunt_lab(&rgb_to_xyz(&Color3::new(0.0, 0.0, 1.0)));
+    let cmax = hyab(&green, &blue).powf(QC);
+    let color_error = |t: &Vec3f, r: &Vec3f| {
+        let e = hyab(t, r).powf(QC);
+        if e < PC * cmax {
+            PT / (PC * cmax) * e
+        } else {
+            PT + (e - PC * cmax) / (cmax - PC * cmax) * (1.0 - PT)
+        }
+    };
+
+    // edges and points of the luminance, by first and second derivatives of a gaussian
+    let sigma = 0.5 * 0.082 * PIXELS_PER_DEGREE;
+    let r = (3.0 * sigma).ceil() as usize;
+    let gauss = gaussian_kernel(sigma, r);
+    let derivative = |f: &dyn Fn(f32) -> f32| -> Vec<f32> {
+        let k: Vec<f32> = (0..=2 * r)
+            .map(|i| {
+                let x = i as f32 - r as f32;
+                f(x) * (-x * x / (2.0 * sigma * sigma)).exp()
+            })
+            .collect();
+        // positive weights sum to 1, negative ones to -1
+        let pos: f32 = k.iter().filter(|w| **w > 0.0).sum();
+        let neg: f32 = -k.iter().filter(|w| **w < 0.0).sum::<f32>();
+        k.iter()
+            .map(|w| if *w > 0.0 { w / pos } else { w / neg })
+            .collect()
+    };
+    let edge = derivative(&|x| -x);
+    let point = derivative(&|x| x * x / (sigma * sigma) - 1.0);
+    let features = |image: &[Color3]| -> (Vec<f32>, Vec<f32>) {
+        let y: Vec<f32> = image
+            .iter()
+            .map(|c| vec3::luminance(&c.map(srgb_eotf)))
+            .collect();
+        let magnitude = |k: &[f32]| -> Vec<f32> {
+            let dx = convolve(&y, width, height, k, &gauss);
+            let dy = convolve(&y, width, height, &gauss, k);
+            dx.iter()
+                .zip(dy.iter())
+                .map(|(a, b)| (a * a + b * b).sqrt())
+                .collect()
+        };
+        (magnitude(&edge), magnitude(&point))
+    };
+    let (test_edges, test_points) = features(test);
+    let (reference_edges, reference_points) = features(reference);
+
+    (0..test.len())
+        .map(|i| {
+            let ec = color_error(&test_lab[i], &reference_lab[i]);
+            let ef = ((test_edges[i] - reference_edges[i])
+                .abs()
+                .max((test_points[i] - reference_points[i]).abs())
+                / 2f32.sqrt())
+            .powf(QF);
+            ec.powf(1.0 - ef).clamp(0.0, 1.0)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compare;
+    use crate::core::{Color3, HdrImage};
+    use crate::utils::random;
+
+    #[test]
+    fn test_compare() {
+        let (width, height) = (32, 24);
+        let mut reference = HdrImage::new(width, height);
+        for idx in 0..width * height {
+            let x = (idx % width) as f32 / width as f32;
+            reference.set_pixel(idx, Color3::new(x, 0.5, 1.0 - x));
+        }
+
+        let (same, errors) = compare(&reference, &reference).unwrap();
+        assert_eq!(same.mse, 0.0);
+        assert!(same.psnr.is_infinite());
+        assert!((same.ssim - 1.0).abs() < 1e-4);
+        assert!(same.flip.abs() < 1e-6 && errors.len() == width * height);
+
+        random::seed(1);
+        let noisy = |amount: f32| {
+            let mut image = reference.clone();
+            for idx in 0..width * height {
+                let noise = (random::f32() - 0.5) * amount;
+                image.set_pixel(idx, reference.get_pixel(idx).add_scalar(noise));
+            }
+            compare(&image, &reference).unwrap().0
+        };
+        let (a, b) = (noisy(0.05), noisy(0.2));
+        assert!(a.mse < b.mse && a.rel_mse < b.rel_mse && a.psnr > b.psnr);
+        assert!(a.ssim > b.ssim && b.ssim < 1.0);
+        assert!(a.flip < b.flip && a.flip > 0.0);
+
+        assert!(compare(&HdrImage::new(2, 2), &reference).is_err());
+    }
+}
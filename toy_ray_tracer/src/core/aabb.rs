@@ -25,9 +25,37 @@ impl AABB {
         }
         true
     }
+
+    // the part (t0, t1) of [t_min, t_max] where `ray` is inside the box
+    pub fn hit_range(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<(f32, f32)> {
+        let (mut t0, mut t1) = (t_min, t_max);
+        for a in 0..3 {
+            let inv_d = 1.0 / ray.direction()[a];
+            let near = (self.min[a] - ray.origin()[a]) * inv_d;
+            let far = (self.max[a] - ray.origin()[a]) * inv_d;
+            let (near, far) = if inv_d < 0.0 {
+                (far, near)
+            } else {
+                (near, far)
+            };
+            // NaN (origin on a slab with a zero direction) keeps the current range
+            t0 = if near > t0 { near } else { t0 };
+            t1 = if far < t1 { far } else { t1 };
+            if t1 <= t0 {
+                return None;
+            }
+        }
+        Some((t0, t1))
+    }
 }
 
 impl AABB {
+    // (center, radius) of a sphere enclosing the box
+    pub fn bounding_sphere(&self) -> (Vec3f, f32) {
+        let center = (self.min + self.max) * 0.5;
+        (center, (self.max - center).norm())
+    }
+
     pub fn union_point3(&self, point: Vec3f) -> AABB {
         let min = Vec3f::new(
             self.min.x.min(point.x),
@@ -0,0 +1,95 @@
+use super::{
+    sample_homogeneous, vec3, Bsdf, HenyeyGreenstein, Ray, Scene, Spectrum, SurfaceInteraction,
+    Vec3f,
+};
+use crate::bxdfs::NormalizedFresnelTransmission;
+
+// walks longer than this are dropped, like the light they carry got absorbed
+const MAX_WALK_STEPS: usize = 256;
+
+// Random walk subsurface scattering: below the surface is a homogeneous medium, a path
+// refracted into the surface scatters through it until it reaches the surface again and
+// leaves diffusely through the dielectric boundary from there
+pub struct Bssrdf {
+    eta: f32,
+    sigma_s: Spectrum,
+    sigma_t: Spectrum,
+    phase: HenyeyGreenstein,
+}
+
+impl Bssrdf {
+    // `albedo` is the color of a thick block of the material, i.e. the multiple scattering
+    // albedo, mapped to the single scattering albedo by the fit of Chiang et al. 2016,
+    // `mfp` the mean free path per channel in scene units and `eta` the index of refraction
+    pub fn new(albedo: &Spectrum, mfp: &Spectrum, eta: f32, g: f32) -> Self {
+        let single_albedo = albedo.map(|a| {
+            let a = a.clamp(0.0, 1.0);
+            1.0 - (a * (-5.09406 + a * (2.61188 - a * 4.31805))).exp()
+        });
+        let sigma_t = mfp.map(|d| 1.0 / d.max(1e-6));
+
+        Self {
+            eta,
+            sigma_s: single_albedo.component_mul(&sigma_t),
+            sigma_t,
+            phase: HenyeyGreenstein::new(g),
+        }
+    }
+
+    // follow a path entering the surface at `si` in direction `wi` until it leaves again.
+    // Returns where it leaves, facing outwards with `wo` along the normal, and the throughput
+    pub fn walk<'a>(
+        &self,
+        si: &SurfaceInteraction,
+        wi: &Vec3f,
+        scene: &'a Scene,
+        time: f32,
+    ) -> Option<(SurfaceInteraction<'a>, Spectrum)> {
+        let mut ray = Ray::new(si.point, wi.normalize(), time);
+        let mut beta = vec3::scalar(1.0);
+
+        for _ in 0..MAX_WALK_STEPS {
+            let hit = scene.intersect(&ray, 0.001, f32::MAX);
+            let t_max = hit.as_ref().map_or(f32::MAX, |hit| hit.t_hit);
+
+            // sampling the channels by throughput keeps the weights of long walks bounded
+            let (t, weight) = sample_homogeneous(&self.sigma_s, &self.sigma_t, &beta, &ray, t_max);
+            beta = beta.component_mul(&weight);
+            if vec3::is_black(&beta) {
+                return None;
+            }
+
+            match t {
+                Some(t) => {
+                    // the phase function is sampled exactly, its value cancels with the pdf
+                    let point = ray.point_at_parameter(t);
+                    let (wi, _) = self.phase.sample_p(&-ray.direction());
+                    ray = Ray::new(point, wi, time);
+                }
+                None => {
+                    let mut exit = hit?;
+                    let outward = -exit.wo;
+                    let normal = if exit.normal.dot(&outward) > 0.0 {
+                        exit.normal
+                    } else {
+                        -exit.normal
+                    };
+                    exit.normal = normal;
+                    exit.front_face = true;
+                    exit.wo = normal;
+                    return Some((exit, beta));
+                }
+            }
+        }
+
+        None
+    }
+
+    // diffuse transmission out of the surface at the exit of `walk`, less the part the
+    // boundary reflects back
+    pub fn exit_bsdf(&self, exit: &SurfaceInteraction) -> Bsdf {
+        let mut bsdf = Bsdf::new(exit.normal);
+        bsdf.set_raw(NormalizedFresnelTransmission::new(self.eta));
+        bsdf
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{core::vec3, core::Vec3f, core::Ray, utils};
+use crate::stats::{self, Counter};
+use crate::{
+    core::vec3, core::MediumPtr, core::Point2f, core::Point3f, core::Ray, core::Vec3f, utils,
+};
 use std::f32;
 
 pub struct Camera {
@@ -8,12 +11,29 @@ pub struct Camera {
     vertical: Vec3f,
     u: Vec3f,
     v: Vec3f,
+    w: Vec3f,
+    // area of the film at distance 1 from the lens
+    film_area: f32,
     time0: f32,
     time1: f32,
     lens_radius: f32,
+    // medium the camera is placed in
+    medium: Option<MediumPtr>,
     opt: CameraOpt,
 }
 
+// a point on the lens seen from somewhere in the scene
+pub struct CameraSample {
+    pub point: Point3f,
+    // normalized direction towards `point`
+    pub wi: Vec3f,
+    // solid angle pdf of `wi`
+    pub pdf: f32,
+    pub we: f32,
+    // film coordinates, as passed to `Camera::get_ray`
+    pub film: Point2f,
+}
+
 pub struct CameraOpt {
     pub look_from: Vec3f,
     pub look_at: Vec3f,
@@ -44,19 +64,37 @@ impl Camera {
             vertical: 2.0 * half_height * v,
             u,
             v,
+            w,
+            film_area: 4.0 * half_width * half_height / (opt.focus_dist * opt.focus_dist),
             time0: opt.time0,
             time1: opt.time1,
             lens_radius: opt.aperture / 2.0,
+            medium: None,
             opt,
         }
     }
 
     #[allow(dead_code)]
     pub fn set_aspect(&mut self, aspect: f32) {
-        *self = Camera::new(CameraOpt { aspect, ..self.opt })
+        let medium = self.medium.take();
+        *self = Camera::new(CameraOpt { aspect, ..self.opt }).with_medium(medium)
+    }
+
+    pub fn with_medium(mut self, medium: Option<MediumPtr>) -> Self {
+        self.medium = medium;
+        self
+    }
+
+    pub fn medium(&self) -> Option<&MediumPtr> {
+        self.medium.as_ref()
+    }
+
+    pub fn origin(&self) -> Vec3f {
+        self.origin
     }
 
     pub fn get_ray(&self, s: f32, t: f32) -> Ray {
+        stats::add(Counter::CameraRays, 1);
         let origin = if self.lens_radius == 0.0 {
             self.origin
         } else {
@@ -71,4 +109,83 @@ impl Camera {
             time,
         )
     }
+
+    fn lens_area(&self) -> f32 {
+        if self.lens_radius == 0.0 {
+            1.0
+        } else {
+            f32::consts::PI * self.lens_radius * self.lens_radius
+        }
+    }
+
+    // film coordinates and cosine to the view direction of a ray leaving the lens
+    fn film_point(&self, ray: &Ray) -> Option<(Point2f, f32)> {
+        let dir = ray.direction().normalize();
+        let cos_theta = -dir.dot(&self.w);
+        if cos_theta <= 0.0 {
+            return None;
+        }
+
+        let p_focus = ray.origin() + dir * (self.opt.focus_dist / cos_theta);
+        let offset = p_focus - self.lower_left_corner;
+        let s = offset.dot(&self.horizontal) / self.horizontal.norm_squared();
+        let t = offset.dot(&self.vertical) / self.vertical.norm_squared();
+        if !(0.0..1.0).contains(&s) || !(0.0..1.0).contains(&t) {
+            return None;
+        }
+
+        Some((Point2f::new(s, t), cos_theta))
+    }
+
+    // importance emitted along a ray leaving the lens and where it lands on the film
+    pub fn we(&self, ray: &Ray) -> Option<(Point2f, f32)> {
+        let (film, cos_theta) = self.film_point(ray)?;
+        let cos2_theta = cos_theta * cos_theta;
+        Some((
+            film,
+            1.0 / (self.film_area * self.lens_area() * cos2_theta * cos2_theta),
+        ))
+    }
+
+    // (pdf_pos, pdf_dir) of `get_ray` generating the ray
+    pub fn pdf_we(&self, ray: &Ray) -> (f32, f32) {
+        match self.film_point(ray) {
+            Some((_, cos_theta)) => (
+                1.0 / self.lens_area(),
+                1.0 / (self.film_area * cos_theta * cos_theta * cos_theta),
+            ),
+            None => (0.0, 0.0),
+        }
+    }
+
+    // sample a point on the lens seen from `point`, used to connect light paths to the camera
+    pub fn sample_wi(&self, point: &Point3f) -> Option<CameraSample> {
+        let lens_point = if self.lens_radius == 0.0 {
+            self.origin
+        } else {
+            let rd = self.lens_radius * vec3::random_in_unit_disk();
+            self.origin + self.u * rd.x + self.v * rd.y
+        };
+
+        let wi = lens_point - point;
+        let distance = wi.norm();
+        let wi = wi / distance;
+
+        let cos_theta = wi.dot(&self.w).abs();
+        let pdf = distance * distance / (cos_theta * self.lens_area());
+        let (film, we) = self.we(&Ray::new(lens_point, -wi, 0.0))?;
+
+        Some(CameraSample {
+            point: lens_point,
+            wi,
+            pdf,
+            we,
+            film,
+        })
+    }
+
+    // direction the camera is looking at
+    pub fn forward(&self) -> Vec3f {
+        -self.w
+    }
 }
@@ -0,0 +1,71 @@
+use nalgebra::Matrix3;
+
+// linear RGB between rec.709 (the primaries of sRGB) and the wider working spaces, ACEScg is
+// adapted from its D60 white point to D65 with Bradford
+pub fn rec709_to_rec2020() -> Matrix3<f32> {
+    Matrix3::new(
+        0.627404, 0.329283, 0.043313, //
+        0.069097, 0.919540, 0.011362, //
+        0.016391, 0.088013, 0.895595,
+    )
+}
+
+pub fn rec2020_to_rec709() -> Matrix3<f32> {
+    Matrix3::new(
+        1.660491, -0.587641, -0.072850, //
+        -0.124551, 1.1329, -0.008349, //
+        -0.018151, -0.100579, 1.11873,
+    )
+}
+
+pub fn rec709_to_acescg() -> Matrix3<f32> {
+    Matrix3::new(
+        0.613097, 0.339523, 0.047379, //
+        0.070194, 0.916354, 0.013452, //
+        0.020616, 0.109570, 0.869815,
+    )
+}
+
+pub fn acescg_to_rec709() -> Matrix3<f32> {
+    Matrix3::new(
+        1.704859, -0.621715, -0.083299, //
+        -0.130078, 1.140734, -0.010560, //
+        -0.023964, -0.128975, 1.153013,
+    )
+}
+
+// the piecewise sRGB transfer function, an encoded value in [0, 1] to linear
+pub fn srgb_eotf(v: f32) -> f32 {
+    if v <= 0.040_45 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        acescg_to_rec709, rec2020_to_rec709, rec709_to_acescg, rec709_to_rec2020, srgb_eotf,
+    };
+    use crate::core::{srgb_oetf, Color3};
+
+    #[test]
+    fn test_color_space() {
+        for v in [0.0, 0.002, 0.04, 0.2, 0.5, 1.0] {
+            assert!((srgb_eotf(srgb_oetf(v)) - v).abs() < 1e-5);
+        }
+
+        let c = Color3::new(0.7, 0.2, 0.05);
+        let white = Color3::new(1.0, 1.0, 1.0);
+        for (to, from) in [
+            (rec709_to_rec2020(), rec2020_to_rec709()),
+            (rec709_to_acescg(), acescg_to_rec709()),
+        ] {
+            assert!((from * (to * c) - c).abs().max() < 1e-4);
+            // white stays white, colors of rec.709 are inside the wider gamuts
+            assert!((to * white - white).abs().max() < 1e-4);
+            assert!((to * c).min() > 0.0);
+        }
+    }
+}
@@ -0,0 +1,405 @@
+use std::path::Path;
+
+use anyhow::{ensure, Context, Result};
+
+use super::{false_color, vec3, Aov, HdrImage, Image, Settings, Spectrum, Vec3List, Vec3f};
+
+// samples of one pixel so far, enough to tell its mean and variance
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FilmPixel {
+    pub sum: Vec3f,
+    // sum of the squared sample luminances
+    pub lum_sq: f32,
+    pub samples: u32,
+}
+
+impl FilmPixel {
+    pub fn add_sample(&mut self, l: &Spectrum) {
+        let lum = vec3::luminance(l);
+        self.sum += l;
+        self.lum_sq += lum * lum;
+        self.samples += 1;
+    }
+
+    // standard error of the square root of the luminance, roughly as bright as displayed,
+    // infinite with too few samples
+    pub fn error(&self) -> f32 {
+        if self.samples < 2 {
+            return f32::INFINITY;
+        }
+        let mean = vec3::luminance(&self.sum) / self.samples as f32;
+        if mean <= 0.0 {
+            return 0.0;
+        }
+        self.variance().sqrt() / (2.0 * mean.sqrt())
+    }
+
+    // estimated variance of the mean luminance, infinite with too few samples
+    pub fn variance(&self) -> f32 {
+        if self.samples < 2 {
+            return f32::INFINITY;
+        }
+        let n = self.samples as f32;
+        let mean = vec3::luminance(&self.sum) / n;
+        let variance = ((self.lum_sq / n - mean * mean) * n / (n - 1.0)).max(0.0);
+        variance / n
+    }
+}
+
+// Radiance accumulated by the renderer: per pixel the sum of its samples and their number,
+// the samples of the pixels around weighted by the reconstruction filter, and the light
+// splatted onto it. Saved to checkpoints so that a render can be continued later, the file
+// is little endian:
+//
+//   magic    4 bytes   b"TRCK"
+//   version  u32
+//   width    u32
+//   height   u32
+//   seed     u64
+//   pixels   width * height times 3 f32 sum, f32 squared luminance sum, 3 f32 filtered sum,
+//            f32 filter weight sum, 3 f32 splat sum, u32 samples, row by row from the top
+//   naovs    u32
+//   aovs     naovs times u32, the index in `Aov::ALL`
+//   values   width * height * naovs times 3 f32, per pixel in the order of the aovs
+pub struct Film {
+    width: usize,
+    height: usize,
+    seed: u64,
+    pixels: Vec<FilmPixel>,
+    filtered: Vec3List,
+    weights: Vec<f32>,
+    splats: Vec3List,
+    aovs: Vec<Aov>,
+    // per pixel and aov the sum of the samples, or the value of the first one for the aovs
+    // which are not averaged
+    aov_values: Vec<Vec3f>,
+}
+
+const MAGIC: &[u8; 4] = b"TRCK";
+const VERSION: u32 = 4;
+const HEADER_SIZE: usize = 24;
+const PIXEL_SIZE: usize = 48;
+
+impl Film {
+    pub fn new(width: usize, height: usize, seed: u64) -> Self {
+        Self {
+            width,
+            height,
+            seed,
+            pixels: vec![FilmPixel::default(); width * height],
+            filtered: Vec3List::new_with_size(width * height),
+            weights: vec![0.0; width * height],
+            splats: Vec3List::new_with_size(width * height),
+            aovs: Vec::new(),
+            aov_values: Vec::new(),
+        }
+    }
+
+    pub fn with_aovs(mut self, aovs: &[Aov]) -> Self {
+        self.aovs = aovs.to_vec();
+        self.aov_values = vec![Vec3f::zeros(); self.pixels.len() * aovs.len()];
+        self
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let bytes = std::fs::read(path).context("failed to load checkpoint")?;
+        Self::parse(&bytes)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        ensure!(
+            bytes.len() >= HEADER_SIZE && &bytes[0..4] == MAGIC,
+            "not a checkpoint file"
+        );
+
+        let read_u32 = |offset: usize| {
+            u32::from_le_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+        let read_f32 = |offset: usize| f32::from_bits(read_u32(offset));
+        let read_vec3 = |offset: usize| {
+            Vec3f::new(read_f32(offset), read_f32(offset + 4), read_f32(offset + 8))
+        };
+
+        let version = read_u32(4);
+        ensure!(
+            version == VERSION,
+            "checkpoint version {} is not supported",
+            version
+        );
+        let (width, height) = (read_u32(8) as usize, read_u32(12) as usize);
+        let seed = read_u32(16) as u64 | (read_u32(20) as u64) << 32;
+
+        // the pixels and the number of aovs have to fit, the size of the rest is known then
+        let body_size = bytes.len() - HEADER_SIZE;
+        let aovs_offset = width
+            .checked_mul(height)
+            .and_then(|n| n.checked_mul(PIXEL_SIZE))
+            .map(|size| HEADER_SIZE + size)
+            .filter(|offset| body_size >= 4 && *offset <= bytes.len() - 4);
+        let aovs_offset = aovs_offset.with_context(|| {
+            format!(
+                "checkpoint of {}x{} has {} bytes of data",
+                width, height, body_size
+            )
+        })?;
+
+        let naovs = read_u32(aovs_offset) as usize;
+        ensure!(naovs <= Aov::ALL.len(), "checkpoint has {} aovs", naovs);
+        let values_offset = aovs_offset + 4 + naovs * 4;
+        ensure!(
+            values_offset + width * height * naovs * 12 == bytes.len(),
+            "checkpoint of {}x{} with {} aovs has {} bytes of data",
+            width,
+            height,
+            naovs,
+            body_size
+        );
+        let mut aovs = Vec::with_capacity(naovs);
+        for i in 0..naovs {
+            let index = read_u32(aovs_offset + 4 + i * 4) as usize;
+            let aov = Aov::ALL
+                .get(index)
+                .context("checkpoint has an unknown aov")?;
+            aovs.push(*aov);
+        }
+
+        let mut film = Self::new(width, height, seed).with_aovs(&aovs);
+        for idx in 0..width * height {
+            let offset = HEADER_SIZE + idx * PIXEL_SIZE;
+            film.pixels[idx] = FilmPixel {
+                sum: read_vec3(offset),
+                lum_sq: read_f32(offset + 12),
+                samples: read_u32(offset + 44),
+            };
+            film.filtered[idx] = read_vec3(offset + 16);
+            film.weights[idx] = read_f32(offset + 28);
+            film.splats[idx] = read_vec3(offset + 32);
+        }
+        for (i, value) in film.aov_values.iter_mut().enumerate() {
+            *value = read_vec3(values_offset + i * 12);
+        }
+        Ok(film)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + self.pixels.len() * PIXEL_SIZE);
+        bytes.extend_from_slice(MAGIC);
+        for v in [VERSION, self.width as u32, self.height as u32] {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+
+        for (idx, pixel) in self.pixels.iter().enumerate() {
+            let (filtered, splat) = (self.filtered[idx], self.splats[idx]);
+            let values = [
+                pixel.sum.x,
+                pixel.sum.y,
+                pixel.sum.z,
+                pixel.lum_sq,
+                filtered.x,
+                filtered.y,
+                filtered.z,
+                self.weights[idx],
+                splat.x,
+                splat.y,
+                splat.z,
+            ];
+            for v in values {
+                bytes.extend_from_slice(&v.to_le_bytes());
+            }
+            bytes.extend_from_slice(&pixel.samples.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(&(self.aovs.len() as u32).to_le_bytes());
+        for aov in &self.aovs {
+            let index = Aov::ALL.iter().position(|a| a == aov).unwrap() as u32;
+            bytes.extend_from_slice(&index.to_le_bytes());
+        }
+        for value in &self.aov_values {
+            for v in [value.x, value.y, value.z] {
+                bytes.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    // written next to the final file and renamed, an interrupted save keeps the old checkpoint
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, self.to_bytes()).context("failed to write checkpoint")?;
+        std::fs::rename(&tmp_path, path).context("failed to write checkpoint")?;
+        Ok(())
+    }
+
+    // a checkpoint can only be continued by a render of the same size and random numbers
+    pub fn check(&self, settings: &Settings) -> Result<()> {
+        ensure!(
+            self.width == settings.width && self.height == settings.height,
+            "checkpoint is {}x{}, the project renders {}x{}",
+            self.width,
+            self.height,
+            settings.width,
+            settings.height
+        );
+        ensure!(
+            self.seed == settings.seed,
+            "checkpoint was rendered with seed {}, the project uses {}",
+            self.seed,
+            settings.seed
+        );
+        ensure!(
+            self.aovs == settings.film_aovs(),
+            "checkpoint has the aovs {:?}, the project needs {:?}",
+            self.aovs,
+            settings.film_aovs()
+        );
+        Ok(())
+    }
+
+    // pixel `idx`, row by row from the top
+    pub fn pixel(&self, idx: usize) -> FilmPixel {
+        self.pixels[idx]
+    }
+
+    pub fn set_pixel(&mut self, idx: usize, pixel: FilmPixel) {
+        self.pixels[idx] = pixel;
+    }
+
+    pub fn pixels(&self) -> &[FilmPixel] {
+        &self.pixels
+    }
+
+    pub fn aovs(&self) -> &[Aov] {
+        &self.aovs
+    }
+
+    // of pixel `idx`, in the order of `aovs`
+    pub fn aov_values(&self, idx: usize) -> &[Vec3f] {
+        let n = self.aovs.len();
+        &self.aov_values[idx * n..(idx + 1) * n]
+    }
+
+    pub fn set_aov_values(&mut self, idx: usize, values: &[Vec3f]) {
+        let n = self.aovs.len();
+        self.aov_values[idx * n..(idx + 1) * n].copy_from_slice(values);
+    }
+
+    // samples multiplied by their filter weights at the center of pixel `idx`, and the weights
+    pub fn add_filtered(&mut self, idx: usize, weighted: &Spectrum, weight: f32) {
+        self.filtered[idx] += weighted;
+        self.weights[idx] += weight;
+    }
+
+    pub fn add_splat(&mut self, idx: usize, l: &Spectrum) {
+        self.splats[idx] += l;
+    }
+
+    pub fn total_samples(&self) -> u64 {
+        self.pixels.iter().map(|p| p.samples as u64).sum()
+    }
+
+    // linear radiance of every pixel
+    pub fn to_hdr_image(&self) -> HdrImage {
+        // light is splatted by the samples of all pixels, averaged like one pixel's samples
+        let total = self.total_samples();
+        let splat_scale = if total > 0 {
+            (self.pixels.len() as f64 / total as f64) as f32
+        } else {
+            0.0
+        };
+
+        let mut img = HdrImage::new(self.width, self.height);
+        for (idx, pixel) in self.pixels.iter().enumerate() {
+            let mut color = self.splats[idx] * splat_scale;
+            // negative filter lobes can cancel out all weight, the plain average is used then
+            if self.weights[idx] > 0.0 {
+                color += vec3::max(&(self.filtered[idx] / self.weights[idx]), &Vec3f::zeros());
+            } else if pixel.samples > 0 {
+                color += pixel.sum / pixel.samples as f32;
+            }
+            img.set_pixel(idx, color);
+        }
+        img
+    }
+
+    // one image per aov, the averaged ones divided by the samples of the pixels
+    pub fn to_aov_images(&self) -> Vec<(Aov, HdrImage)> {
+        let n = self.aovs.len();
+        self.aovs
+            .iter()
+            .enumerate()
+            .map(|(i, aov)| {
+                let mut img = HdrImage::new(self.width, self.height);
+                for (idx, pixel) in self.pixels.iter().enumerate() {
+                    let value = self.aov_values[idx * n + i];
+                    let value = if !aov.is_averaged() {
+                        value
+                    } else if pixel.samples > 0 {
+                        value / pixel.samples as f32
+                    } else {
+                        Vec3f::zeros()
+                    };
+                    img.set_pixel(idx, value);
+                }
+                (*aov, img)
+            })
+            .collect()
+    }
+
+    // samples per pixel relative to the most sampled one
+    pub fn to_heatmap(&self) -> Image {
+        let max = self
+            .pixels
+            .iter()
+            .map(|p| p.samples)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        let mut img = Image::new(self.width, self.height);
+        for (idx, pixel) in self.pixels.iter().enumerate() {
+            img.set_pixel(idx, false_color(pixel.samples as f32 / max as f32));
+        }
+        img
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Film, FilmPixel};
+    use crate::core::{Aov, Vec3f};
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let mut film = Film::new(2, 1, u64::MAX - 1).with_aovs(&[Aov::Depth, Aov::Albedo]);
+        let mut pixel = FilmPixel::default();
+        pixel.add_sample(&Vec3f::new(1.0, 2.0, 3.0));
+        film.set_pixel(1, pixel);
+        film.add_splat(0, &Vec3f::new(0.5, 0.0, 0.25));
+        film.add_filtered(0, &Vec3f::new(1.0, 1.0, 1.0), -0.125);
+        let aov_values = [Vec3f::new(2.5, 0.0, 0.0), Vec3f::new(0.25, 0.5, 0.75)];
+        film.set_aov_values(1, &aov_values);
+
+        let bytes = film.to_bytes();
+        let loaded = Film::parse(&bytes).unwrap();
+        assert_eq!(loaded.seed, film.seed);
+        assert_eq!(loaded.pixel(1), pixel);
+        assert_eq!(loaded.splats[0], Vec3f::new(0.5, 0.0, 0.25));
+        assert_eq!(loaded.weights[0], -0.125);
+        assert_eq!(loaded.aovs(), film.aovs());
+        assert_eq!(loaded.aov_values(1), &aov_values);
+        let images = loaded.to_aov_images();
+        assert_eq!(images[0].1.get_pixel(1), Vec3f::new(2.5, 0.0, 0.0));
+        assert_eq!(images[1].1.get_pixel(1), Vec3f::new(0.25, 0.5, 0.75));
+        assert_eq!(loaded.to_bytes(), bytes);
+
+        assert!(Film::parse(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Film::parse(b"TRCK").is_err());
+    }
+}
@@ -0,0 +1,11 @@
+use std::sync::Arc;
+
+// pixel reconstruction filter, the weight of a sample at offset (x, y) from a pixel center,
+// in pixels. Zero outside of `radius` in both directions
+pub trait Filter {
+    fn radius(&self) -> f32;
+
+    fn evaluate(&self, x: f32, y: f32) -> f32;
+}
+
+pub type FilterPtr = Arc<dyn Filter + Sync + Send>;
@@ -0,0 +1,355 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use image::{codecs::hdr::HdrEncoder, Rgb};
+use nalgebra::Matrix3;
+
+use super::{
+    false_color, tone_map, vec3, Aov, Color3, Image, OutputFormat, Settings, ToneMapConfig,
+};
+
+// linear RGB radiance of the render, row by row from the top
+#[derive(Clone)]
+pub struct HdrImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color3>,
+}
+
+impl HdrImage {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color3::zeros(); width * height],
+        }
+    }
+
+    // pfm, and png, jpeg, exr, hdr and the other formats of `image`, 8 bit values are scaled
+    // to [0, 1]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if OutputFormat::from_path(path).ok() == Some(OutputFormat::Pfm) {
+            let bytes = std::fs::read(path).context("failed to load image")?;
+            return Self::parse_pfm(&bytes);
+        }
+
+        let image = image::open(path)
+            .context("failed to load image")?
+            .to_rgb32f();
+        let (width, height) = image.dimensions();
+        let pixels = image
+            .pixels()
+            .map(|p| Color3::new(p[0], p[1], p[2]))
+            .collect();
+        Ok(Self {
+            width: width as usize,
+            height: height as usize,
+            pixels,
+        })
+    }
+
+    // e.g. into another color space
+    pub fn transform(&mut self, m: &Matrix3<f32>) {
+        for c in self.pixels.iter_mut() {
+            *c = m * *c;
+        }
+    }
+
+    pub fn set_pixel(&mut self, idx: usize, color: Color3) {
+        self.pixels[idx] = color;
+    }
+
+    pub fn get_pixel(&self, idx: usize) -> Color3 {
+        self.pixels[idx]
+    }
+
+    // the image for display: exposed, tone mapped and sRGB encoded
+    pub fn to_image(&self, exposure: f32, tone_map: &ToneMapConfig) -> Image {
+        let scale = exposure.exp2();
+        let white = match tone_map {
+            ToneMapConfig::ExtendedReinhard { white: None } => self
+                .pixels
+                .iter()
+                .map(|c| vec3::luminance(c) * scale)
+                .fold(0.0, f32::max),
+            ToneMapConfig::ExtendedReinhard { white: Some(white) } => *white,
+            _ => 1.0,
+        };
+
+        let mut img = Image::new(self.width, self.height);
+        for (idx, color) in self.pixels.iter().enumerate() {
+            let c = color * scale;
+            let c = match tone_map {
+                ToneMapConfig::Clamp {} => c,
+                ToneMapConfig::Reinhard {} => tone_map::reinhard(&c),
+                ToneMapConfig::ExtendedReinhard { .. } => tone_map::extended_reinhard(&c, white),
+                ToneMapConfig::Aces {} => tone_map::aces(&c),
+                ToneMapConfig::Agx {} => tone_map::agx(&c),
+            };
+            img.set_pixel(idx, c.map(|v| tone_map::srgb_oetf(v.clamp(0.0, 1.0))));
+        }
+        img
+    }
+
+    // the tone mapping settings only apply to png
+    pub fn save<P: AsRef<Path>>(
+        &self,
+        path: P,
+        format: OutputFormat,
+        settings: &Settings,
+    ) -> Result<()> {
+        let path = path.as_ref();
+        match format {
+            OutputFormat::Png => self
+                .to_image(settings.exposure, &settings.tone_map)
+                .save_to_png(path)
+                .context("failed to write png")?,
+            OutputFormat::Exr => self.save_exr(path)?,
+            OutputFormat::Pfm => {
+                std::fs::write(path, self.to_pfm()).context("failed to write pfm")?;
+            }
+            OutputFormat::Hdr => {
+                // RGBE can't hold negative values
+                let pixels: Vec<Rgb<f32>> = self
+                    .pixels
+                    .iter()
+                    .map(|c| Rgb([c.x, c.y, c.z].map(|v| if v > 0.0 { v } else { 0.0 })))
+                    .collect();
+                let mut writer = BufWriter::new(File::create(path).context("failed to write hdr")?);
+                HdrEncoder::new(&mut writer)
+                    .encode(&pixels, self.width, self.height)
+                    .context("failed to write hdr")?;
+                writer.flush().context("failed to write hdr")?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn save_exr<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        exr::prelude::write_rgb_file(path, self.width, self.height, |x, y| {
+            let c = self.pixels[y * self.width + x];
+            (c.x, c.y, c.z)
+        })
+        .context("failed to write exr")
+    }
+
+    // the image as the channels R, G and B, the aovs as layers of channels <aov>.R, .G and .B,
+    // or <aov>.Y of the single valued ones
+    pub fn save_exr_with_aovs<P: AsRef<Path>>(
+        &self,
+        path: P,
+        aovs: &[(Aov, HdrImage)],
+    ) -> Result<()> {
+        use exr::prelude::{
+            AnyChannel, AnyChannels, Encoding, FlatSamples, Layer, LayerAttributes, SmallVec,
+            WritableImage,
+        };
+
+        let channel = |name: &str, image: &HdrImage, c: usize| {
+            let samples = image.pixels.iter().map(|p| p[c]).collect();
+            AnyChannel::new(name, FlatSamples::F32(samples))
+        };
+        let mut channels = SmallVec::new();
+        for (c, name) in ["R", "G", "B"].iter().enumerate() {
+            channels.push(channel(name, self, c));
+        }
+        for (aov, image) in aovs {
+            if aov.channels() == 1 {
+                channels.push(channel(&format!("{}.Y", aov.name()), image, 0));
+            } else {
+                for (c, name) in ["R", "G", "B"].iter().enumerate() {
+                    channels.push(channel(&format!("{}.{}", aov.name(), name), image, c));
+                }
+            }
+        }
+
+        let layer = Layer::new(
+            (self.width, self.height),
+            LayerAttributes::default(),
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(channels),
+        );
+        exr::prelude::Image::from_layer(layer)
+            .write()
+            .to_file(path)
+            .context("failed to write exr")
+    }
+
+    // `aov` as a separate file, png shows colors like the image, normals mapped from [-1, 1],
+    // depths relative to the farthest hit and ids in false colors
+    pub fn save_aov<P: AsRef<Path>>(
+        &self,
+        path: P,
+        aov: Aov,
+        format: OutputFormat,
+        settings: &Settings,
+    ) -> Result<()> {
+        if format != OutputFormat::Png || aov.is_color() {
+            return self.save(path, format, settings);
+        }
+
+        let max = self.pixels.iter().map(|c| c.x).fold(0.0, f32::max);
+        let mut img = Image::new(self.width, self.height);
+        for (idx, c) in self.pixels.iter().enumerate() {
+            let c = match aov {
+                Aov::Normal => (c + Color3::new(1.0, 1.0, 1.0)) * 0.5,
+                Aov::Depth if max > 0.0 => c / max,
+                // neighboring ids far apart on the ramp, 0 stays black
+                Aov::PrimitiveId | Aov::MaterialId if c.x > 0.0 => {
+                    false_color((c.x * 0.618_034).fract())
+                }
+                Aov::PrimitiveId | Aov::MaterialId => Color3::zeros(),
+                _ => *c,
+            };
+            img.set_pixel(idx, c);
+        }
+        img.save_to_png(path).context("failed to write png")
+    }
+
+    // portable float map: a text header, then little endian f32 RGB rows from the bottom
+    pub fn to_pfm(&self) -> Vec<u8> {
+        let mut bytes = format!("PF\n{} {}\n-1.0\n", self.width, self.height).into_bytes();
+        for row in self.pixels.chunks(self.width.max(1)).rev() {
+            for c in row {
+                for v in [c.x, c.y, c.z] {
+                    bytes.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+        }
+        bytes
+    }
+
+    pub fn parse_pfm(bytes: &[u8]) -> Result<Self> {
+        // three whitespace separated header fields after the magic, then a single whitespace
+        let mut fields = Vec::new();
+        let mut pos = 0;
+        while fields.len() < 4 {
+            while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            let start = pos;
+            while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            ensure!(start < pos, "truncated pfm header");
+            fields.push(String::from_utf8_lossy(&bytes[start..pos]).to_string());
+        }
+        let channels = match fields[0].as_str() {
+            "PF" => 3,
+            "Pf" => 1,
+            _ => bail!("not a pfm file"),
+        };
+        let width: usize = fields[1].parse().context("invalid pfm width")?;
+        let height: usize = fields[2].parse().context("invalid pfm height")?;
+        let scale: f32 = fields[3].parse().context("invalid pfm scale")?;
+
+        let body = &bytes[(pos + 1).min(bytes.len())..];
+        ensure!(
+            body.len() == width * height * channels * 4,
+            "pfm of {}x{} has {} bytes of data",
+            width,
+            height,
+            body.len()
+        );
+        let values: Vec<f32> = body
+            .chunks_exact(4)
+            .map(|b| {
+                let b = [b[0], b[1], b[2], b[3]];
+                if scale < 0.0 {
+                    f32::from_le_bytes(b)
+                } else {
+                    f32::from_be_bytes(b)
+                }
+            })
+            .collect();
+
+        let mut img = Self::new(width, height);
+        for (k, v) in values.chunks_exact(channels).enumerate() {
+            let (row, i) = (k / width.max(1), k % width.max(1));
+            let c = if channels == 3 {
+                Color3::new(v[0], v[1], v[2])
+            } else {
+                vec3::scalar(v[0])
+            };
+            img.pixels[(height - row - 1) * width + i] = c;
+        }
+        Ok(img)
+    }
+
+    /// Get the image's width.
+    #[inline]
+    #[must_use]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Get the image's height.
+    #[inline]
+    #[must_use]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HdrImage;
+    use crate::core::{Aov, Color3};
+
+    #[test]
+    fn test_pfm() {
+        let mut img = HdrImage::new(1, 2);
+        img.set_pixel(0, Color3::new(4.5, 0.0, -1.0));
+        let bytes = img.to_pfm();
+
+        let header = b"PF\n1 2\n-1.0\n";
+        assert_eq!(&bytes[..header.len()], header);
+        assert_eq!(bytes.len(), header.len() + 2 * 12);
+        // the top row comes last
+        let top = &bytes[header.len() + 12..];
+        assert_eq!(&top[0..4], &4.5f32.to_le_bytes());
+        assert_eq!(&top[8..12], &(-1.0f32).to_le_bytes());
+
+        let loaded = HdrImage::parse_pfm(&bytes).unwrap();
+        assert_eq!(loaded.get_pixel(0), img.get_pixel(0));
+        assert!(HdrImage::parse_pfm(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_exr_with_aovs() {
+        let mut img = HdrImage::new(2, 1);
+        img.set_pixel(1, Color3::new(0.5, 1.0, 2.0));
+        let mut depth = HdrImage::new(2, 1);
+        depth.set_pixel(0, Color3::new(3.0, 3.0, 3.0));
+        let mut normal = HdrImage::new(2, 1);
+        normal.set_pixel(1, Color3::new(0.0, -1.0, 0.0));
+
+        let path = std::env::temp_dir().join("toy_ray_tracer_aovs.exr");
+        img.save_exr_with_aovs(&path, &[(Aov::Depth, depth), (Aov::Normal, normal)])
+            .unwrap();
+
+        // readers of plain rgb files see the image
+        let loaded = HdrImage::load(&path).unwrap();
+        assert_eq!(loaded.get_pixel(1), img.get_pixel(1));
+
+        let exr = exr::prelude::read_all_flat_layers_from_file(&path).unwrap();
+        let channels = &exr.layer_data[0].channel_data.list;
+        let names: Vec<String> = channels.iter().map(|c| c.name.to_string()).collect();
+        assert_eq!(
+            names,
+            ["B", "G", "R", "depth.Y", "normal.B", "normal.G", "normal.R"]
+        );
+        let value = |name: &str, x: usize| {
+            let channel = channels.iter().find(|c| c.name.to_string() == name);
+            channel.unwrap().sample_data.value_by_flat_index(x).to_f32()
+        };
+        assert_eq!(value("depth.Y", 0), 3.0);
+        assert_eq!(value("normal.G", 1), -1.0);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
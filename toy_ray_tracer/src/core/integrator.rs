@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use super::{Point2f, Ray, Scene, Settings, Spectrum, Vec3List};
+
+// radiance landing on an arbitrary pixel, e.g. light paths connected to the camera
+pub struct Splat {
+    // film coordinates, as passed to `Camera::get_ray`
+    pub film: Point2f,
+    pub l: Spectrum,
+}
+
+pub trait Integrator {
+    // radiance arriving at the camera along `ray`
+    fn li(&self, ray: &Ray, scene: &Scene, settings: &Settings) -> Spectrum;
+
+    // like `li`, contributions to other pixels are pushed to `splats`
+    fn li_splats(
+        &self,
+        ray: &Ray,
+        scene: &Scene,
+        settings: &Settings,
+        _splats: &mut Vec<Splat>,
+    ) -> Spectrum {
+        self.li(ray, scene, settings)
+    }
+
+    // like `li_splats`, also returns the part of the radiance which reached the camera after
+    // at most one bounce if the integrator tells it apart, see `splits_direct`
+    fn li_direct(
+        &self,
+        ray: &Ray,
+        scene: &Scene,
+        settings: &Settings,
+        splats: &mut Vec<Splat>,
+    ) -> (Spectrum, Option<Spectrum>) {
+        (self.li_splats(ray, scene, settings, splats), None)
+    }
+
+    // whether `li_direct` returns the direct part with these settings
+    fn splits_direct(&self, _settings: &Settings) -> bool {
+        false
+    }
+
+    // whether `li` can trace the sampled wavelengths of `settings.spectral`, the debug views
+    // and whole-image integrators render RGB
+    fn supports_spectral(&self) -> bool {
+        false
+    }
+
+    // whether `li` samples the media of the scene, the others pass through their boundaries
+    fn supports_media(&self, _settings: &Settings) -> bool {
+        false
+    }
+
+    // whether `li` walks below subsurface materials, the others only see their dielectric
+    // boundary
+    fn supports_subsurface(&self, _settings: &Settings) -> bool {
+        false
+    }
+
+    // integrators which need the whole image at once, e.g. photon mapping, return the
+    // averaged radiance of every pixel, top row first. `None` renders pixels with `li`
+    fn render(&self, _scene: &Scene, _settings: &Settings) -> Option<Vec3List> {
+        None
+    }
+}
+
+pub type IntegratorPtr = Arc<dyn Integrator + Sync + Send>;
@@ -1,4 +1,12 @@
-use super::{Material, Point2f, Point3f, Vec3f};
+use super::{Material, MediumInterface, MediumPtr, Point2f, Point3f, Vec3f};
+
+// numbers of the object and of the material hit, counted from 1 in the order the scene is
+// built, 0 if unknown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PrimitiveIds {
+    pub primitive: u32,
+    pub material: u32,
+}
 
 pub struct SurfaceInteraction<'a> {
     pub t_hit: f32,
@@ -10,7 +18,10 @@ pub struct SurfaceInteraction<'a> {
     pub normal: Vec3f,
     pub front_face: bool,
 
+    // None for surfaces which only separate media
     pub material: Option<&'a dyn Material>,
+    pub medium_interface: Option<&'a MediumInterface>,
+    pub ids: PrimitiveIds,
 }
 
 impl<'a> SurfaceInteraction<'a> {
@@ -29,6 +40,27 @@ impl<'a> SurfaceInteraction<'a> {
             normal,
             front_face,
             material: None,
+            medium_interface: None,
+            ids: PrimitiveIds::default(),
+        }
+    }
+
+    // medium on the side of the surface `w` points to, `current` if the surface is no medium boundary
+    pub fn medium(&self, w: &Vec3f, current: Option<&'a MediumPtr>) -> Option<&'a MediumPtr> {
+        let mi = match self.medium_interface {
+            Some(mi) => mi,
+            None => return current,
+        };
+
+        let outward = if self.front_face {
+            self.normal
+        } else {
+            -self.normal
+        };
+        if w.dot(&outward) > 0.0 {
+            mi.outside.as_ref()
+        } else {
+            mi.inside.as_ref()
         }
     }
 
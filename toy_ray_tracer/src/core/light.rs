@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use super::{Point3f, Ray, Spectrum, Vec3f};
+use super::{Point3f, Ray, Spectrum, Vec3f, AABB};
 use enumflags2::{bitflags, BitFlags};
 
 #[bitflags]
@@ -14,6 +14,31 @@ pub enum LightType {
 
 pub type LightTypeFlags = BitFlags<LightType>;
 
+// incident radiance sampled from a light
+pub struct LightSample {
+    // normalized direction towards the light
+    pub wi: Vec3f,
+    // solid angle pdf of `wi`
+    pub pdf: f32,
+    pub li: Spectrum,
+    // distance to the sampled point, infinity for environment lights
+    pub distance: f32,
+    // surface normal at the sampled point, zero for environment lights
+    pub normal: Vec3f,
+}
+
+// ray leaving a light, used to start light subpaths
+pub struct LightEmission {
+    pub ray: Ray,
+    // normal of the emitting surface, the emitted direction for environment lights
+    pub normal: Vec3f,
+    pub le: Spectrum,
+    // area pdf of the ray origin
+    pub pdf_pos: f32,
+    // solid angle pdf of the ray direction
+    pub pdf_dir: f32,
+}
+
 pub trait Light {
     // background_l for envrionment light
     fn background_l(&self, r: &Ray) -> Spectrum;
@@ -23,6 +48,19 @@ pub trait Light {
     fn sample_wi(&self, point: &Point3f) -> Vec3f;
 
     fn sample_pdf(&self, point: &Point3f, wi: &Vec3f) -> f32;
+
+    // sample incident radiance at `point`, used for next event estimation
+    fn sample_li(&self, point: &Point3f) -> Option<LightSample>;
+
+    // pdf of `sample_li` choosing `wi` when the surface seen along `wi` is `distance` away
+    fn pdf_li(&self, point: &Point3f, wi: &Vec3f, distance: f32) -> f32;
+
+    // sample emitted radiance, environment lights emit from a disk covering `world_bound`
+    fn sample_le(&self, world_bound: &AABB, time: f32) -> Option<LightEmission>;
+
+    // (pdf_pos, pdf_dir) of `sample_le` emitting from `point` on the light towards `from`,
+    // environment lights have no such point and return zero
+    fn pdf_le(&self, from: &Point3f, point: &Point3f) -> (f32, f32);
 }
 
 pub type LightPtr = Arc<dyn Light + Sync + Send>;
@@ -3,6 +3,7 @@ use std::sync::Arc;
 use crate::core::SurfaceInteraction;
 
 use super::Bsdf;
+use super::Bssrdf;
 use super::Color3;
 
 pub trait Material: Sync {
@@ -13,6 +14,16 @@ pub trait Material: Sync {
     fn compute_bsdf(&self, _si: &SurfaceInteraction) -> Option<Bsdf> {
         None
     }
+
+    // reflectance color of the surface, used by debug outputs
+    fn albedo(&self, _si: &SurfaceInteraction) -> Color3 {
+        Color3::zeros()
+    }
+
+    // scattering below the surface, for paths the bsdf refracts into it
+    fn bssrdf(&self, _si: &SurfaceInteraction) -> Option<Bssrdf> {
+        None
+    }
 }
 
 pub type MaterialPtr = Arc<dyn Material + Sync + Send>;
@@ -0,0 +1,132 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use super::{Ray, Spectrum, Vec3f};
+use crate::math::ONB;
+use crate::utils::random;
+
+// Henyey-Greenstein phase function, `g` > 0 scatters forward, `g` < 0 backward
+#[derive(Debug, Clone, Copy)]
+pub struct HenyeyGreenstein {
+    g: f32,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(g: f32) -> Self {
+        Self {
+            g: g.clamp(-0.99, 0.99),
+        }
+    }
+
+    // probability density of scattering from `wi` to `wo`, both pointing away from the scattering point
+    pub fn p(&self, wo: &Vec3f, wi: &Vec3f) -> f32 {
+        let cos_theta = wo.normalize().dot(&wi.normalize());
+        let denom = 1.0 + self.g * self.g + 2.0 * self.g * cos_theta;
+        (1.0 - self.g * self.g) / (4.0 * PI * denom * denom.max(0.0).sqrt())
+    }
+
+    // sample `wi` for `wo`, the value of the phase function equals the pdf
+    pub fn sample_p(&self, wo: &Vec3f) -> (Vec3f, f32) {
+        let (u1, u2) = random::f32_2d();
+        let g = self.g;
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * u1
+        } else {
+            let sqr = (1.0 - g * g) / (1.0 + g - 2.0 * g * u1);
+            -(1.0 + g * g - sqr * sqr) / (2.0 * g)
+        };
+
+        // cos_theta is measured from `wo`, forward scattering gives wi close to -wo
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u2;
+        let local = Vec3f::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        let wi = ONB::build_form_w(wo).local(local).normalize();
+
+        (wi, self.p(wo, &wi))
+    }
+}
+
+// free-flight sampling result, `weight` multiplies the path throughput
+pub struct MediumSample {
+    // ray parameter of the scattering event, None if the ray passed through the medium
+    pub t: Option<f32>,
+    pub weight: Spectrum,
+    pub phase: HenyeyGreenstein,
+}
+
+pub trait Medium {
+    // transmittance along `ray` from its origin to parameter `t_max`
+    fn tr(&self, ray: &Ray, t_max: f32) -> Spectrum;
+
+    // sample the distance to the next scattering event along `ray` before `t_max`
+    fn sample(&self, ray: &Ray, t_max: f32) -> MediumSample;
+}
+
+pub type MediumPtr = Arc<dyn Medium + Sync + Send>;
+
+// media on both sides of a surface, inside is the side opposite to the geometric normal
+#[derive(Clone, Default)]
+pub struct MediumInterface {
+    pub inside: Option<MediumPtr>,
+    pub outside: Option<MediumPtr>,
+}
+
+impl MediumInterface {
+    pub fn new(inside: Option<MediumPtr>, outside: Option<MediumPtr>) -> Self {
+        Self { inside, outside }
+    }
+}
+
+// exp(-sigma_t * distance)
+pub fn beer_lambert(sigma_t: &Spectrum, distance: f32) -> Spectrum {
+    sigma_t.map(|sigma| (-sigma * distance).exp())
+}
+
+// sample the distance to the next scattering event in a homogeneous medium with the extinction
+// of a channel picked proportionally to `channel_weights`, weighted by all of them. Returns the
+// ray parameter of the event, None if the ray passed `t_max`, and the weight multiplying the
+// path throughput
+pub fn sample_homogeneous(
+    sigma_s: &Spectrum,
+    sigma_t: &Spectrum,
+    channel_weights: &Spectrum,
+    ray: &Ray,
+    t_max: f32,
+) -> (Option<f32>, Spectrum) {
+    let length = ray.direction().norm();
+
+    let total = channel_weights.sum();
+    if total <= 0.0 || !total.is_finite() {
+        return (None, Spectrum::zeros());
+    }
+    let channel_pdf = channel_weights / total;
+    let u = random::f32();
+    let channel = if u < channel_pdf[0] {
+        0
+    } else if u < channel_pdf[0] + channel_pdf[1] {
+        1
+    } else {
+        2
+    };
+    let distance = -(1.0 - random::f32()).ln() / sigma_t[channel];
+    let t = (distance / length).min(t_max);
+    let scattered = t < t_max;
+
+    let tr = beer_lambert(sigma_t, (t * length).min(f32::MAX));
+    let density = if scattered {
+        sigma_t.component_mul(&tr)
+    } else {
+        tr
+    };
+    let pdf = density.dot(&channel_pdf);
+
+    let weight = if pdf <= 0.0 {
+        Spectrum::zeros()
+    } else if scattered {
+        tr.component_mul(sigma_s) / pdf
+    } else {
+        tr / pdf
+    };
+
+    (if scattered { Some(t) } else { None }, weight)
+}
@@ -1,8 +1,15 @@
 mod aabb;
+mod bssrdf;
 mod camera;
+mod color_space;
+mod film;
+mod filter;
+mod hdr_image;
+mod integrator;
 mod interaction;
 pub(crate) mod light;
 mod material;
+mod medium;
 mod nimage;
 mod primitive;
 mod project;
@@ -13,26 +20,41 @@ mod scene;
 mod shape;
 mod spectrum;
 mod texture;
+mod tone_map;
 mod transform;
 mod vec;
 
 pub use aabb::AABB;
+pub use bssrdf::Bssrdf;
 pub use camera::{Camera, CameraOpt};
-pub use interaction::SurfaceInteraction;
-pub use light::{Light, LightPtr, LightType};
+pub use color_space::srgb_eotf;
+pub use film::{Film, FilmPixel};
+pub use filter::{Filter, FilterPtr};
+pub use hdr_image::HdrImage;
+pub use integrator::{Integrator, IntegratorPtr, Splat};
+pub use interaction::{PrimitiveIds, SurfaceInteraction};
+pub use light::{Light, LightEmission, LightPtr, LightSample, LightType};
 pub use material::{Material, MaterialPtr};
-pub use nimage::Image;
+pub use medium::{
+    beer_lambert, sample_homogeneous, HenyeyGreenstein, Medium, MediumInterface, MediumPtr,
+    MediumSample,
+};
+pub use nimage::{false_color, Image};
 pub use primitive::{
     Primitive, PrimitiveContainer, PrimitiveContainerPtr, PrimitivePtr, PrimitiveRef,
 };
 pub use ray::Ray;
 pub use reflection::{Bsdf, Bxdf, BxdfPtr};
-pub use sampler::sample;
+pub use sampler::{sample, PixelSampler, PixelSamplerPtr};
 pub use shape::{Shape, ShapePtr};
-pub use spectrum::Spectrum;
+pub use spectrum::{spectral, Ior, Spectrum};
 pub use texture::{Texture, TextureData, TexturePtr};
+pub use tone_map::srgb_oetf;
 pub use transform::Transform;
 pub use vec::{vec3, Color3, Point2f, Point3f, Vec2f, Vec3List, Vec3f, Vec4f};
 
-pub use project::{Project, Settings};
+pub use project::{
+    AdaptiveSettings, Aov, DenoiseSettings, MisHeuristic, OutputFormat, Project, SamplerConfig,
+    Settings, ToneMapConfig, WorkingSpace,
+};
 pub use scene::{Scene, SceneBundle};
@@ -1,7 +1,6 @@
 use std::path::Path;
 
 use crate::core::Color3;
-use anyhow::Context;
 use image::{save_buffer_with_format, ImageBuffer, ImageResult, Rgb};
 
 #[derive(Clone)]
@@ -60,11 +59,6 @@ impl Image {
         return &self.buf[s..e];
     }
 
-    pub fn load_png<P: AsRef<Path>>(path: P) -> anyhow::Result<Image> {
-        let image = image::open(path).context("failed to load image")?.to_rgb8();
-        Ok(image.into())
-    }
-
     pub fn save_to_png<P: AsRef<Path>>(&self, path: P) -> ImageResult<()> {
         save_buffer_with_format(
             path,
@@ -90,3 +84,24 @@ impl Image {
         self.height
     }
 }
+
+// blue - cyan - green - yellow - red ramp for `t` in [0, 1], e.g. for heatmaps
+pub fn false_color(t: f32) -> Color3 {
+    const STOPS: [[f32; 3]; 5] = [
+        [0.0, 0.0, 0.5],
+        [0.0, 0.8, 1.0],
+        [0.1, 0.9, 0.1],
+        [1.0, 0.9, 0.0],
+        [0.9, 0.0, 0.0],
+    ];
+    let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+    let x = t * (STOPS.len() - 1) as f32;
+    let i = (x as usize).min(STOPS.len() - 2);
+    let f = x - i as f32;
+    let (a, b) = (STOPS[i], STOPS[i + 1]);
+    Color3::new(
+        a[0] + (b[0] - a[0]) * f,
+        a[1] + (b[1] - a[1]) * f,
+        a[2] + (b[2] - a[2]) * f,
+    )
+}
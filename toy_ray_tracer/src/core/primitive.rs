@@ -8,11 +8,20 @@ use super::SurfaceInteraction;
 
 pub trait Primitive: Sync + Send {
     fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<SurfaceInteraction>;
+    // occlusion query, true if anything is hit in (t_min, t_max)
+    fn intersect_p(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        self.intersect(ray, t_min, t_max).is_some()
+    }
     fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB>;
 
     fn sample_pdf(&self, _point: &Point3f, _wi: &Vec3f) -> f32;
 
     fn sample_wi(&self, _point: &Vec3f) -> Vec3f;
+
+    fn area(&self) -> f32;
+
+    // uniformly sample a point on the surface, pdf is 1 / area
+    fn sample_area(&self) -> SurfaceInteraction<'_>;
 }
 
 pub type PrimitivePtr = Arc<dyn Primitive + Sync + Send>;
@@ -20,6 +29,8 @@ pub type PrimitiveRef<'a> = &'a (dyn Primitive);
 
 pub trait PrimitiveContainer: Sync + Send {
     fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<SurfaceInteraction>;
+    // occlusion query, stops at the first hit in (t_min, t_max)
+    fn intersect_p(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool;
     fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB>;
 }
 
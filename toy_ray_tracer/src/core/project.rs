@@ -1,17 +1,425 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use nalgebra::Matrix3;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::core::Scene;
+use crate::core::{color_space, FilterPtr, IntegratorPtr, PixelSamplerPtr, Scene};
+use crate::samplers::{HaltonSampler, IndependentSampler, SobolSampler, StratifiedSampler};
 
 #[derive(JsonSchema, Serialize, Deserialize, Debug, Clone)]
 pub struct Settings {
     pub output_dir: String,
+    // file type of the rendered image <output_dir>/<name>.<format>
+    #[serde(default)]
+    pub output_format: OutputFormat,
     pub width: usize,
     pub height: usize,
     pub nsamples: i32,
     pub max_depth: i32,
     #[serde(default = "Settings::default_mis_weight")]
     pub mis_weight: f32,
+    #[serde(default)]
+    pub mis_heuristic: MisHeuristic,
+    #[serde(default)]
+    pub photon_mapping: PhotonMappingSettings,
+    // random numbers of every pixel sample are derived from it, the same project renders
+    // the same image
+    #[serde(default)]
+    pub seed: u64,
+    // how the random numbers of the samples of a pixel are spread, independent by default
+    #[serde(default)]
+    pub sampler: SamplerConfig,
+    // trace sampled wavelengths instead of RGB, see `spectral`
+    #[serde(default)]
+    pub spectral: bool,
+    // brightness of the displayed image in stops, the radiance is scaled by 2^exposure
+    #[serde(default)]
+    pub exposure: f32,
+    // how the radiance is fitted into the range of the display
+    #[serde(default)]
+    pub tone_map: ToneMapConfig,
+    // RGB primaries colors are rendered in, the colors of the project are rec709
+    #[serde(default)]
+    pub working_space: WorkingSpace,
+    // sample noisy pixels more, `nsamples` becomes the average per pixel
+    pub adaptive: Option<AdaptiveSettings>,
+    // render in passes of a few samples per pixel, within a time budget
+    pub progressive: Option<ProgressiveSettings>,
+    // extra images written with the rendered one, see `Aov`
+    #[serde(default)]
+    pub aovs: Vec<Aov>,
+    // filter the noise out of the finished image
+    pub denoise: Option<DenoiseSettings>,
+}
+
+// Samplers hand out the random numbers of the pixel samples: the lens position, time, bsdf
+// and light samples of a pixel cover their domains more evenly than independent random
+// numbers do, so the noise drops faster at the same number of samples
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SamplerConfig {
+    Independent {},
+    // jittered strata, every dimension is shuffled over the `nsamples` of a pixel
+    Stratified {},
+    // Halton sequence, randomly shifted per pixel
+    Halton {},
+    // Owen scrambled Sobol points, best with a power of two `nsamples`
+    Sobol {},
+}
+
+impl SamplerConfig {
+    pub fn build(&self, seed: u64, nsamples: u32) -> PixelSamplerPtr {
+        match self {
+            SamplerConfig::Independent {} => Box::new(IndependentSampler::new(seed)),
+            SamplerConfig::Stratified {} => Box::new(StratifiedSampler::new(seed, nsamples)),
+            SamplerConfig::Halton {} => Box::new(HaltonSampler::new(seed)),
+            SamplerConfig::Sobol {} => Box::new(SobolSampler::new(seed)),
+        }
+    }
+}
+
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        SamplerConfig::Independent {}
+    }
+}
+
+// Adaptive sampling: every pixel starts with `min_samples`, then the pixels whose noise is above
+// `threshold` get `min_samples` more per round, until the budget of `nsamples` per pixel on
+// average is spent or all of them are below the threshold
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone)]
+pub struct AdaptiveSettings {
+    #[serde(default = "AdaptiveSettings::default_min_samples")]
+    pub min_samples: u32,
+    // samples a single pixel can take at most, 4 * nsamples by default
+    pub max_samples: Option<u32>,
+    // standard error of the displayed pixel luminance (0-1) a pixel is sampled down to
+    #[serde(default = "AdaptiveSettings::default_threshold")]
+    pub threshold: f32,
+    // also write the samples taken per pixel as <name>_samples.png
+    #[serde(default)]
+    pub heatmap: bool,
+}
+
+impl AdaptiveSettings {
+    pub fn default_min_samples() -> u32 {
+        16
+    }
+
+    pub fn default_threshold() -> f32 {
+        0.01
+    }
+}
+
+// Progressive rendering: every pixel gets `samples_per_pass` more samples per pass, and the
+// image is written after each one, so that a preview is on disk while rendering. No pass is
+// started once the next one wouldn't end within `time_budget` seconds, it renders until
+// `nsamples` without a budget. Adaptive sampling writes the image after each of its rounds
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone)]
+pub struct ProgressiveSettings {
+    #[serde(default = "ProgressiveSettings::default_samples_per_pass")]
+    pub samples_per_pass: u32,
+    pub time_budget: Option<f32>,
+}
+
+impl ProgressiveSettings {
+    pub fn default_samples_per_pass() -> u32 {
+        4
+    }
+}
+
+// Joint non-local means denoising, see `denoise`. Distances of colors are measured relative
+// to the noise of the pixels, the albedo and normals of the first hits keep texture and
+// geometry edges apart
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone)]
+pub struct DenoiseSettings {
+    // pixels this far apart are averaged
+    #[serde(default = "DenoiseSettings::default_radius")]
+    pub radius: u32,
+    // colors are compared over patches of (2 * patch_radius + 1)^2 pixels
+    #[serde(default = "DenoiseSettings::default_patch_radius")]
+    pub patch_radius: u32,
+    // larger values smooth more, color differences below strength * noise count as equal
+    #[serde(default = "DenoiseSettings::default_strength")]
+    pub strength: f32,
+    // albedo and normal differences at which the weights drop to exp(-1/2)
+    #[serde(default = "DenoiseSettings::default_albedo_sigma")]
+    pub albedo_sigma: f32,
+    #[serde(default = "DenoiseSettings::default_normal_sigma")]
+    pub normal_sigma: f32,
+    // also write the image before denoising as <name>_noisy.<format>
+    #[serde(default)]
+    pub keep_noisy: bool,
+}
+
+impl DenoiseSettings {
+    pub fn default_radius() -> u32 {
+        7
+    }
+
+    pub fn default_patch_radius() -> u32 {
+        1
+    }
+
+    pub fn default_strength() -> f32 {
+        0.7
+    }
+
+    pub fn default_albedo_sigma() -> f32 {
+        0.1
+    }
+
+    pub fn default_normal_sigma() -> f32 {
+        0.25
+    }
+}
+
+impl Default for DenoiseSettings {
+    fn default() -> Self {
+        Self {
+            radius: Self::default_radius(),
+            patch_radius: Self::default_patch_radius(),
+            strength: Self::default_strength(),
+            albedo_sigma: Self::default_albedo_sigma(),
+            normal_sigma: Self::default_normal_sigma(),
+            keep_noisy: false,
+        }
+    }
+}
+
+// progressive photon mapping, each of the `nsamples` passes shoots a new set of photons
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone)]
+pub struct PhotonMappingSettings {
+    #[serde(default = "PhotonMappingSettings::default_photons_per_pass")]
+    pub photons_per_pass: usize,
+    // gather radius of the first pass, defaults to 1% of the world bounding sphere radius
+    pub initial_radius: Option<f32>,
+    // fraction of the new photons kept after each pass, the radius shrinks faster when smaller
+    #[serde(default = "PhotonMappingSettings::default_alpha")]
+    pub alpha: f32,
+}
+
+impl PhotonMappingSettings {
+    pub fn default_photons_per_pass() -> usize {
+        100000
+    }
+
+    pub fn default_alpha() -> f32 {
+        2.0 / 3.0
+    }
+}
+
+impl Default for PhotonMappingSettings {
+    fn default() -> Self {
+        Self {
+            photons_per_pass: Self::default_photons_per_pass(),
+            initial_radius: None,
+            alpha: Self::default_alpha(),
+        }
+    }
+}
+
+// Tone mapping operators for the displayed image, exr, pfm and hdr files keep the radiance
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ToneMapConfig {
+    // values above 1 clip
+    Clamp {},
+    Reinhard {},
+    // Reinhard with the luminance `white` mapped to 1, the brightest pixel by default
+    ExtendedReinhard { white: Option<f32> },
+    // filmic curve of ACES
+    Aces {},
+    // AgX-like, desaturates bright colors smoothly
+    Agx {},
+}
+
+impl Default for ToneMapConfig {
+    fn default() -> Self {
+        ToneMapConfig::Clamp {}
+    }
+}
+
+// Colors of the project and its textures are given in rec709, the primaries of sRGB, and
+// converted to the working space when the scene is built. The rendered image is converted
+// back. Wider spaces mix saturated colors more like light does
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkingSpace {
+    #[default]
+    Rec709,
+    Rec2020,
+    Acescg,
+}
+
+impl WorkingSpace {
+    // rec709 to the working space, for colors of the scene
+    pub fn input_matrix(&self) -> Matrix3<f32> {
+        match self {
+            WorkingSpace::Rec709 => Matrix3::identity(),
+            WorkingSpace::Rec2020 => color_space::rec709_to_rec2020(),
+            WorkingSpace::Acescg => color_space::rec709_to_acescg(),
+        }
+    }
+
+    // the working space to rec709, for the image
+    pub fn output_matrix(&self) -> Matrix3<f32> {
+        match self {
+            WorkingSpace::Rec709 => Matrix3::identity(),
+            WorkingSpace::Rec2020 => color_space::rec2020_to_rec709(),
+            WorkingSpace::Acescg => color_space::acescg_to_rec709(),
+        }
+    }
+}
+
+// png is for viewing, the others keep the linear radiance as floats for compositing
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    #[default]
+    Png,
+    // OpenEXR
+    Exr,
+    // portable float map
+    Pfm,
+    // Radiance RGBE
+    Hdr,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Exr => "exr",
+            OutputFormat::Pfm => "pfm",
+            OutputFormat::Hdr => "hdr",
+        }
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        [
+            OutputFormat::Png,
+            OutputFormat::Exr,
+            OutputFormat::Pfm,
+            OutputFormat::Hdr,
+        ]
+        .into_iter()
+        .find(|format| format.extension() == ext)
+        .with_context(|| format!("unknown image format of {}", path.display()))
+    }
+}
+
+// Arbitrary output variables, properties of what the pixels see for compositing and denoising.
+// They become layers of the exr file, or <name>_<aov>.<format> files for the other formats.
+// Depths and ids are the value of the first sample of a pixel, the others are averaged
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Aov {
+    // distance from the camera to the first surface hit, 0 if none
+    Depth,
+    // world space shading normal of the first hit, facing the camera
+    Normal,
+    // albedo of the material first hit
+    Albedo,
+    // texture coordinates of the first hit
+    Uv,
+    // numbers of the object and the material first hit, counted from 1, 0 if none
+    PrimitiveId,
+    MaterialId,
+    // light which reached the camera after at most one bounce, path integrator only
+    Direct,
+    // the rest of the rendered image
+    Indirect,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 8] = [
+        Aov::Depth,
+        Aov::Normal,
+        Aov::Albedo,
+        Aov::Uv,
+        Aov::PrimitiveId,
+        Aov::MaterialId,
+        Aov::Direct,
+        Aov::Indirect,
+    ];
+
+    // of the files and exr layers
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::Uv => "uv",
+            Aov::PrimitiveId => "primitive_id",
+            Aov::MaterialId => "material_id",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+        }
+    }
+
+    // averaging depths or ids across edges makes up values no surface has
+    pub fn is_averaged(&self) -> bool {
+        !matches!(self, Aov::Depth | Aov::PrimitiveId | Aov::MaterialId)
+    }
+
+    // colors in the working space, converted like the rendered image
+    pub fn is_color(&self) -> bool {
+        matches!(self, Aov::Albedo | Aov::Direct | Aov::Indirect)
+    }
+
+    // of the exr layer, depths and ids are single values
+    pub fn channels(&self) -> usize {
+        if self.is_averaged() {
+            3
+        } else {
+            1
+        }
+    }
+
+    // needs the direct part of the radiance from the integrator
+    pub fn is_split(&self) -> bool {
+        matches!(self, Aov::Direct | Aov::Indirect)
+    }
+}
+
+// how light sampling and bsdf sampling are combined
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MisHeuristic {
+    // pick one of them per bounce with probability `mis_weight`, no shadow rays
+    Mixture,
+    // next event estimation with shadow rays, weighted by the balance heuristic
+    Balance,
+    // next event estimation with shadow rays, weighted by the power heuristic
+    #[default]
+    Power,
+}
+
+impl MisHeuristic {
+    // weight of a sample drawn with `pdf_f`, while `pdf_g` could also produce it
+    pub fn weight(&self, pdf_f: f32, pdf_g: f32) -> f32 {
+        let (f, g) = match self {
+            MisHeuristic::Power => (pdf_f * pdf_f, pdf_g * pdf_g),
+            _ => (pdf_f, pdf_g),
+        };
+
+        if f.is_infinite() {
+            return 1.0;
+        }
+        if f + g <= 0.0 {
+            return 0.0;
+        }
+        f / (f + g)
+    }
 }
 
 impl Settings {
@@ -22,20 +430,43 @@ impl Settings {
     pub fn get_aspect(&self) -> f32 {
         return self.width as f32 / self.height as f32;
     }
+
+    // the aovs the film collects: those written and the features of the denoiser
+    pub fn film_aovs(&self) -> Vec<Aov> {
+        let mut aovs = self.aovs.clone();
+        if self.denoise.is_some() {
+            for aov in [Aov::Albedo, Aov::Normal] {
+                if !aovs.contains(&aov) {
+                    aovs.push(aov);
+                }
+            }
+        }
+        aovs
+    }
 }
 
 pub struct Project {
     pub(crate) name: String,
     pub(crate) settings: Settings,
     pub(crate) scene: Scene,
+    pub(crate) integrator: IntegratorPtr,
+    pub(crate) filter: FilterPtr,
 }
 
 impl Project {
-    pub fn new(name: String, settings: Settings, scene: Scene) -> Self {
+    pub fn new(
+        name: String,
+        settings: Settings,
+        scene: Scene,
+        integrator: IntegratorPtr,
+        filter: FilterPtr,
+    ) -> Self {
         Self {
             name,
             settings,
             scene,
+            integrator,
+            filter,
         }
     }
 
@@ -51,6 +482,18 @@ impl Project {
         &self.scene
     }
 
+    /// Get a reference to the project's integrator.
+    #[must_use]
+    pub fn integrator(&self) -> &IntegratorPtr {
+        &self.integrator
+    }
+
+    /// Get a reference to the project's pixel filter.
+    #[must_use]
+    pub fn filter(&self) -> &FilterPtr {
+        &self.filter
+    }
+
     /// Get a reference to the project's name.
     #[must_use]
     pub fn name(&self) -> &str {
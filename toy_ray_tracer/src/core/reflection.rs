@@ -17,7 +17,7 @@ impl Bsdf {
         self.bxdf = bxdf
     }
 
-    pub fn set_raw<T: Bxdf + 'static>(&mut self, bxdf: T) {
+    pub fn set_raw<T: Bxdf + Sync + Send + 'static>(&mut self, bxdf: T) {
         self.bxdf = Box::new(bxdf);
     }
 }
@@ -31,8 +31,7 @@ impl Bsdf {
 
     // f_r(w_i, w_o) -> value, brdf function
     #[inline]
-    #[allow(dead_code)]
-    fn f(&self, wi: &Vec3f, wo: &Vec3f) -> Spectrum {
+    pub fn f(&self, wi: &Vec3f, wo: &Vec3f) -> Spectrum {
         self.bxdf.f(wi, wo, &self.normal)
     }
 
@@ -72,7 +71,7 @@ pub trait Bxdf {
     fn sample_pdf(&self, wi: &Vec3f, wo: &Vec3f, normal: &Vec3f) -> f32;
 }
 
-pub type BxdfPtr = Box<dyn Bxdf>;
+pub type BxdfPtr = Box<dyn Bxdf + Sync + Send>;
 
 pub struct NopBxdf {}
 impl Bxdf for NopBxdf {
@@ -1,15 +1,26 @@
+// Random numbers of the samples of a pixel, handed out one dimension (or a pair of them) at
+// a time. The n-th draw of all samples of a pixel comes from one well distributed set of
+// points, so that e.g. the lens positions of a pixel cover the lens evenly
+pub trait PixelSampler {
+    // start sample `index` of `pixel`, or of any other stream of samples like a photon
+    fn start_sample(&mut self, pixel: u64, index: u64);
+
+    fn get_1d(&mut self) -> f32;
+
+    // two dimensions meant to be used together, e.g. a point on the lens or a direction
+    fn get_2d(&mut self) -> (f32, f32);
+}
+
+pub type PixelSamplerPtr = Box<dyn PixelSampler + Send>;
+
 pub mod sample {
     use std::f32::consts::FRAC_1_PI;
 
     use crate::core::{vec3, Vec3f};
+    use crate::utils::random;
 
     pub fn sample_hemisphere_cos_wi(normal: &Vec3f) -> Vec3f {
-        let wi = vec3::random_cosine_direction();
-        if wi.dot(normal) > 0.0 {
-            wi
-        } else {
-            -wi
-        }
+        vec3::onb_fromz(normal) * vec3::random_cosine_direction()
     }
 
     pub fn sample_hemisphere_cos_pdf(normal: &Vec3f, wi: &Vec3f) -> f32 {
@@ -20,4 +31,19 @@ pub mod sample {
             cosine * FRAC_1_PI
         }
     }
+
+    // index of an item picked proportional to its area, so that all items together are
+    // sampled uniformly
+    pub fn sample_by_area<T>(items: &[T], area: impl Fn(&T) -> f32) -> usize {
+        let total: f32 = items.iter().map(&area).sum();
+        let mut target = random::f32() * total;
+        for (idx, item) in items.iter().enumerate() {
+            let item_area = area(item);
+            if target < item_area {
+                return idx;
+            }
+            target -= item_area;
+        }
+        items.len() - 1
+    }
 }
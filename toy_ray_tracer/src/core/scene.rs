@@ -1,29 +1,53 @@
 use std::sync::Arc;
 
+use crate::stats::{self, Counter};
 use crate::{core::Camera, lights::LightList};
 
-use super::{LightPtr, PrimitiveContainerPtr, PrimitivePtr};
+use super::{LightPtr, PrimitiveContainerPtr, PrimitivePtr, Ray, SurfaceInteraction};
 
 pub struct Scene {
     pub(crate) camera: Arc<Camera>,
     pub(crate) world: PrimitiveContainerPtr,
     pub lights: LightList,
+    // the scene contains media or surfaces without material, shadow rays must look through them
+    pub(crate) has_media: bool,
 }
 
 impl Scene {
-    pub fn new(camera: Arc<Camera>, world: PrimitiveContainerPtr, lights: Vec<LightPtr>) -> Self {
+    pub fn new(
+        camera: Arc<Camera>,
+        world: PrimitiveContainerPtr,
+        lights: Vec<LightPtr>,
+        has_media: bool,
+    ) -> Self {
         Self {
             camera,
             world,
             lights: lights.into(),
+            has_media,
         }
     }
+
+    // the queries of the integrators, counted as rays for the stats
+    pub fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<SurfaceInteraction<'_>> {
+        stats::add(Counter::SceneRays, 1);
+        self.world.intersect(ray, t_min, t_max)
+    }
+
+    pub fn intersect_p(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        stats::add(Counter::SceneRays, 1);
+        self.world.intersect_p(ray, t_min, t_max)
+    }
 }
 
 pub struct SceneBundle {
     pub primitives: Vec<PrimitivePtr>,
     pub lights: Vec<LightPtr>,
     pub camera: Option<Arc<Camera>>,
+    pub has_media: bool,
+    // media which scatter or absorb, not only the surfaces without material
+    pub has_participating_media: bool,
+    pub has_subsurface: bool,
 }
 
 impl Default for SceneBundle {
@@ -32,6 +56,9 @@ impl Default for SceneBundle {
             primitives: Vec::new(),
             lights: Vec::new(),
             camera: Default::default(),
+            has_media: false,
+            has_participating_media: false,
+            has_subsurface: false,
         }
     }
 }
@@ -40,6 +67,9 @@ impl SceneBundle {
     pub fn union_assign(&mut self, mut other: SceneBundle) {
         self.primitives.append(&mut other.primitives);
         self.lights.append(&mut other.lights);
+        self.has_media |= other.has_media;
+        self.has_participating_media |= other.has_participating_media;
+        self.has_subsurface |= other.has_subsurface;
         if let Some(camera) = other.camera {
             self.camera = Some(camera);
         }
@@ -7,12 +7,10 @@ pub trait Shape: Sync + Send {
 
     fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<SurfaceInteraction>;
 
-    fn intersect_p(&self, ray: &Ray) -> bool {
+    // any hit in (t_min, t_max), shapes should override it to skip building the interaction
+    fn intersect_p(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
         // naive implementation
-        match self.intersect(ray, 0.0, f32::INFINITY) {
-            Some(_) => true,
-            None => false,
-        }
+        self.intersect(ray, t_min, t_max).is_some()
     }
 
     fn sample_pdf(&self, _point: &Point3f, _wi: &Vec3f) -> f32 {
@@ -22,6 +20,11 @@ pub trait Shape: Sync + Send {
     fn sample_wi(&self, _point: &Point3f) -> Vec3f {
         unimplemented!()
     }
+
+    fn area(&self) -> f32;
+
+    // uniformly sample a point on the surface (pdf is 1 / area), used to emit light from it
+    fn sample_area(&self) -> SurfaceInteraction<'_>;
 }
 
 pub type ShapePtr = Arc<dyn Shape + Sync + Send>;
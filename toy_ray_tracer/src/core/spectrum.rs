@@ -1,3 +1,272 @@
 use nalgebra::Vector3;
 
-pub type Spectrum = Vector3<f32>;
\ No newline at end of file
+pub type Spectrum = Vector3<f32>;
+
+// index of refraction, the wavelength `lambda` is in nm
+#[derive(Debug, Clone)]
+pub enum Ior {
+    Constant(f32),
+    // n = a + b / lambda^2, lambda in um
+    Cauchy { a: f32, b: f32 },
+    // n^2 = 1 + sum of b_i lambda^2 / (lambda^2 - c_i), lambda in um
+    Sellmeier { b: Vec<f32>, c: Vec<f32> },
+}
+
+// the sodium d line, where catalogs quote the index of refraction of glass
+const D_LINE: f32 = 587.6;
+
+impl Ior {
+    pub fn at(&self, lambda: f32) -> f32 {
+        let l2 = (lambda * 1e-3) * (lambda * 1e-3);
+        match self {
+            Ior::Constant(eta) => *eta,
+            Ior::Cauchy { a, b } => a + b / l2,
+            Ior::Sellmeier { b, c } => {
+                let n2 = 1.0
+                    + b.iter()
+                        .zip(c.iter())
+                        .map(|(b, c)| b * l2 / (l2 - c))
+                        .sum::<f32>();
+                n2.max(1.0).sqrt()
+            }
+        }
+    }
+
+    // eta for the path being traced: with sampled wavelengths the one of the hero wavelength,
+    // the other wavelengths would be refracted into other directions and are dropped. Without
+    // them the index at the d line
+    pub fn eta(&self) -> f32 {
+        if let Ior::Constant(eta) = self {
+            return *eta;
+        }
+        match spectral::hero() {
+            Some(lambda) => {
+                spectral::terminate_secondary();
+                self.at(lambda)
+            }
+            None => self.at(D_LINE),
+        }
+    }
+}
+
+// Spectral rendering with hero wavelength sampling, Wilkie et al. 2014: every camera sample
+// traces three wavelengths spread evenly over the visible range, starting at a random hero
+// wavelength. The components of a `Spectrum` then hold the values at these wavelengths
+// instead of red, green and blue, RGB colors of the scene are turned into spectra where
+// they're used with `from_rgb`, and the radiance is turned back into RGB with `to_rgb`.
+// Without wavelengths of the current thread, e.g. when not enabled in the settings,
+// `Spectrum` is RGB
+pub mod spectral {
+    use std::cell::Cell;
+
+    use crate::core::{Color3, Spectrum, Vec3f};
+
+    pub const LAMBDA_MIN: f32 = 380.0;
+    pub const LAMBDA_MAX: f32 = 720.0;
+
+    #[derive(Debug, Clone, Copy)]
+    struct Wavelengths {
+        lambda: [f32; 3],
+        // only the hero wavelength is carried on, e.g. after dispersion
+        terminated: bool,
+    }
+
+    thread_local! {
+        static WAVELENGTHS: Cell<Option<Wavelengths>> = const { Cell::new(None) };
+    }
+
+    // start a camera sample with the hero wavelength at `u` in [0, 1) of the visible range
+    pub fn start_sample(u: f32) {
+        let mut lambda = [0.0; 3];
+        for (i, l) in lambda.iter_mut().enumerate() {
+            let t = (u + i as f32 / 3.0).fract();
+            *l = LAMBDA_MIN + t * (LAMBDA_MAX - LAMBDA_MIN);
+        }
+        WAVELENGTHS.with(|w| {
+            w.set(Some(Wavelengths {
+                lambda,
+                terminated: false,
+            }))
+        });
+    }
+
+    // back to RGB rendering
+    pub fn clear() {
+        WAVELENGTHS.with(|w| w.set(None));
+    }
+
+    pub fn hero() -> Option<f32> {
+        WAVELENGTHS.with(|w| w.get().map(|w| w.lambda[0]))
+    }
+
+    pub fn terminate_secondary() {
+        WAVELENGTHS.with(|w| {
+            if let Some(mut wavelengths) = w.get() {
+                wavelengths.terminated = true;
+                w.set(Some(wavelengths));
+            }
+        });
+    }
+
+    // Smits 1999, "An RGB-to-spectrum conversion for reflectances": spectra of white and
+    // the primary and secondary colors, in 10 bins over the visible range
+    const WHITE: [f32; 10] = [
+        1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+    ];
+    const CYAN: [f32; 10] = [
+        0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+    ];
+    const MAGENTA: [f32; 10] = [
+        1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+    ];
+    const YELLOW: [f32; 10] = [
+        0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+    ];
+    const RED: [f32; 10] = [
+        0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+    ];
+    const GREEN: [f32; 10] = [
+        0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025,
+    ];
+    const BLUE: [f32; 10] = [
+        1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+    ];
+
+    // value at `lambda` of a spectrum with the color `rgb`: the smallest component is white,
+    // the next one is made up of the secondary color of both and the rest is the primary
+    pub fn upsample(rgb: &Color3, lambda: f32) -> f32 {
+        let t = (lambda - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN);
+        let bin = ((t * 10.0) as usize).min(9);
+        let (r, g, b) = (rgb.x, rgb.y, rgb.z);
+        if r <= g && r <= b {
+            r * WHITE[bin]
+                + if g <= b {
+                    (g - r) * CYAN[bin] + (b - g) * BLUE[bin]
+                } else {
+                    (b - r) * CYAN[bin] + (g - b) * GREEN[bin]
+                }
+        } else if g <= r && g <= b {
+            g * WHITE[bin]
+                + if r <= b {
+                    (r - g) * MAGENTA[bin] + (b - r) * BLUE[bin]
+                } else {
+                    (b - g) * MAGENTA[bin] + (r - b) * RED[bin]
+                }
+        } else {
+            b * WHITE[bin]
+                + if r <= g {
+                    (r - b) * YELLOW[bin] + (g - r) * GREEN[bin]
+                } else {
+                    (g - b) * YELLOW[bin] + (r - g) * RED[bin]
+                }
+        }
+    }
+
+    // a color of the scene, e.g. an albedo or emission, as the current thread renders it
+    pub fn from_rgb(rgb: &Color3) -> Spectrum {
+        match WAVELENGTHS.with(|w| w.get()) {
+            Some(w) => Spectrum::new(
+                upsample(rgb, w.lambda[0]),
+                upsample(rgb, w.lambda[1]),
+                upsample(rgb, w.lambda[2]),
+            ),
+            None => *rgb,
+        }
+    }
+
+    // CIE 1931 color matching functions, the multi-lobe fit of Wyman et al. 2013
+    fn cmf(lambda: f32) -> Vec3f {
+        let g = |mu: f32, sigma1: f32, sigma2: f32| {
+            let t = (lambda - mu) / if lambda < mu { sigma1 } else { sigma2 };
+            (-0.5 * t * t).exp()
+        };
+        Vec3f::new(
+            1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7)
+                - 0.065 * g(501.1, 20.4, 26.2),
+            0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+            1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+        )
+    }
+
+    // integral of the fitted y over the visible range
+    const CIE_Y_INTEGRAL: f32 = 106.9119;
+
+    // linear sRGB of the equal energy spectrum, divided out so that it stays white
+    const WHITE_RGB: [f32; 3] = [1.200_606, 0.949_638, 0.907_908];
+
+    pub fn xyz_to_rgb(xyz: &Vec3f) -> Color3 {
+        Color3::new(
+            3.240_454 * xyz.x - 1.537_138 * xyz.y - 0.498_531 * xyz.z,
+            -0.969_266 * xyz.x + 1.876_011 * xyz.y + 0.041_556 * xyz.z,
+            0.055_643 * xyz.x - 0.204_026 * xyz.y + 1.057_225 * xyz.z,
+        )
+    }
+
+    // radiance at the wavelengths of the current sample as linear sRGB, unchanged without
+    pub fn to_rgb(l: &Spectrum) -> Color3 {
+        let w = match WAVELENGTHS.with(|w| w.get()) {
+            Some(w) => w,
+            None => return *l,
+        };
+
+        // Monte Carlo estimate of the integral over the visible range, every wavelength was
+        // sampled uniformly
+        let n = if w.terminated { 1 } else { 3 };
+        let mut xyz = Vec3f::zeros();
+        for i in 0..n {
+            xyz += cmf(w.lambda[i]) * l[i];
+        }
+        xyz *= (LAMBDA_MAX - LAMBDA_MIN) / (n as f32 * CIE_Y_INTEGRAL);
+
+        let rgb = xyz_to_rgb(&xyz);
+        Color3::new(
+            rgb.x / WHITE_RGB[0],
+            rgb.y / WHITE_RGB[1],
+            rgb.z / WHITE_RGB[2],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{spectral, Ior};
+    use crate::core::Color3;
+
+    // colors turned into spectra and integrated back over many wavelengths
+    #[test]
+    fn test_rgb_roundtrip() {
+        for rgb in [
+            Color3::new(1.0, 1.0, 1.0),
+            Color3::new(0.65, 0.05, 0.05),
+            Color3::new(0.12, 0.45, 0.15),
+            Color3::new(0.2, 0.5, 0.9),
+        ] {
+            let n = 3000;
+            let mut sum = Color3::zeros();
+            for k in 0..n {
+                spectral::start_sample((k as f32 + 0.5) / n as f32);
+                sum += spectral::to_rgb(&spectral::from_rgb(&rgb));
+            }
+            spectral::clear();
+            let rgb2 = sum / n as f32;
+            assert!((rgb - rgb2).abs().max() < 0.02, "{:?} {:?}", rgb, rgb2);
+        }
+    }
+
+    #[test]
+    fn test_dispersion() {
+        // BK7 glass
+        let sellmeier = Ior::Sellmeier {
+            b: vec![1.039_612, 0.231_792, 1.010_469],
+            c: vec![0.006_000_7, 0.020_017_9, 103.560_65],
+        };
+        assert!((sellmeier.at(587.6) - 1.5168).abs() < 1e-3);
+        assert!(sellmeier.at(450.0) > sellmeier.at(650.0));
+
+        let cauchy = Ior::Cauchy {
+            a: 1.5046,
+            b: 0.0042,
+        };
+        assert!((cauchy.eta() - sellmeier.eta()).abs() < 2e-3);
+    }
+}
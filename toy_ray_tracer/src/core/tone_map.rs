@@ -0,0 +1,120 @@
+use nalgebra::Matrix3;
+
+use super::{vec3, Color3};
+
+// Tone mapping operators, from linear rec.709 radiance to display referred values in [0, 1]
+// that are then encoded with `srgb_oetf`
+
+// Reinhard et al. 2002 on the luminance, the hue is kept
+pub fn reinhard(c: &Color3) -> Color3 {
+    scale_luminance(c, |l| l / (1.0 + l))
+}
+
+// Reinhard with the luminance `white` mapped to 1, brighter values clip
+pub fn extended_reinhard(c: &Color3, white: f32) -> Color3 {
+    let w2 = (white * white).max(1e-6);
+    scale_luminance(c, |l| l * (1.0 + l / w2) / (1.0 + l))
+}
+
+fn scale_luminance(c: &Color3, f: impl Fn(f32) -> f32) -> Color3 {
+    let l = vec3::luminance(c);
+    if l <= 0.0 {
+        return Color3::zeros();
+    }
+    c * (f(l) / l)
+}
+
+// the ACES reference rendering and sRGB output transforms, fitted by Stephen Hill
+pub fn aces(c: &Color3) -> Color3 {
+    // sRGB to the ACES rendering space with the RRT saturation
+    let input = Matrix3::new(
+        0.59719, 0.35458, 0.04823, //
+        0.07600, 0.90834, 0.01566, //
+        0.02840, 0.13383, 0.83777,
+    );
+    let output = Matrix3::new(
+        1.60475, -0.53108, -0.07367, //
+        -0.10208, 1.10813, -0.00605, //
+        -0.00327, -0.07276, 1.07602,
+    );
+
+    let v = input * c;
+    let v = v.map(|v| {
+        let a = v * (v + 0.024_578_6) - 0.000_090_537;
+        let b = v * (0.983_729 * v + 0.432_951) + 0.238_081;
+        a / b
+    });
+    (output * v).map(|v| v.clamp(0.0, 1.0))
+}
+
+// AgX after Troy Sobotka, the polynomial fit of the sigmoid by Benjamin Wrensch: the color is
+// squeezed towards white in log space, bright saturated lights desaturate instead of
+// skewing their hue
+pub fn agx(c: &Color3) -> Color3 {
+    const MIN_EV: f32 = -12.473_93;
+    const MAX_EV: f32 = 4.026_069;
+
+    let inset = Matrix3::new(
+        0.842479, 0.0784336, 0.0792237, //
+        0.0423282, 0.8784686, 0.0791661, //
+        0.0423757, 0.0784336, 0.879143,
+    );
+    let outset = Matrix3::new(
+        1.196879, -0.0980209, -0.0990297, //
+        -0.0528969, 1.1519031, -0.0989612, //
+        -0.0529716, -0.0980435, 1.1510737,
+    );
+
+    let v = (inset * c).map(|v| {
+        let x = (v.max(1e-10).log2().clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+        let x2 = x * x;
+        let x4 = x2 * x2;
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.00232
+    });
+    // the sigmoid gives display values for a 2.2 gamma, made linear again
+    (outset * v).map(|v| v.max(0.0).powf(2.2).min(1.0))
+}
+
+// the piecewise sRGB transfer function, linear [0, 1] to the encoded value
+pub fn srgb_oetf(v: f32) -> f32 {
+    if v <= 0.003_130_8 {
+        12.92 * v
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{aces, agx, extended_reinhard, reinhard, srgb_oetf};
+    use crate::core::{vec3, Color3};
+
+    #[test]
+    fn test_tone_map() {
+        assert_eq!(srgb_oetf(0.0), 0.0);
+        assert!((srgb_oetf(1.0) - 1.0).abs() < 1e-6);
+        assert!((srgb_oetf(0.18) - 0.4614).abs() < 1e-3);
+        // both pieces meet
+        assert!((srgb_oetf(0.003_130_8) - srgb_oetf(0.003_130_9)).abs() < 1e-5);
+
+        let white = Color3::new(4.0, 4.0, 4.0);
+        assert!(
+            (extended_reinhard(&white, 4.0) - vec3::scalar(1.0))
+                .abs()
+                .max()
+                < 1e-5
+        );
+        for op in [reinhard, aces, agx] {
+            let mut last = 0.0;
+            for i in 0..20 {
+                let c = op(&vec3::scalar(0.01 * 2f32.powi(i)));
+                assert!(c.iter().all(|v| (0.0..=1.0).contains(v)), "{:?}", c);
+                // monotonic and neutral on grey
+                assert!(c.x >= last && (c.x - c.y).abs() < 1e-2 && (c.x - c.z).abs() < 1e-2);
+                last = c.x;
+            }
+            assert!(last > 0.9);
+        }
+    }
+}
@@ -35,16 +35,6 @@ impl Vec3List {
         self.0.resize(new_len, Vec3f::zeros())
     }
 
-    pub(crate) fn add(&self, rhs: &Vec3List) -> Self {
-        let v: Vec<Vec3f> = self
-            .0
-            .iter()
-            .zip(rhs.0.iter())
-            .map(|(v1, v2)| v1 + v2)
-            .collect();
-        Self(v)
-    }
-
     pub(crate) fn iter(&self) -> std::slice::Iter<Vec3f> {
         self.0.iter()
     }
@@ -71,7 +61,10 @@ impl IndexMut<usize> for Vec3List {
 
 // utils for Vec3
 pub mod vec3 {
-    use std::f32::{consts::PI, EPSILON};
+    use std::f32::{
+        consts::{FRAC_PI_2, FRAC_PI_4, PI},
+        EPSILON,
+    };
 
     use nalgebra::Matrix3;
 
@@ -101,7 +94,7 @@ pub mod vec3 {
     #[inline]
     pub fn is_near_zero(v: &Vec3f) -> bool {
         let eps = EPSILON;
-        return v[0] < eps && v[1] < eps && v[2] < eps;
+        return v[0].abs() < eps && v[1].abs() < eps && v[2].abs() < eps;
     }
 
     #[inline]
@@ -129,15 +122,20 @@ pub mod vec3 {
         }
     }
 
-    #[allow(dead_code)]
+    // concentric mapping of the unit square, keeps the strata of the sampler in place
     pub fn random_in_unit_disk() -> Vec3f {
-        let unit = Vec3f::new(1.0, 1.0, 0.0);
-        loop {
-            let p = 2.0 * Vec3f::new(random::f32(), random::f32(), 0.0) - unit;
-            if p.dot(&p) < 1.0 {
-                return p;
-            }
+        let (r1, r2) = random::f32_2d();
+        let (x, y) = (2.0 * r1 - 1.0, 2.0 * r2 - 1.0);
+        if x == 0.0 && y == 0.0 {
+            return Vec3f::zeros();
         }
+
+        let (r, theta) = if x.abs() > y.abs() {
+            (x, FRAC_PI_4 * (y / x))
+        } else {
+            (y, FRAC_PI_2 - FRAC_PI_4 * (x / y))
+        };
+        Vec3f::new(r * theta.cos(), r * theta.sin(), 0.0)
     }
 
     #[allow(dead_code)]
@@ -156,8 +154,7 @@ pub mod vec3 {
     }
 
     pub fn random_cosine_direction() -> Vec3f {
-        let r1 = random::f32();
-        let r2 = random::f32();
+        let (r1, r2) = random::f32_2d();
         let z = (1.0 - r2).sqrt();
 
         let phi = 2.0 * PI * r1;
@@ -168,8 +165,7 @@ pub mod vec3 {
     }
 
     pub fn random_to_sphere(radius: f32, distance_squared: f32) -> Vec3f {
-        let r1 = random::f32();
-        let r2 = random::f32();
+        let (r1, r2) = random::f32_2d();
         let z = 1.0 + r2 * ((1.0 - radius * radius / distance_squared).sqrt() - 1.0);
 
         let phi = 2.0 * PI * r1;
@@ -181,8 +177,7 @@ pub mod vec3 {
     }
 
     pub fn random_env_sphere() -> Vec3f {
-        let r1 = random::f32();
-        let r2 = random::f32();
+        let (r1, r2) = random::f32_2d();
         let z = 2.0 * r2 - 1.0;
 
         let r = (1.0 - z * z).clamp(0.0, 1.0).sqrt();
@@ -227,6 +222,12 @@ pub mod vec3 {
         v.map(|e| e.sqrt())
     }
 
+    // relative luminance of linear rec.709 rgb
+    #[inline]
+    pub fn luminance(v: &Vec3f) -> f32 {
+        0.2126 * v.x + 0.7152 * v.y + 0.0722 * v.z
+    }
+
     #[inline]
     pub fn dot(v1: &Vec3f, v2: &Vec3f) -> f32 {
         return v1.dot(v2);
@@ -0,0 +1,189 @@
+use rayon::prelude::*;
+
+use crate::core::{Aov, DenoiseSettings, Film, HdrImage, Vec3f};
+
+// keeps the color distance finite for pixels without noise
+const EPSILON: f32 = 1e-10;
+
+// Joint non-local means filter (Rousselle et al. 2013, "Robust denoising using feature and
+// color information"): every pixel becomes the weighted average of the pixels within
+// `radius`. A neighbor gets a high weight when the patch around it has similar colors,
+// relative to the variance of the pixel means, and when its albedo and normal are close.
+// Noise averages out in flat regions while texture and geometry edges stay sharp.
+// `variance` is per pixel the variance of its mean luminance, infinite where it's unknown,
+// the features alone guide the weights there
+pub fn denoise(
+    color: &HdrImage,
+    variance: &[f32],
+    albedo: &HdrImage,
+    normal: &HdrImage,
+    settings: &DenoiseSettings,
+) -> HdrImage {
+    let (width, height) = (color.width(), color.height());
+    let radius = settings.radius as isize;
+    let k2 = settings.strength * settings.strength;
+    let albedo_scale = 1.0 / (2.0 * settings.albedo_sigma * settings.albedo_sigma);
+    let normal_scale = 1.0 / (2.0 * settings.normal_sigma * settings.normal_sigma);
+
+    let mut sum = vec![Vec3f::zeros(); width * height];
+    let mut weights = vec![0.0f32; width * height];
+    // one neighbor offset at a time, the patch distances of all pixels are box filtered
+    // distances of single pixels
+    let mut distances = vec![0.0f32; width * height];
+    let mut valid = vec![0.0f32; width * height];
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            let neighbor = |x: usize, y: usize| {
+                let (qx, qy) = (x as isize + dx, y as isize + dy);
+                let inside = qx >= 0 && qy >= 0 && qx < width as isize && qy < height as isize;
+                inside.then(|| qy as usize * width + qx as usize)
+            };
+
+            distances
+                .par_chunks_mut(width)
+                .zip(valid.par_chunks_mut(width))
+                .enumerate()
+                .for_each(|(y, (distances, valid))| {
+                    for x in 0..width {
+                        let p = y * width + x;
+                        (distances[x], valid[x]) = match neighbor(x, y) {
+                            Some(q) => {
+                                let (vp, vq) = (variance[p], variance[q]);
+                                if (vp + vq).is_finite() {
+                                    let d = color.get_pixel(p) - color.get_pixel(q);
+                                    let noise = vp + vp.min(vq);
+                                    let d = (d.norm_squared() / 3.0 - noise)
+                                        / (EPSILON + k2 * (vp + vq));
+                                    (d, 1.0)
+                                } else {
+                                    (0.0, 1.0)
+                                }
+                            }
+                            None => (0.0, 0.0),
+                        };
+                    }
+                });
+            let patch = box_sum(&distances, width, height, settings.patch_radius as usize);
+            let patch_valid = box_sum(&valid, width, height, settings.patch_radius as usize);
+
+            sum.par_chunks_mut(width)
+                .zip(weights.par_chunks_mut(width))
+                .enumerate()
+                .for_each(|(y, (sum, weights))| {
+                    for x in 0..width {
+                        let p = y * width + x;
+                        let q = match neighbor(x, y) {
+                            Some(q) => q,
+                            None => continue,
+                        };
+                        let color_distance = (patch[p] / patch_valid[p]).max(0.0);
+                        let albedo_distance =
+                            (albedo.get_pixel(p) - albedo.get_pixel(q)).norm_squared();
+                        let normal_distance =
+                            (normal.get_pixel(p) - normal.get_pixel(q)).norm_squared();
+                        let w = (-color_distance
+                            - albedo_distance * albedo_scale
+                            - normal_distance * normal_scale)
+                            .exp();
+                        sum[x] += color.get_pixel(q) * w;
+                        weights[x] += w;
+                    }
+                });
+        }
+    }
+
+    let mut denoised = HdrImage::new(width, height);
+    for (idx, (c, w)) in sum.iter().zip(weights.iter()).enumerate() {
+        // the pixel itself always has weight 1
+        denoised.set_pixel(idx, c / *w);
+    }
+    denoised
+}
+
+// the radiance of the film denoised with its own variance, albedo and normal aovs, none if
+// the film has no features, e.g. of a whole image integrator
+pub fn denoise_film(film: &Film, settings: &DenoiseSettings) -> Option<HdrImage> {
+    let aovs = film.to_aov_images();
+    let feature = |aov: Aov| aovs.iter().find(|(a, _)| *a == aov).map(|(_, img)| img);
+    let (albedo, normal) = (feature(Aov::Albedo)?, feature(Aov::Normal)?);
+
+    let variance: Vec<f32> = film.pixels().iter().map(|p| p.variance()).collect();
+    Some(denoise(
+        &film.to_hdr_image(),
+        &variance,
+        albedo,
+        normal,
+        settings,
+    ))
+}
+
+// sums of `values` over the squares of (2 * radius + 1)^2 pixels around every pixel, cut
+// off at the borders
+fn box_sum(values: &[f32], width: usize, height: usize, radius: usize) -> Vec<f32> {
+    let mut rows = vec![0.0; values.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let (x0, x1) = (x.saturating_sub(radius), (x + radius + 1).min(width));
+            rows[y * width + x] = values[y * width + x0..y * width + x1].iter().sum();
+        }
+    }
+    let mut sums = vec![0.0; values.len()];
+    for y in 0..height {
+        let (y0, y1) = (y.saturating_sub(radius), (y + radius + 1).min(height));
+        for x in 0..width {
+            sums[y * width + x] = (y0..y1).map(|y| rows[y * width + x]).sum();
+        }
+    }
+    sums
+}
+
+#[cfg(test)]
+mod tests {
+    use super::denoise;
+    use crate::core::{DenoiseSettings, HdrImage, Vec3f};
+    use crate::utils::random;
+
+    // a gray wall with a step of the albedo in the middle, the noise must go and the step stay
+    #[test]
+    fn test_denoise() {
+        let (width, height) = (32, 16);
+        let truth = |x: usize| if x < width / 2 { 0.2 } else { 0.6 };
+        let sigma: f32 = 0.1;
+
+        random::seed(0);
+        let mut color = HdrImage::new(width, height);
+        let mut albedo = HdrImage::new(width, height);
+        let mut normal = HdrImage::new(width, height);
+        for idx in 0..width * height {
+            let v = truth(idx % width);
+            // uniform noise of standard deviation sigma
+            let noise = (random::f32() - 0.5) * sigma * 12f32.sqrt();
+            color.set_pixel(idx, Vec3f::new(v, v, v) + Vec3f::new(noise, noise, noise));
+            albedo.set_pixel(idx, Vec3f::new(v, v, v));
+            normal.set_pixel(idx, Vec3f::new(0.0, 0.0, 1.0));
+        }
+        let variance = vec![sigma * sigma; width * height];
+
+        let denoised = denoise(
+            &color,
+            &variance,
+            &albedo,
+            &normal,
+            &DenoiseSettings::default(),
+        );
+        let rmse = |img: &HdrImage| {
+            let se: f32 = (0..width * height)
+                .map(|idx| (img.get_pixel(idx).x - truth(idx % width)).powi(2))
+                .sum();
+            (se / (width * height) as f32).sqrt()
+        };
+        assert!(rmse(&denoised) < rmse(&color) / 4.0);
+
+        // the pixels next to the step stay on their side, a blur would meet at 0.4
+        for y in 0..height {
+            let left = denoised.get_pixel(y * width + width / 2 - 1).x;
+            let right = denoised.get_pixel(y * width + width / 2).x;
+            assert!(left < 0.3 && right > 0.5);
+        }
+    }
+}
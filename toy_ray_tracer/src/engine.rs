@@ -1,216 +1,467 @@
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::{cell::RefCell, sync::Arc};
+use std::time::{Duration, Instant};
 
-use crate::core::{Light, Scene, Settings, Vec3f};
+use crate::stats::{self, Counter};
 use crate::{core::Project, utils::random};
-use log::trace;
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use thread_local::ThreadLocal;
+use anyhow::Result;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 
 use crate::{
-    core::Image,
-    core::Ray,
-    core::{vec3, Color3, Vec3List},
+    core::{spectral, vec3, Aov, Film, FilmPixel, Ray, Scene, Spectrum, SurfaceInteraction, Vec3f},
     utils::ExecutionTimer,
 };
 
-pub struct Engine {}
+// tiles are squares of TILE_SIZE pixels, cut off at the right and bottom border
+const TILE_SIZE: usize = 16;
 
-impl Engine {
-    pub fn new() -> Self {
-        return Engine {};
-    }
+// tiles rendered in parallel before their results are merged, per thread
+const TILES_PER_THREAD: usize = 4;
 
-    pub fn render(&self, project: &Project) -> Image {
-        let opts = project.settings();
-        let width = opts.width;
-        let height = opts.height;
-        let nsamples = opts.nsamples;
-        let max_depth = opts.max_depth;
+// pixel rows [y0, y1) and columns [x0, x1), row 0 is the top of the image
+struct Tile {
+    x0: usize,
+    x1: usize,
+    y0: usize,
+    y1: usize,
+}
 
-        let scene = project.scene();
-        let settings = project.settings();
+struct TileResult {
+    // the tile pixels with their new samples, row by row
+    pixels: Vec<FilmPixel>,
+    // the new samples weighted by the filter, over the tile grown by the filter radius
+    filtered: Tile,
+    weighted: Vec<(Spectrum, f32)>,
+    // light splatted onto any pixel of the image, in the order it was traced
+    splats: Vec<(usize, Spectrum)>,
+    // the aovs of the tile pixels, row by row
+    aov_values: Vec<Vec3f>,
+}
 
-        let camera = &scene.camera;
+// called with the film after every pass, e.g. to write a preview
+type PreviewFn = Box<dyn Fn(&Film) -> Result<()>>;
 
-        let tasks_finished = Arc::new(AtomicUsize::new(0));
-        let pixels_local = Arc::new(ThreadLocal::new());
+pub struct Engine {
+    // the film is saved to the path every interval and once the render is done
+    checkpoint: Option<(PathBuf, Duration)>,
+    preview: Option<PreviewFn>,
+}
 
-        let unit_height = height / 100;
+impl Engine {
+    pub fn new() -> Self {
+        return Engine {
+            checkpoint: None,
+            preview: None,
+        };
+    }
 
-        (0..height).into_par_iter().for_each(|j: usize| {
-            let _timer = ExecutionTimer::new(|start_time| {
-                let val = tasks_finished.fetch_add(1, Ordering::Relaxed) + 1;
-                if val % unit_height == 0 || val == height {
-                    log::debug!(
-                        "render elapsed {} ms, height_idx={}, progress={}/{} ({:.2}%)",
-                        start_time.elapsed().as_millis(),
-                        j,
-                        val,
-                        height,
-                        (val as f32 / height as f32) * 100.0
-                    )
-                }
-            });
+    pub fn with_checkpoint(mut self, path: PathBuf, interval: Duration) -> Self {
+        self.checkpoint = Some((path, interval));
+        self
+    }
 
-            let plc = pixels_local.get_or(|| RefCell::new(Vec3List::new_with_size(width * height)));
+    pub fn with_preview<F: Fn(&Film) -> Result<()> + 'static>(mut self, preview: F) -> Self {
+        self.preview = Some(Box::new(preview));
+        self
+    }
 
-            let mut pixels = plc.borrow_mut();
+    // renders until every pixel has `nsamples` samples, the adaptive sampling budget or the
+    // time budget is spent, continuing the samples of `film` if given, e.g. loaded from a
+    // checkpoint
+    pub fn render(&self, project: &Project, film: Option<Film>) -> Result<Film> {
+        let settings = project.settings();
+        let width = settings.width;
+        let height = settings.height;
+        let nsamples = settings.nsamples.max(0) as u32;
+
+        if let Some(pixels) = project.integrator().render(project.scene(), settings) {
+            if !settings.film_aovs().is_empty() {
+                log::warn!(
+                    "the integrator renders the whole image, settings.aovs and denoise are ignored"
+                );
+            }
+            if settings.progressive.is_some() {
+                log::warn!(
+                    "the integrator renders all passes at once, settings.progressive is ignored"
+                );
+            }
+            if settings.adaptive.is_some() {
+                log::warn!(
+                    "the integrator renders nsamples per pixel, settings.adaptive is ignored"
+                );
+            }
+            let mut film = Film::new(width, height, settings.seed);
+            for (idx, color) in pixels.iter().enumerate() {
+                count_broken(color);
+                let mut pixel = FilmPixel::default();
+                pixel.add_sample(color);
+                film.set_pixel(idx, pixel);
+            }
+            return Ok(film);
+        }
 
-            // let light_pdf = HittablePDF::new(o, hittable)
+        let mut film = match film {
+            Some(film) => {
+                film.check(settings)?;
+                film
+            }
+            None => Film::new(width, height, settings.seed).with_aovs(&settings.film_aovs()),
+        };
+
+        let mut tiles = Vec::new();
+        for y0 in (0..height).step_by(TILE_SIZE) {
+            for x0 in (0..width).step_by(TILE_SIZE) {
+                tiles.push(Tile {
+                    x0,
+                    x1: (x0 + TILE_SIZE).min(width),
+                    y0,
+                    y1: (y0 + TILE_SIZE).min(height),
+                });
+            }
+        }
 
-            let rng = random::new_rng();
-            for i in 0..width {
-                for _s in 0..nsamples {
-                    let u = (i as f32 + rng.f32()) / width as f32;
-                    let v = (j as f32 + rng.f32()) / height as f32;
-                    let r = camera.get_ray(u, v);
-                    let c = self.trace_ray_loop(&r, &scene, settings, max_depth);
-                    pixels[(height - j - 1) * width + i] += c;
+        let start = Instant::now();
+        let progressive = settings.progressive.as_ref();
+        let budget = progressive
+            .and_then(|p| p.time_budget)
+            .map(|budget| Duration::from_secs_f32(budget.max(0.0)));
+        // whether a pass as long as the last one still ends within the budget
+        let has_time = |pass: Duration| {
+            let time_left = budget.is_none_or(|budget| start.elapsed() + pass <= budget);
+            if !time_left {
+                log::info!(
+                    "time budget spent, stopping after {:.2} s",
+                    start.elapsed().as_secs_f32()
+                );
+            }
+            time_left
+        };
+
+        let mut last_saved = Instant::now();
+        match &settings.adaptive {
+            None => {
+                let step = progressive.map_or(nsamples, |p| p.samples_per_pass.max(1));
+                while film.pixels().iter().any(|p| p.samples < nsamples) {
+                    let pass_start = Instant::now();
+                    let targets: Vec<u32> = film
+                        .pixels()
+                        .iter()
+                        .map(|p| p.samples.saturating_add(step).min(nsamples))
+                        .collect();
+                    self.render_pass(project, &tiles, &targets, &mut film, &mut last_saved)?;
+                    self.finish_pass(&film, start)?;
+                    if !has_time(pass_start.elapsed()) {
+                        break;
+                    }
                 }
             }
-        });
-
-        let mut img = Image::new(width, height);
-        {
-            let _timer = ExecutionTimer::new(|start_time| {
-                trace!("to_image elapsed {:} ms", start_time.elapsed().as_millis(),)
-            });
+            Some(adaptive) => {
+                let min_samples = adaptive.min_samples.max(2);
+                let max_samples = adaptive
+                    .max_samples
+                    .unwrap_or_else(|| nsamples.saturating_mul(4));
+                let budget = nsamples as u64 * (width * height) as u64;
+                let is_noisy =
+                    |p: &FilmPixel| p.samples < max_samples && p.error() > adaptive.threshold;
+
+                let pass_start = Instant::now();
+                let targets = vec![min_samples.min(max_samples); width * height];
+                self.render_pass(project, &tiles, &targets, &mut film, &mut last_saved)?;
+                self.finish_pass(&film, start)?;
+                let mut time_left = has_time(pass_start.elapsed());
+
+                // the noisy pixels share what's left of the budget, `min_samples` per round
+                while time_left {
+                    let remaining = budget.saturating_sub(film.total_samples());
+                    let active = film.pixels().iter().filter(|p| is_noisy(p)).count() as u64;
+                    if active == 0 || remaining < active {
+                        break;
+                    }
+                    let step = (remaining / active).min(min_samples as u64) as u32;
+                    log::debug!(
+                        "adaptive sampling round, {} pixels get {} more samples",
+                        active,
+                        step
+                    );
+
+                    let targets: Vec<u32> = film
+                        .pixels()
+                        .iter()
+                        .map(|p| {
+                            if is_noisy(p) {
+                                (p.samples + step).min(max_samples)
+                            } else {
+                                p.samples
+                            }
+                        })
+                        .collect();
+                    let pass_start = Instant::now();
+                    self.render_pass(project, &tiles, &targets, &mut film, &mut last_saved)?;
+                    self.finish_pass(&film, start)?;
+                    time_left = has_time(pass_start.elapsed());
+                }
+            }
+        }
 
-            let pixels_local = Arc::try_unwrap(pixels_local).unwrap();
-            let pixels = pixels_local
-                .into_iter()
-                .fold(Vec3List::new_with_size(width * height), |v1, v2| {
-                    v1.add(&v2.borrow())
-                });
+        if let Some((path, _)) = &self.checkpoint {
+            film.save(path)?;
+            log::info!("checkpoint saved to {}", path.display());
+        }
 
-            for (i, color) in pixels.iter().enumerate() {
-                let color = vec3::sqrt(color / nsamples as f32);
+        Ok(film)
+    }
 
-                img.set_pixel(i, color);
-            }
+    fn finish_pass(&self, film: &Film, start: Instant) -> Result<()> {
+        log::debug!(
+            "pass finished, {:.1} samples per pixel after {:.2} s",
+            film.total_samples() as f64 / film.pixels().len() as f64,
+            start.elapsed().as_secs_f32()
+        );
+        if let Some(preview) = &self.preview {
+            preview(film)?;
         }
-
-        return img;
+        Ok(())
     }
 
-    #[allow(dead_code)]
-    fn trace_ray_loop<'a>(
+    // takes samples until every pixel has its target number of them
+    fn render_pass(
         &self,
-        ray: &Ray,
-        scene: &Scene,
-        settings: &'a Settings,
-        max_depth: i32,
-    ) -> Color3 {
-        let mut ray = ray.clone();
-        let mut color = Color3::zeros();
-        let mut beta = Vec3f::new(1.0, 1.0, 1.0);
-
-        let world = &scene.world;
-        let lights = &scene.lights;
-
-        for bounce in 0..max_depth {
-            if let Some(si) = world.intersect(&ray, 0.001, f32::MAX) {
-                let material = si.material.unwrap();
-                // TODO: support single sided light
-                let emission = if si.wo.dot(&si.normal) > 0.0 {
-                    material.emission(&si)
-                } else {
-                    // Color3::zeros()
-                    material.emission(&si)
-                };
-
-                color = color + vec3::elementwise_mult(&beta, &emission);
-
-                if let Some(bsdf) = material.compute_bsdf(&si) {
-                    let wo = &si.wo;
-
-                    // delta 分布 brdf，如 镜面反射
-                    if bsdf.is_delta() {
-                        let wi = bsdf.sample_wi(wo);
-                        let pdf = bsdf.sample_pdf(&wi, wo);
-                        let bsdf_value = bsdf.f_cos(&wi, wo);
-
-                        ray = Ray::new(si.point, wi, ray.time());
-                        beta = vec3::elementwise_mult(&beta, &bsdf_value) / pdf;
-                    } else {
-                        // 非 delta 分布 brdf，如 漫反射
-                        let lights = &scene.lights;
-
-                        // bsdf and light sampling weight
-                        let mis_weight = settings.mis_weight;
-
-                        let wi = if random::f32() < mis_weight {
-                            bsdf.sample_wi(wo)
+        project: &Project,
+        tiles: &[Tile],
+        targets: &[u32],
+        film: &mut Film,
+        last_saved: &mut Instant,
+    ) -> Result<()> {
+        let scene = project.scene();
+        let settings = project.settings();
+        let camera = &scene.camera;
+        let integrator = project.integrator();
+        let width = settings.width;
+        let height = settings.height;
+        // strata of the stratified sampler
+        let nsamples = settings.nsamples.max(1) as u32;
+        let spectral = settings.spectral && integrator.supports_spectral();
+        let aovs = film.aovs().to_vec();
+        let split = aovs.iter().any(|aov| aov.is_split());
+        let needs_hit = aovs.iter().any(|aov| !aov.is_split());
+
+        // samples are splatted onto the pixels whose center is within the filter radius
+        let filter = project.filter();
+        let radius = filter.radius();
+        let border = (radius - 0.5).ceil().max(0.0) as usize;
+        // pixels in [start, end) within the radius of a sample at offset `d` in [0, 1) of
+        // `pixel`, for the box filter of radius 0.5 that's only `pixel` itself
+        let pixel_range = |pixel: usize, d: f32, start: usize, end: usize| {
+            let min = (pixel as f32 + (d - radius - 0.5).floor() + 1.0).max(start as f32);
+            let max = (pixel as f32 + (d + radius - 0.5).floor() + 1.0).min(end as f32);
+            min as usize..max.max(min) as usize
+        };
+
+        let ntiles = tiles.len();
+        let tasks_finished = AtomicUsize::new(0);
+
+        // every sample draws from its own stream, so the result doesn't depend on the
+        // tile a thread picks up next. The numbers come from the sampler of the settings
+        let render_tile = |tile: &Tile, film: &Film| {
+            let _timer = ExecutionTimer::new(|start_time| {
+                let val = tasks_finished.fetch_add(1, Ordering::Relaxed) + 1;
+                // once per percent
+                if val * 100 / ntiles != (val - 1) * 100 / ntiles {
+                    log::debug!(
+                        "render elapsed {} ms, tile=({}, {}), progress={}/{} ({:.2}%)",
+                        start_time.elapsed().as_millis(),
+                        tile.x0,
+                        tile.y0,
+                        val,
+                        ntiles,
+                        (val as f32 / ntiles as f32) * 100.0
+                    )
+                }
+            });
+
+            random::set_sampler(Some(settings.sampler.build(settings.seed, nsamples)));
+
+            let filtered = Tile {
+                x0: tile.x0.saturating_sub(border),
+                x1: (tile.x1 + border).min(width),
+                y0: tile.y0.saturating_sub(border),
+                y1: (tile.y1 + border).min(height),
+            };
+            let filtered_width = filtered.x1 - filtered.x0;
+            let mut result = TileResult {
+                pixels: Vec::with_capacity((tile.x1 - tile.x0) * (tile.y1 - tile.y0)),
+                weighted: vec![
+                    (Spectrum::zeros(), 0.0);
+                    filtered_width * (filtered.y1 - filtered.y0)
+                ],
+                filtered,
+                splats: Vec::new(),
+                aov_values: Vec::with_capacity(
+                    (tile.x1 - tile.x0) * (tile.y1 - tile.y0) * aovs.len(),
+                ),
+            };
+            let mut splats = Vec::new();
+            for row in tile.y0..tile.y1 {
+                let j = height - row - 1;
+                for i in tile.x0..tile.x1 {
+                    let idx = row * width + i;
+                    // sample `s` draws the same numbers whether it's rendered now or after
+                    // resuming, the sum continues in the same order
+                    let mut pixel = film.pixel(idx);
+                    let mut aov_values = film.aov_values(idx).to_vec();
+                    for s in pixel.samples..targets[idx] {
+                        random::start_sample(idx as u64, s as u64);
+                        let (dx, dy) = random::f32_2d();
+                        if spectral {
+                            spectral::start_sample(random::f32());
+                        }
+                        let u = (i as f32 + dx) / width as f32;
+                        let v = (j as f32 + dy) / height as f32;
+                        let r = camera.get_ray(u, v);
+                        let (l, direct) = if split {
+                            integrator.li_direct(&r, scene, settings, &mut splats)
                         } else {
-                            lights.sample_wi(&si.point)
+                            (integrator.li_splats(&r, scene, settings, &mut splats), None)
                         };
-
-                        // no light ray
-                        if vec3::is_near_zero(&wi) {
-                            break;
+                        let l = spectral::to_rgb(&l);
+                        pixel.add_sample(&l);
+
+                        if !aovs.is_empty() {
+                            let hit = needs_hit.then(|| first_hit(&r, scene)).flatten();
+                            let direct = direct.map(|direct| spectral::to_rgb(&direct));
+                            for (value, aov) in aov_values.iter_mut().zip(&aovs) {
+                                let sample = aov_sample(*aov, &r, hit.as_ref(), &l, direct);
+                                if aov.is_averaged() {
+                                    *value += sample;
+                                } else if s == 0 {
+                                    *value = sample;
+                                }
+                            }
                         }
 
-                        let pdf = mis_weight * bsdf.sample_pdf(&wi, wo)
-                            + (1.0 - mis_weight) * lights.sample_pdf(&si.point, &wi);
-
-                        let bsdf_value = bsdf.f_cos(&wi, wo);
-
-                        // black, stop trace
-                        if vec3::is_black(&bsdf_value) || pdf < f32::EPSILON {
-                            break;
+                        // a broken sample would spread over all pixels around, it only spoils
+                        // its own one. Rows count from the top, `j` from the bottom
+                        let region = &result.filtered;
+                        let rows = pixel_range(j, dy, height - region.y1, height - region.y0);
+                        let columns = pixel_range(i, dx, region.x0, region.x1);
+                        count_broken(&l);
+                        if l.iter().all(|c| c.is_finite()) {
+                            for pj in rows {
+                                for pi in columns.clone() {
+                                    let w = filter.evaluate(
+                                        (pi as f32 - i as f32) + 0.5 - dx,
+                                        (pj as f32 - j as f32) + 0.5 - dy,
+                                    );
+                                    if w != 0.0 {
+                                        let k = (height - pj - 1 - region.y0) * filtered_width + pi
+                                            - region.x0;
+                                        result.weighted[k].0 += l * w;
+                                        result.weighted[k].1 += w;
+                                    }
+                                }
+                            }
                         }
 
-                        ray = Ray::new(si.point, wi, ray.time());
-                        let old_beta = beta;
-                        beta = vec3::elementwise_mult(&beta, &bsdf_value) / pdf;
-
-                        if log::max_level() >= log::Level::Trace {
-                            log::trace!("wi: {:?}, wo: {:?}, normal: {:?}", &wi, &wo, &si.normal);
-                            log::trace!(
-                                "wi.norm(): {:?}, wo.norm(): {:?}, normal.norm(): {:?}",
-                                &wi.norm(),
-                                &wo.norm(),
-                                &si.normal.norm()
-                            );
-
-                            log::trace!(
-                                "pdf: {:?}, bsdf_value: {:?}, beta: {:?}, old_beta: {:?}",
-                                pdf,
-                                bsdf_value,
-                                beta,
-                                old_beta,
-                            );
+                        for splat in splats.drain(..) {
+                            let si = ((splat.film.x * width as f32) as usize).min(width - 1);
+                            let sj = ((splat.film.y * height as f32) as usize).min(height - 1);
+                            let l = spectral::to_rgb(&splat.l);
+                            result.splats.push(((height - sj - 1) * width + si, l));
                         }
                     }
-                } else {
-                    // no bsdf for material, stop trace
-                    break;
+                    result.pixels.push(pixel);
+                    result.aov_values.extend(aov_values);
+                }
+            }
+            random::set_sampler(None);
+            spectral::clear();
+            result
+        };
+
+        // tiles are merged in a fixed order, floating point sums come out the same with
+        // any number of threads
+        let wave = rayon::current_num_threads() * TILES_PER_THREAD;
+        for tiles in tiles.chunks(wave) {
+            let results: Vec<TileResult> = tiles
+                .par_iter()
+                .map(|tile| render_tile(tile, film))
+                .collect();
+            for (tile, result) in tiles.iter().zip(results) {
+                let tile_width = tile.x1 - tile.x0;
+                for (k, pixel) in result.pixels.into_iter().enumerate() {
+                    let idx = (tile.y0 + k / tile_width) * width + tile.x0 + k % tile_width;
+                    film.set_pixel(idx, pixel);
+                    if !aovs.is_empty() {
+                        let n = aovs.len();
+                        film.set_aov_values(idx, &result.aov_values[k * n..(k + 1) * n]);
+                    }
+                }
+                let filtered = &result.filtered;
+                let filtered_width = filtered.x1 - filtered.x0;
+                for (k, (l, w)) in result.weighted.into_iter().enumerate() {
+                    let idx = (filtered.y0 + k / filtered_width) * width
+                        + filtered.x0
+                        + k % filtered_width;
+                    film.add_filtered(idx, &l, w);
+                }
+                for (idx, l) in result.splats {
+                    film.add_splat(idx, &l);
                 }
-            } else {
-                // no hit, return environment lights
-                color = color + vec3::elementwise_mult(&beta, &lights.background_l(&ray));
-                break;
             }
 
-            if beta == Vec3f::zeros() {
-                break;
+            if let Some((path, interval)) = &self.checkpoint {
+                if last_saved.elapsed() >= *interval {
+                    film.save(path)?;
+                    log::debug!("checkpoint saved to {}", path.display());
+                    *last_saved = Instant::now();
+                }
             }
+        }
 
-            if bounce > 3 {
-                // prop 0.99 or beta
-                let rr_prob = f32::min(0.99, beta.max());
+        Ok(())
+    }
+}
 
-                // rr_prop stop trace
-                if random::f32() >= rr_prob {
-                    break;
-                }
+// counts the sample for the stats if it's NaN or infinite
+fn count_broken(l: &Vec3f) {
+    if l.iter().any(|c| c.is_nan()) {
+        stats::add(Counter::NanSamples, 1);
+    } else if l.iter().any(|c| c.is_infinite()) {
+        stats::add(Counter::InfSamples, 1);
+    }
+}
 
-                // (1 - rr_prob) continue to trace
-                beta *= 1.0 / rr_prob;
-            }
+// first surface with a material along the camera ray, media boundaries are looked through.
+// Not a ray of the integrator, the stats don't count it as one
+fn first_hit<'a>(ray: &Ray, scene: &'a Scene) -> Option<SurfaceInteraction<'a>> {
+    let mut ray = ray.clone();
+    loop {
+        let si = scene.world.intersect(&ray, 0.001, f32::MAX)?;
+        if si.material.is_some() {
+            return Some(si);
         }
+        ray = Ray::new(si.point, ray.direction(), ray.time());
+    }
+}
 
-        return color;
+// `aov` of one sample of radiance `l` along `ray`, `direct` is its direct part if the
+// integrator splits it off
+fn aov_sample(
+    aov: Aov,
+    ray: &Ray,
+    hit: Option<&SurfaceInteraction>,
+    l: &Spectrum,
+    direct: Option<Spectrum>,
+) -> Vec3f {
+    match (aov, hit) {
+        (Aov::Direct, _) => direct.unwrap_or_else(Vec3f::zeros),
+        (Aov::Indirect, _) => direct.map_or_else(Vec3f::zeros, |direct| l - direct),
+        (_, None) => Vec3f::zeros(),
+        (Aov::Depth, Some(si)) => vec3::scalar((si.point - ray.origin()).norm()),
+        (Aov::Normal, Some(si)) => si.normal,
+        (Aov::Albedo, Some(si)) => si.material.map_or(Vec3f::zeros(), |m| m.albedo(si)),
+        (Aov::Uv, Some(si)) => Vec3f::new(si.uv.x, si.uv.y, 0.0),
+        (Aov::PrimitiveId, Some(si)) => vec3::scalar(si.ids.primitive as f32),
+        (Aov::MaterialId, Some(si)) => vec3::scalar(si.ids.material as f32),
     }
 }
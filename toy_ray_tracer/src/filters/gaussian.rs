@@ -0,0 +1,36 @@
+use crate::core::Filter;
+
+// gaussian of standard deviation `sigma`, shifted down to reach zero at the radius
+pub struct GaussianFilter {
+    radius: f32,
+    sigma: f32,
+    edge: f32,
+}
+
+impl GaussianFilter {
+    pub fn new(radius: f32, sigma: f32) -> Self {
+        let mut filter = Self {
+            radius,
+            sigma,
+            edge: 0.0,
+        };
+        filter.edge = filter.gaussian(radius);
+        filter
+    }
+
+    fn gaussian(&self, x: f32) -> f32 {
+        (-x * x / (2.0 * self.sigma * self.sigma)).exp()
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        let gx = (self.gaussian(x) - self.edge).max(0.0);
+        let gy = (self.gaussian(y) - self.edge).max(0.0);
+        gx * gy
+    }
+}
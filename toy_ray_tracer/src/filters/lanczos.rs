@@ -0,0 +1,40 @@
+use std::f32::consts::PI;
+
+use crate::core::Filter;
+
+// sinc windowed by a wider sinc with `tau` lobes, cut off at the radius
+pub struct LanczosFilter {
+    radius: f32,
+    tau: f32,
+}
+
+impl LanczosFilter {
+    pub fn new(radius: f32, tau: f32) -> Self {
+        Self { radius, tau }
+    }
+
+    fn windowed_sinc(&self, x: f32) -> f32 {
+        let x = x.abs();
+        if x > self.radius {
+            return 0.0;
+        }
+        sinc(x) * sinc(x / self.tau)
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x < 1e-5 {
+        return 1.0;
+    }
+    (PI * x).sin() / (PI * x)
+}
+
+impl Filter for LanczosFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.windowed_sinc(x) * self.windowed_sinc(y)
+    }
+}
@@ -0,0 +1,11 @@
+mod box_filter;
+mod gaussian;
+mod lanczos;
+mod mitchell;
+mod tent;
+
+pub use box_filter::BoxFilter;
+pub use gaussian::GaussianFilter;
+pub use lanczos::LanczosFilter;
+pub use mitchell::MitchellFilter;
+pub use tent::TentFilter;
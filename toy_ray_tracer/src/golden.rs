@@ -0,0 +1,210 @@
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::compare::{self, ImageMetrics};
+use crate::core::HdrImage;
+use crate::engine::Engine;
+use crate::scene_builder::{load_project_config, AssetsManager, Builder};
+
+// Golden image regression tests: the projects listed in `<golden_dir>/cases.json` are
+// rendered small with a fixed seed and compared against the references `<name>.exr` next to
+// it. Changes of the bxdfs, shapes or loaders show up as images drifting away from them
+#[derive(Deserialize, Debug, Clone)]
+pub struct GoldenCase {
+    pub name: String,
+    // project file, relative to the assets dir
+    pub project: String,
+    #[serde(default = "GoldenCase::default_size")]
+    pub width: usize,
+    #[serde(default = "GoldenCase::default_size")]
+    pub height: usize,
+    #[serde(default = "GoldenCase::default_nsamples")]
+    pub nsamples: i32,
+    // largest mean FLIP error and relMSE that still pass. The renders are deterministic, the
+    // defaults allow for floating point differences between platforms but not e.g. a 3%
+    // darker albedo. Other seeds are much further away, so changes of the sampling also fail
+    // and need the references to be written again
+    #[serde(default = "GoldenCase::default_max_flip")]
+    pub max_flip: f32,
+    #[serde(default = "GoldenCase::default_max_rel_mse")]
+    pub max_rel_mse: f32,
+}
+
+impl GoldenCase {
+    pub fn default_size() -> usize {
+        64
+    }
+
+    pub fn default_nsamples() -> i32 {
+        8
+    }
+
+    pub fn default_max_flip() -> f32 {
+        0.01
+    }
+
+    pub fn default_max_rel_mse() -> f32 {
+        1e-3
+    }
+}
+
+pub struct GoldenResult {
+    pub case: GoldenCase,
+    // none when the reference was written
+    pub metrics: Option<ImageMetrics>,
+}
+
+impl GoldenResult {
+    pub fn passed(&self) -> bool {
+        match &self.metrics {
+            Some(m) => m.flip <= self.case.max_flip && m.rel_mse <= self.case.max_rel_mse,
+            None => true,
+        }
+    }
+}
+
+pub struct GoldenHarness {
+    assets_dir: PathBuf,
+    golden_dir: PathBuf,
+    // the renders and FLIP error images of the cases are written here, if set
+    report_dir: Option<PathBuf>,
+}
+
+impl GoldenHarness {
+    pub fn new<P1: AsRef<Path>, P2: AsRef<Path>>(assets_dir: P1, golden_dir: P2) -> Self {
+        Self {
+            assets_dir: assets_dir.as_ref().to_path_buf(),
+            golden_dir: golden_dir.as_ref().to_path_buf(),
+            report_dir: None,
+        }
+    }
+
+    pub fn with_report_dir<P: AsRef<Path>>(mut self, report_dir: P) -> Self {
+        self.report_dir = Some(report_dir.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn load_cases(&self) -> Result<Vec<GoldenCase>> {
+        let path = self.golden_dir.join("cases.json");
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to load {}", path.display()))?;
+        let cases = serde_json::from_str(&text)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        Ok(cases)
+    }
+
+    // the project of `case` at its size and number of samples, with seed 0
+    pub fn render(&self, case: &GoldenCase) -> Result<HdrImage> {
+        let project_file = self.assets_dir.join(&case.project);
+        let mut project_config = load_project_config(&project_file)?;
+        {
+            let settings = &mut project_config.settings;
+            settings.width = case.width;
+            settings.height = case.height;
+            settings.nsamples = case.nsamples;
+            settings.seed = 0;
+        }
+
+        let project_dir = project_file.parent().context("project has no dir")?;
+        let assets_manager = Rc::new(AssetsManager::new(&self.assets_dir, project_dir));
+        let project = Builder::new(assets_manager).build_project(&project_config)?;
+
+        let film = Engine::new().render(&project, None)?;
+        let mut image = film.to_hdr_image();
+        image.transform(&project.settings().working_space.output_matrix());
+        Ok(image)
+    }
+
+    // renders the cases whose name contains `filter` and compares them against their
+    // references, or writes the references with `update`
+    pub fn run(&self, filter: Option<&str>, update: bool) -> Result<Vec<GoldenResult>> {
+        if let Some(report_dir) = &self.report_dir {
+            std::fs::create_dir_all(report_dir)?;
+        }
+
+        let mut results = Vec::new();
+        for case in self.load_cases()? {
+            if filter.is_some_and(|filter| !case.name.contains(filter)) {
+                continue;
+            }
+            log::info!("rendering golden case {}", case.name);
+            let image = self
+                .render(&case)
+                .with_context(|| format!("failed to render golden case {}", case.name))?;
+
+            let reference_path = self.golden_dir.join(format!("{}.exr", case.name));
+            if update {
+                image.save_exr(&reference_path)?;
+                results.push(GoldenResult {
+                    case,
+                    metrics: None,
+                });
+                continue;
+            }
+
+            let reference = compare::load_linear(&reference_path).with_context(|| {
+                format!("no reference of {}, create it with --update", case.name)
+            })?;
+            let (metrics, errors) = compare::compare(&image, &reference)?;
+            if let Some(report_dir) = &self.report_dir {
+                image.save_exr(report_dir.join(format!("{}.exr", case.name)))?;
+                compare::error_image(&errors, image.width(), image.height())
+                    .save_to_png(report_dir.join(format!("{}_diff.png", case.name)))?;
+            }
+            results.push(GoldenResult {
+                case,
+                metrics: Some(metrics),
+            });
+        }
+        Ok(results)
+    }
+}
+
+// one line per case, e.g. for the console
+pub fn format_result(result: &GoldenResult) -> String {
+    let case = &result.case;
+    match &result.metrics {
+        Some(m) => format!(
+            "{:<24} {}  FLIP {:.4} (max {:.4})  relMSE {:.2e} (max {:.2e})  PSNR {:.2} dB",
+            case.name,
+            if result.passed() { "ok  " } else { "FAIL" },
+            m.flip,
+            case.max_flip,
+            m.rel_mse,
+            case.max_rel_mse,
+            m.psnr
+        ),
+        None => format!("{:<24} reference written", case.name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_result, GoldenHarness};
+
+    // renders every bundled golden case, a few seconds per case in debug builds
+    #[test]
+    fn test_golden_images() {
+        // the js loader resolves imports relative to the working dir, the package dir in tests
+        let report_dir = std::env::temp_dir().join("toy_ray_tracer_golden");
+        let harness =
+            GoldenHarness::new("../assets", "../assets/golden").with_report_dir(&report_dir);
+
+        let results = harness.run(None, false).unwrap();
+        assert!(!results.is_empty());
+        let failed: Vec<String> = results
+            .iter()
+            .filter(|r| !r.passed())
+            .map(format_result)
+            .collect();
+        assert!(
+            failed.is_empty(),
+            "golden images differ, the renders and FLIP error images are in {}:\n{}",
+            report_dir.display(),
+            failed.join("\n")
+        );
+    }
+}
@@ -0,0 +1,39 @@
+use crate::core::{vec3, Integrator, Ray, Scene, Settings, Spectrum};
+use crate::math::ONB;
+
+// Ambient occlusion: fraction of the cosine-weighted hemisphere above the
+// first hit that is not blocked within `max_distance`.
+pub struct AmbientOcclusionIntegrator {
+    nsamples: usize,
+    max_distance: f32,
+}
+
+impl AmbientOcclusionIntegrator {
+    pub fn new(nsamples: usize, max_distance: f32) -> Self {
+        Self {
+            nsamples: nsamples.max(1),
+            max_distance,
+        }
+    }
+}
+
+impl Integrator for AmbientOcclusionIntegrator {
+    fn li(&self, ray: &Ray, scene: &Scene, _settings: &Settings) -> Spectrum {
+        let si = match scene.intersect(ray, 0.001, f32::MAX) {
+            Some(si) => si,
+            None => return Spectrum::zeros(),
+        };
+
+        let uvw = ONB::build_form_w(&si.normal);
+        let mut unoccluded = 0;
+        for _ in 0..self.nsamples {
+            let wi = uvw.local(vec3::random_cosine_direction());
+            let ao_ray = Ray::new(si.point, wi, ray.time());
+            if !scene.intersect_p(&ao_ray, 0.001, self.max_distance) {
+                unoccluded += 1;
+            }
+        }
+
+        vec3::scalar(unoccluded as f32 / self.nsamples as f32)
+    }
+}
@@ -0,0 +1,691 @@
+use std::f32::consts::PI;
+use std::sync::Once;
+
+use crate::core::{
+    vec3, Bsdf, Integrator, Light, LightType, MisHeuristic, Point2f, Point3f, Ray, Scene, Settings,
+    Spectrum, Splat, SurfaceInteraction, Vec3f, AABB,
+};
+use crate::utils::random;
+
+use super::transmittance;
+
+// Bidirectional path tracing: a camera subpath and a light subpath are traced
+// independently, then their vertices are connected with every strategy (s, t),
+// s light vertices and t camera vertices, and the strategies are combined by MIS.
+// Light vertices connected directly to the camera (t = 1) are splatted.
+// Media are not sampled, paths pass through their boundaries unchanged.
+pub struct BdptIntegrator {
+    // infinite lights emit from a disk covering the world
+    world_bound: AABB,
+    world_radius: f32,
+}
+
+impl BdptIntegrator {
+    pub fn new(world_bound: AABB) -> Self {
+        let (_, world_radius) = world_bound.bounding_sphere();
+        Self {
+            world_bound,
+            world_radius,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum VertexKind {
+    Camera,
+    Light,
+    Surface,
+}
+
+struct Vertex<'a> {
+    kind: VertexKind,
+    point: Point3f,
+    // geometric normal, zero if the vertex is not on a surface (pinhole camera)
+    normal: Vec3f,
+    // throughput from the start of the subpath to this vertex
+    beta: Spectrum,
+    si: Option<SurfaceInteraction<'a>>,
+    bsdf: Option<Bsdf>,
+    // light at infinity, only the direction towards it matters
+    infinite: bool,
+    // scattered by a delta bsdf
+    delta: bool,
+    // area pdfs of sampling this vertex from the previous / next vertex of the subpath,
+    // solid angle pdfs for vertices at infinity
+    pdf_fwd: f32,
+    pdf_rev: f32,
+}
+
+impl<'a> Vertex<'a> {
+    fn camera(point: Point3f, normal: Vec3f, beta: Spectrum) -> Self {
+        Self {
+            kind: VertexKind::Camera,
+            point,
+            normal,
+            beta,
+            si: None,
+            bsdf: None,
+            infinite: false,
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }
+    }
+
+    fn light(point: Point3f, normal: Vec3f, beta: Spectrum, pdf_fwd: f32, infinite: bool) -> Self {
+        Self {
+            kind: VertexKind::Light,
+            point,
+            normal,
+            beta,
+            si: None,
+            bsdf: None,
+            infinite,
+            delta: false,
+            pdf_fwd,
+            pdf_rev: 0.0,
+        }
+    }
+
+    fn surface(si: SurfaceInteraction<'a>, bsdf: Option<Bsdf>, beta: Spectrum) -> Self {
+        Self {
+            kind: VertexKind::Surface,
+            point: si.point,
+            normal: si.normal,
+            beta,
+            si: Some(si),
+            bsdf,
+            infinite: false,
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }
+    }
+
+    fn is_on_surface(&self) -> bool {
+        self.normal != Vec3f::zeros()
+    }
+
+    fn is_connectible(&self) -> bool {
+        match self.kind {
+            VertexKind::Camera | VertexKind::Light => true,
+            VertexKind::Surface => matches!(&self.bsdf, Some(bsdf) if !bsdf.is_delta()),
+        }
+    }
+
+    fn emission(&self) -> Spectrum {
+        match &self.si {
+            Some(si) => si.material.map_or(Spectrum::zeros(), |m| m.emission(si)),
+            None => Spectrum::zeros(),
+        }
+    }
+
+    fn is_light(&self) -> bool {
+        self.kind == VertexKind::Light || !vec3::is_black(&self.emission())
+    }
+
+    // radiance emitted towards `v`
+    fn le(&self, scene: &Scene, v: &Vertex) -> Spectrum {
+        if self.infinite {
+            let w = (self.point - v.point).normalize();
+            return scene.lights.background_l(&Ray::new(v.point, w, 0.0));
+        }
+        self.emission()
+    }
+
+    // bsdf for light scattered towards `next`
+    fn f(&self, next: &Vertex) -> Spectrum {
+        match (&self.si, &self.bsdf) {
+            (Some(si), Some(bsdf)) => {
+                let wi = (next.point - self.point).normalize();
+                bsdf.f(&wi, &si.wo)
+            }
+            _ => Spectrum::zeros(),
+        }
+    }
+
+    // solid angle pdf at this vertex to area pdf at `next`
+    fn convert_density(&self, pdf: f32, next: &Vertex) -> f32 {
+        if next.infinite {
+            return pdf;
+        }
+
+        let w = next.point - self.point;
+        let dist2 = w.norm_squared();
+        if dist2 == 0.0 {
+            return 0.0;
+        }
+
+        let mut pdf = pdf / dist2;
+        if next.is_on_surface() {
+            pdf *= next.normal.dot(&(w / dist2.sqrt())).abs();
+        }
+        pdf
+    }
+
+    // area pdf of sampling `next` from this vertex, reached from `prev`
+    fn pdf(&self, scene: &Scene, world_radius: f32, prev: Option<&Vertex>, next: &Vertex) -> f32 {
+        if self.kind == VertexKind::Light {
+            return self.pdf_light(scene, world_radius, next);
+        }
+
+        let wn = next.point - self.point;
+        if wn.norm_squared() == 0.0 {
+            return 0.0;
+        }
+        let wn = wn.normalize();
+
+        let pdf = match (self.kind, &self.bsdf, prev) {
+            (VertexKind::Camera, _, _) => scene.camera.pdf_we(&Ray::new(self.point, wn, 0.0)).1,
+            (VertexKind::Surface, Some(bsdf), Some(prev)) => {
+                let wp = prev.point - self.point;
+                if wp.norm_squared() == 0.0 {
+                    return 0.0;
+                }
+                bsdf.sample_pdf(&wn, &wp.normalize())
+            }
+            _ => 0.0,
+        };
+
+        self.convert_density(pdf, next)
+    }
+
+    // area pdf of a light at this vertex emitting towards `v`
+    fn pdf_light(&self, scene: &Scene, world_radius: f32, v: &Vertex) -> f32 {
+        let w = v.point - self.point;
+        let dist2 = w.norm_squared();
+        let w = w / dist2.sqrt();
+
+        let mut pdf = if self.infinite {
+            1.0 / (PI * world_radius * world_radius)
+        } else {
+            scene.lights.pdf_le(&v.point, &self.point).1 / dist2
+        };
+
+        if v.is_on_surface() {
+            pdf *= v.normal.dot(&w).abs();
+        }
+        pdf
+    }
+
+    // pdf of picking this vertex as the start of a light subpath, seen from `v`
+    fn pdf_light_origin(&self, scene: &Scene, v: &Vertex) -> f32 {
+        let w = v.point - self.point;
+        if w.norm_squared() == 0.0 {
+            return 0.0;
+        }
+
+        if self.infinite {
+            scene
+                .lights
+                .pdf_li(&v.point, &-w.normalize(), f32::INFINITY)
+        } else {
+            scene.lights.pdf_le(&v.point, &self.point).0
+        }
+    }
+}
+
+impl BdptIntegrator {
+    fn camera_subpath<'a>(
+        &self,
+        scene: &'a Scene,
+        ray: &Ray,
+        max_vertices: usize,
+    ) -> Vec<Vertex<'a>> {
+        let ray = Ray::new(ray.origin(), ray.direction().normalize(), ray.time());
+        let beta = vec3::scalar(1.0);
+
+        let mut path = vec![Vertex::camera(ray.origin(), Vec3f::zeros(), beta)];
+        let (_, pdf_dir) = scene.camera.pdf_we(&ray);
+        self.random_walk(scene, ray, beta, pdf_dir, max_vertices - 1, true, &mut path);
+        path
+    }
+
+    fn light_subpath<'a>(
+        &self,
+        scene: &'a Scene,
+        time: f32,
+        max_vertices: usize,
+    ) -> Vec<Vertex<'a>> {
+        let mut path = Vec::new();
+
+        let (light, pick_pdf) = match scene.lights.pick() {
+            Some(picked) => picked,
+            None => return path,
+        };
+        let emission = match light.sample_le(&self.world_bound, time) {
+            Some(emission) => emission,
+            None => return path,
+        };
+        if emission.pdf_pos == 0.0 || emission.pdf_dir == 0.0 || vec3::is_black(&emission.le) {
+            return path;
+        }
+
+        let ray = emission.ray;
+        let infinite = light.get_flags().contains(LightType::Infinite);
+        path.push(Vertex::light(
+            ray.origin(),
+            emission.normal,
+            emission.le,
+            emission.pdf_pos * pick_pdf,
+            infinite,
+        ));
+
+        let beta = emission.le * emission.normal.dot(&ray.direction()).abs()
+            / (pick_pdf * emission.pdf_pos * emission.pdf_dir);
+        let dir = ray.direction();
+        self.random_walk(
+            scene,
+            ray,
+            beta,
+            emission.pdf_dir,
+            max_vertices - 1,
+            false,
+            &mut path,
+        );
+
+        // the origin of rays from infinite lights is spread over a disk
+        if infinite {
+            if let Some(v) = path.get_mut(1) {
+                v.pdf_fwd = emission.pdf_pos;
+                if v.is_on_surface() {
+                    v.pdf_fwd *= dir.dot(&v.normal).abs();
+                }
+            }
+            path[0].pdf_fwd = scene.lights.pdf_li(&path[0].point, &-dir, f32::INFINITY);
+        }
+
+        path
+    }
+
+    // extend `path` by sampling bsdfs, `pdf` is the solid angle pdf of `ray`
+    #[allow(clippy::too_many_arguments)]
+    fn random_walk<'a>(
+        &self,
+        scene: &'a Scene,
+        mut ray: Ray,
+        mut beta: Spectrum,
+        pdf: f32,
+        max_vertices: usize,
+        from_camera: bool,
+        path: &mut Vec<Vertex<'a>>,
+    ) {
+        if max_vertices == 0 {
+            return;
+        }
+
+        let mut pdf_fwd = pdf;
+        let mut bounces = 0;
+        loop {
+            let si = scene.intersect(&ray, 0.001, f32::MAX);
+            if vec3::is_black(&beta) {
+                break;
+            }
+
+            let si = match si {
+                Some(si) => si,
+                None => {
+                    // camera rays escaping the scene see the environment
+                    if from_camera {
+                        path.push(Vertex::light(
+                            ray.origin() + ray.direction(),
+                            -ray.direction(),
+                            beta,
+                            pdf_fwd,
+                            true,
+                        ));
+                    }
+                    break;
+                }
+            };
+
+            if si.material.is_none() {
+                ray = Ray::new(si.point, ray.direction(), ray.time());
+                continue;
+            }
+
+            let wo = si.wo;
+            let bsdf = si.material.and_then(|m| m.compute_bsdf(&si));
+            let mut vertex = Vertex::surface(si, bsdf, beta);
+            vertex.pdf_fwd = path.last().unwrap().convert_density(pdf_fwd, &vertex);
+            bounces += 1;
+
+            // surfaces without bsdf, e.g. lights, absorb
+            let bsdf = match &vertex.bsdf {
+                Some(bsdf) if bounces < max_vertices => bsdf,
+                _ => {
+                    path.push(vertex);
+                    break;
+                }
+            };
+
+            let wi = bsdf.sample_wi(&wo);
+            if vec3::is_near_zero(&wi) {
+                path.push(vertex);
+                break;
+            }
+            let wi = wi.normalize();
+
+            pdf_fwd = bsdf.sample_pdf(&wi, &wo);
+            let bsdf_value = bsdf.f_cos(&wi, &wo);
+            if vec3::is_black(&bsdf_value) || pdf_fwd < f32::EPSILON {
+                path.push(vertex);
+                break;
+            }
+
+            beta = vec3::elementwise_mult(&beta, &bsdf_value) / pdf_fwd;
+            let mut pdf_rev = bsdf.sample_pdf(&wo, &wi);
+            if bsdf.is_delta() {
+                vertex.delta = true;
+                pdf_rev = 0.0;
+                pdf_fwd = 0.0;
+            }
+
+            let prev = path.last_mut().unwrap();
+            prev.pdf_rev = vertex.convert_density(pdf_rev, prev);
+
+            ray = Ray::new(vertex.point, wi, ray.time());
+            path.push(vertex);
+
+            // russian roulette, compensated in beta and left out of the MIS pdfs
+            if bounces > 3 {
+                let rr_prob = f32::min(0.99, beta.max());
+                if random::f32() >= rr_prob {
+                    break;
+                }
+                beta *= 1.0 / rr_prob;
+            }
+        }
+    }
+
+    fn unoccluded(&self, scene: &Scene, p0: &Point3f, p1: &Point3f) -> bool {
+        let d = p1 - p0;
+        let distance = d.norm();
+        let ray = Ray::new(*p0, d / distance, 0.0);
+        !vec3::is_black(&transmittance(scene, &ray, distance - 0.001, None))
+    }
+
+    // geometry term between two vertices, including visibility
+    fn g(&self, scene: &Scene, v0: &Vertex, v1: &Vertex) -> f32 {
+        let d = v0.point - v1.point;
+        let mut g = 1.0 / d.norm_squared();
+        let d = d.normalize();
+
+        if v0.is_on_surface() {
+            g *= v0.normal.dot(&d).abs();
+        }
+        if v1.is_on_surface() {
+            g *= v1.normal.dot(&d).abs();
+        }
+
+        if self.unoccluded(scene, &v0.point, &v1.point) {
+            g
+        } else {
+            0.0
+        }
+    }
+
+    // contribution of strategy (s, t), with the film position if it has to be splatted
+    fn connect(
+        &self,
+        scene: &Scene,
+        settings: &Settings,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        s: usize,
+        t: usize,
+    ) -> (Spectrum, Option<Point2f>) {
+        // escaped camera rays can only be used as they are
+        if t > 1 && s != 0 && camera_path[t - 1].kind == VertexKind::Light {
+            return (Spectrum::zeros(), None);
+        }
+
+        let mut l = Spectrum::zeros();
+        let mut film = None;
+        let mut sampled = None;
+
+        if s == 0 {
+            // camera subpath hits a light
+            let pt = &camera_path[t - 1];
+            if pt.is_light() {
+                l = vec3::elementwise_mult(&pt.le(scene, &camera_path[t - 2]), &pt.beta);
+            }
+        } else if t == 1 {
+            // connect a light vertex to the camera
+            let qs = &light_path[s - 1];
+            if let (true, Some(cs)) = (qs.is_connectible(), scene.camera.sample_wi(&qs.point)) {
+                if cs.pdf > 0.0 && cs.we > 0.0 {
+                    let v = Vertex::camera(
+                        cs.point,
+                        scene.camera.forward(),
+                        vec3::scalar(cs.we / cs.pdf),
+                    );
+                    l = vec3::elementwise_mult(&qs.beta, &qs.f(&v)) * cs.we / cs.pdf;
+                    if qs.is_on_surface() {
+                        l *= cs.wi.dot(&qs.normal).abs();
+                    }
+                    if !vec3::is_black(&l) && !self.unoccluded(scene, &qs.point, &cs.point) {
+                        l = Spectrum::zeros();
+                    }
+                    film = Some(cs.film);
+                    sampled = Some(v);
+                }
+            }
+        } else if s == 1 {
+            // sample a point on a light, i.e. next event estimation
+            let pt = &camera_path[t - 1];
+            if let (true, Some(ls)) = (pt.is_connectible(), scene.lights.sample_li(&pt.point)) {
+                if ls.pdf > 0.0 && !vec3::is_black(&ls.li) {
+                    let infinite = ls.distance.is_infinite();
+                    let distance = if infinite {
+                        2.0 * self.world_radius
+                    } else {
+                        ls.distance
+                    };
+
+                    let mut v = Vertex::light(
+                        pt.point + ls.wi * distance,
+                        ls.normal,
+                        ls.li / ls.pdf,
+                        0.0,
+                        infinite,
+                    );
+                    v.pdf_fwd = v.pdf_light_origin(scene, pt);
+
+                    l = vec3::elementwise_mult(
+                        &vec3::elementwise_mult(&pt.beta, &pt.f(&v)),
+                        &v.beta,
+                    );
+                    if pt.is_on_surface() {
+                        l *= ls.wi.dot(&pt.normal).abs();
+                    }
+                    if !vec3::is_black(&l) && !self.unoccluded(scene, &pt.point, &v.point) {
+                        l = Spectrum::zeros();
+                    }
+                    sampled = Some(v);
+                }
+            }
+        } else {
+            let qs = &light_path[s - 1];
+            let pt = &camera_path[t - 1];
+            if qs.is_connectible() && pt.is_connectible() {
+                l = vec3::elementwise_mult(
+                    &vec3::elementwise_mult(&qs.beta, &qs.f(pt)),
+                    &vec3::elementwise_mult(&pt.f(qs), &pt.beta),
+                );
+                if !vec3::is_black(&l) {
+                    l *= self.g(scene, qs, pt);
+                }
+            }
+        }
+
+        if vec3::is_black(&l) {
+            return (Spectrum::zeros(), None);
+        }
+
+        let weight = self.mis_weight(
+            scene,
+            settings,
+            light_path,
+            camera_path,
+            sampled.as_ref(),
+            s,
+            t,
+        );
+        (l * weight, film)
+    }
+
+    // weight of strategy (s, t) against every other strategy producing the same path
+    #[allow(clippy::too_many_arguments)]
+    fn mis_weight(
+        &self,
+        scene: &Scene,
+        settings: &Settings,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        sampled: Option<&Vertex>,
+        s: usize,
+        t: usize,
+    ) -> f32 {
+        if s + t == 2 {
+            return 1.0;
+        }
+
+        // the endpoints of the connection, sampled ones replace the subpath vertices
+        let qs = match s {
+            0 => None,
+            1 => sampled,
+            _ => Some(&light_path[s - 1]),
+        };
+        let pt = if t == 1 {
+            sampled.unwrap()
+        } else {
+            &camera_path[t - 1]
+        };
+        let qs_minus = if s > 1 {
+            Some(&light_path[s - 2])
+        } else {
+            None
+        };
+        let pt_minus = if t > 1 {
+            Some(&camera_path[t - 2])
+        } else {
+            None
+        };
+
+        // (pdf_fwd, pdf_rev, delta) of the vertices used by this strategy
+        let pdfs = |v: &Vertex| (v.pdf_fwd, v.pdf_rev, v.delta);
+        let mut light_pdfs: Vec<_> = light_path[..s].iter().map(pdfs).collect();
+        let mut camera_pdfs: Vec<_> = camera_path[..t].iter().map(pdfs).collect();
+        if let (1, Some(qs)) = (s, qs) {
+            light_pdfs[0] = pdfs(qs);
+        }
+        if t == 1 {
+            camera_pdfs[0] = pdfs(pt);
+        }
+
+        // the connection vertices are never delta
+        camera_pdfs[t - 1].2 = false;
+        if s > 0 {
+            light_pdfs[s - 1].2 = false;
+        }
+
+        // reverse pdfs of the vertices around the connection
+        let radius = self.world_radius;
+        camera_pdfs[t - 1].1 = match qs {
+            Some(qs) => qs.pdf(scene, radius, qs_minus, pt),
+            None => pt.pdf_light_origin(scene, pt_minus.unwrap()),
+        };
+        if let Some(pt_minus) = pt_minus {
+            camera_pdfs[t - 2].1 = match qs {
+                Some(qs) => pt.pdf(scene, radius, Some(qs), pt_minus),
+                None => pt.pdf_light(scene, radius, pt_minus),
+            };
+        }
+        if let Some(qs) = qs {
+            light_pdfs[s - 1].1 = pt.pdf(scene, radius, pt_minus, qs);
+        }
+        if let (Some(qs), Some(qs_minus)) = (qs, qs_minus) {
+            light_pdfs[s - 2].1 = qs.pdf(scene, radius, Some(pt), qs_minus);
+        }
+
+        let heuristic = |ri: f32| match settings.mis_heuristic {
+            MisHeuristic::Power => ri * ri,
+            _ => ri,
+        };
+        // delta vertices have zero pdfs, which cancel out in the ratios
+        let remap0 = |pdf: f32| if pdf != 0.0 { pdf } else { 1.0 };
+
+        // ratios of the pdfs of the other strategies to this one
+        let mut sum_ri = 0.0;
+        let mut ri = 1.0;
+        for i in (1..t).rev() {
+            ri *= remap0(camera_pdfs[i].1) / remap0(camera_pdfs[i].0);
+            if !camera_pdfs[i].2 && !camera_pdfs[i - 1].2 {
+                sum_ri += heuristic(ri);
+            }
+        }
+
+        let mut ri = 1.0;
+        for i in (0..s).rev() {
+            ri *= remap0(light_pdfs[i].1) / remap0(light_pdfs[i].0);
+            let delta_before = i > 0 && light_pdfs[i - 1].2;
+            if !light_pdfs[i].2 && !delta_before {
+                sum_ri += heuristic(ri);
+            }
+        }
+
+        1.0 / (1.0 + sum_ri)
+    }
+}
+
+impl Integrator for BdptIntegrator {
+    fn supports_spectral(&self) -> bool {
+        true
+    }
+
+    // the splats land on other pixels and are dropped here, without the light paths connected
+    // to the camera the estimate is too dark. The renderer calls `li_splats`
+    fn li(&self, ray: &Ray, scene: &Scene, settings: &Settings) -> Spectrum {
+        static DROPPED: Once = Once::new();
+        let mut splats = Vec::new();
+        let l = self.li_splats(ray, scene, settings, &mut splats);
+        if !splats.is_empty() {
+            DROPPED.call_once(|| {
+                log::warn!("bdpt splats are dropped by `li`, light tracing strategies are missing")
+            });
+        }
+        l
+    }
+
+    fn li_splats(
+        &self,
+        ray: &Ray,
+        scene: &Scene,
+        settings: &Settings,
+        splats: &mut Vec<Splat>,
+    ) -> Spectrum {
+        let max_depth = settings.max_depth.max(0) as usize;
+        let camera_path = self.camera_subpath(scene, ray, max_depth + 2);
+        let light_path = self.light_subpath(scene, ray.time(), max_depth + 1);
+
+        let mut l = Spectrum::zeros();
+        for t in 1..=camera_path.len() {
+            for s in 0..=light_path.len() {
+                // s = 1, t = 1 would be the light seen directly, covered by s = 0, t = 2
+                let depth = (s + t) as i32 - 2;
+                if (s == 1 && t == 1) || depth < 0 || depth > max_depth as i32 {
+                    continue;
+                }
+
+                let (value, film) = self.connect(scene, settings, &light_path, &camera_path, s, t);
+                match film {
+                    Some(film) => splats.push(Splat { film, l: value }),
+                    None => l += value,
+                }
+            }
+        }
+
+        l
+    }
+}
@@ -0,0 +1,81 @@
+use crate::core::{vec3, Integrator, Ray, Scene, Settings, Spectrum};
+
+// Debug integrators visualize a single property of the first hit, misses are black.
+
+pub struct NormalIntegrator {}
+
+impl NormalIntegrator {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Integrator for NormalIntegrator {
+    // map world-space normal from [-1, 1] to [0, 1]
+    fn li(&self, ray: &Ray, scene: &Scene, _settings: &Settings) -> Spectrum {
+        match scene.intersect(ray, 0.001, f32::MAX) {
+            Some(si) => (si.normal + vec3::scalar(1.0)) * 0.5,
+            None => Spectrum::zeros(),
+        }
+    }
+}
+
+pub struct UvIntegrator {}
+
+impl UvIntegrator {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Integrator for UvIntegrator {
+    fn li(&self, ray: &Ray, scene: &Scene, _settings: &Settings) -> Spectrum {
+        match scene.intersect(ray, 0.001, f32::MAX) {
+            Some(si) => Spectrum::new(si.uv[0], si.uv[1], 0.0),
+            None => Spectrum::zeros(),
+        }
+    }
+}
+
+pub struct AlbedoIntegrator {}
+
+impl AlbedoIntegrator {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Integrator for AlbedoIntegrator {
+    fn li(&self, ray: &Ray, scene: &Scene, _settings: &Settings) -> Spectrum {
+        match scene.intersect(ray, 0.001, f32::MAX) {
+            Some(si) => si.material.map_or(Spectrum::zeros(), |m| m.albedo(&si)),
+            None => Spectrum::zeros(),
+        }
+    }
+}
+
+pub struct DepthIntegrator {
+    // distance shown as black
+    near: f32,
+    // distance shown as white
+    far: f32,
+}
+
+impl DepthIntegrator {
+    pub fn new(near: f32, far: f32) -> Self {
+        Self { near, far }
+    }
+}
+
+impl Integrator for DepthIntegrator {
+    fn li(&self, ray: &Ray, scene: &Scene, _settings: &Settings) -> Spectrum {
+        match scene.intersect(ray, 0.001, f32::MAX) {
+            Some(si) => {
+                let distance = si.t_hit * ray.direction().norm();
+                let depth = (distance - self.near) / (self.far - self.near);
+                vec3::scalar(depth.clamp(0.0, 1.0))
+            }
+            None => Spectrum::zeros(),
+        }
+    }
+}
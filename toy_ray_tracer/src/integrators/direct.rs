@@ -0,0 +1,111 @@
+use crate::core::{
+    vec3, Color3, Integrator, Light, MisHeuristic, Ray, Scene, Settings, Spectrum, Vec3f,
+};
+use crate::utils::random;
+
+use super::{sample_direct_bsdf, sample_direct_light};
+
+// Direct lighting only: follows specular bounces, then gathers the light
+// arriving at the first non-specular surface and stops.
+pub struct DirectLightingIntegrator {}
+
+impl DirectLightingIntegrator {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Integrator for DirectLightingIntegrator {
+    fn supports_spectral(&self) -> bool {
+        true
+    }
+
+    fn li(&self, ray: &Ray, scene: &Scene, settings: &Settings) -> Spectrum {
+        let mut ray = ray.clone();
+        let mut color = Color3::zeros();
+        let mut beta = Vec3f::new(1.0, 1.0, 1.0);
+
+        let lights = &scene.lights;
+
+        for _ in 0..settings.max_depth {
+            let si = match scene.intersect(&ray, 0.001, f32::MAX) {
+                Some(si) => si,
+                None => {
+                    color += vec3::elementwise_mult(&beta, &lights.background_l(&ray));
+                    break;
+                }
+            };
+
+            let material = match si.material {
+                Some(material) => material,
+                None => {
+                    ray = Ray::new(si.point, ray.direction(), ray.time());
+                    continue;
+                }
+            };
+            color += vec3::elementwise_mult(&beta, &material.emission(&si));
+
+            let bsdf = match material.compute_bsdf(&si) {
+                Some(bsdf) => bsdf,
+                None => break,
+            };
+            let wo = &si.wo;
+
+            if bsdf.is_delta() {
+                let wi = bsdf.sample_wi(wo);
+                let pdf = bsdf.sample_pdf(&wi, wo);
+                beta = vec3::elementwise_mult(&beta, &bsdf.f_cos(&wi, wo)) / pdf;
+                ray = Ray::new(si.point, wi, ray.time());
+                continue;
+            }
+
+            if settings.mis_heuristic != MisHeuristic::Mixture {
+                // light sample with a shadow ray plus a bsdf sample, combined by MIS
+                color += vec3::elementwise_mult(
+                    &beta,
+                    &sample_direct_light(&si, &bsdf, scene, settings, ray.time(), None),
+                );
+
+                color += vec3::elementwise_mult(
+                    &beta,
+                    &sample_direct_bsdf(&si, &bsdf, scene, settings, ray.time(), None),
+                );
+                break;
+            }
+
+            // one-sample estimate of the light arriving at the surface
+            let mis_weight = settings.mis_weight;
+            let wi = if random::f32() < mis_weight {
+                bsdf.sample_wi(wo)
+            } else {
+                lights.sample_wi(&si.point)
+            };
+
+            if vec3::is_near_zero(&wi) {
+                break;
+            }
+
+            let pdf = mis_weight * bsdf.sample_pdf(&wi, wo)
+                + (1.0 - mis_weight) * lights.sample_pdf(&si.point, &wi);
+            let bsdf_value = bsdf.f_cos(&wi, wo);
+
+            if vec3::is_black(&bsdf_value) || pdf < f32::EPSILON {
+                break;
+            }
+
+            let light_ray = Ray::new(si.point, wi, ray.time());
+            let li = match scene.intersect(&light_ray, 0.001, f32::MAX) {
+                Some(light_si) => light_si
+                    .material
+                    .map_or(Color3::zeros(), |m| m.emission(&light_si)),
+                None => lights.background_l(&light_ray),
+            };
+
+            let beta = vec3::elementwise_mult(&beta, &bsdf_value) / pdf;
+            color += vec3::elementwise_mult(&beta, &li);
+            break;
+        }
+
+        color
+    }
+}
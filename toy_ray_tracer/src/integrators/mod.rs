@@ -0,0 +1,207 @@
+mod ao;
+mod bdpt;
+mod debug;
+mod direct;
+mod path;
+mod pssmlt;
+mod sppm;
+
+pub use ao::AmbientOcclusionIntegrator;
+pub use bdpt::BdptIntegrator;
+pub use debug::{AlbedoIntegrator, DepthIntegrator, NormalIntegrator, UvIntegrator};
+pub use direct::DirectLightingIntegrator;
+pub use path::PathIntegrator;
+pub use pssmlt::PssmltIntegrator;
+pub use sppm::SppmIntegrator;
+
+use crate::core::{
+    vec3, Bsdf, HenyeyGreenstein, Light, MediumPtr, Point3f, Ray, Scene, Settings, Spectrum,
+    SurfaceInteraction, Vec3f,
+};
+
+// utility functions
+
+// next event estimation: sample a light, trace a shadow ray towards it and
+// weight the unoccluded contribution against bsdf sampling.
+// `medium` is the medium on the side of `wo`
+pub fn sample_direct_light(
+    si: &SurfaceInteraction,
+    bsdf: &Bsdf,
+    scene: &Scene,
+    settings: &Settings,
+    time: f32,
+    medium: Option<&MediumPtr>,
+) -> Spectrum {
+    sample_light(
+        &si.point,
+        |wi| (bsdf.f_cos(wi, &si.wo), bsdf.sample_pdf(wi, &si.wo)),
+        |wi| si.medium(wi, medium),
+        scene,
+        settings,
+        time,
+    )
+}
+
+// next event estimation at a scattering point inside `medium`
+pub fn sample_direct_light_medium(
+    point: &Point3f,
+    wo: &Vec3f,
+    phase: &HenyeyGreenstein,
+    scene: &Scene,
+    settings: &Settings,
+    time: f32,
+    medium: Option<&MediumPtr>,
+) -> Spectrum {
+    sample_light(
+        point,
+        |wi| {
+            let p = phase.p(wo, wi);
+            (vec3::scalar(p), p)
+        },
+        |_| medium,
+        scene,
+        settings,
+        time,
+    )
+}
+
+// `f` gives the scattering function (times cosine for surfaces) and its sampling pdf for a
+// direction, `medium_towards` the medium a shadow ray in that direction starts in
+fn sample_light<'a, F, M>(
+    point: &Point3f,
+    f: F,
+    medium_towards: M,
+    scene: &'a Scene,
+    settings: &Settings,
+    time: f32,
+) -> Spectrum
+where
+    F: Fn(&Vec3f) -> (Spectrum, f32),
+    M: Fn(&Vec3f) -> Option<&'a MediumPtr>,
+{
+    let ls = match scene.lights.sample_li(point) {
+        Some(ls) => ls,
+        None => return Spectrum::zeros(),
+    };
+
+    if !(ls.pdf > 0.0 && ls.pdf.is_finite()) || vec3::is_black(&ls.li) {
+        return Spectrum::zeros();
+    }
+
+    let (f_value, f_pdf) = f(&ls.wi);
+    if vec3::is_black(&f_value) {
+        return Spectrum::zeros();
+    }
+
+    // stop right before the light surface
+    let shadow_ray = Ray::new(*point, ls.wi, time);
+    let t_max = if ls.distance.is_finite() {
+        ls.distance * (1.0 - 1e-4)
+    } else {
+        f32::MAX
+    };
+    let tr = transmittance(scene, &shadow_ray, t_max, medium_towards(&ls.wi));
+    if vec3::is_black(&tr) {
+        return Spectrum::zeros();
+    }
+
+    let weight = settings.mis_heuristic.weight(ls.pdf, f_pdf);
+    vec3::elementwise_mult(&vec3::elementwise_mult(&f_value, &ls.li), &tr) * weight / ls.pdf
+}
+
+// the bsdf sampling half of `sample_direct_light`: sample the bsdf and weight the
+// emission found along it against light sampling
+pub fn sample_direct_bsdf(
+    si: &SurfaceInteraction,
+    bsdf: &Bsdf,
+    scene: &Scene,
+    settings: &Settings,
+    time: f32,
+    medium: Option<&MediumPtr>,
+) -> Spectrum {
+    let wo = &si.wo;
+    let wi = bsdf.sample_wi(wo);
+    let pdf = bsdf.sample_pdf(&wi, wo);
+    let bsdf_value = bsdf.f_cos(&wi, wo);
+    if vec3::is_near_zero(&wi) || vec3::is_black(&bsdf_value) || pdf < f32::EPSILON {
+        return Spectrum::zeros();
+    }
+
+    let wi = wi.normalize();
+    let mut light_ray = Ray::new(si.point, wi, time);
+    let mut medium = si.medium(&wi, medium);
+    let mut tr = vec3::scalar(1.0);
+
+    // look through surfaces which only bound media
+    let (li, distance) = loop {
+        let light_si = scene.intersect(&light_ray, 0.001, f32::MAX);
+        if let Some(medium) = medium {
+            let t_max = light_si
+                .as_ref()
+                .map_or(f32::MAX, |light_si| light_si.t_hit);
+            tr = vec3::elementwise_mult(&tr, &medium.tr(&light_ray, t_max));
+        }
+
+        match light_si {
+            Some(light_si) => match light_si.material {
+                Some(material) => {
+                    let distance = (light_si.point - si.point).norm();
+                    break (material.emission(&light_si), distance);
+                }
+                None => {
+                    medium = light_si.medium(&wi, medium);
+                    light_ray = Ray::new(light_si.point, wi, time);
+                }
+            },
+            None => break (scene.lights.background_l(&light_ray), f32::INFINITY),
+        }
+    };
+    if vec3::is_black(&li) || vec3::is_black(&tr) {
+        return Spectrum::zeros();
+    }
+
+    let light_pdf = scene.lights.pdf_li(&si.point, &wi, distance);
+    let weight = settings.mis_heuristic.weight(pdf, light_pdf);
+    vec3::elementwise_mult(&vec3::elementwise_mult(&bsdf_value, &li), &tr) * weight / pdf
+}
+
+// fraction of light passing along `ray` up to `t_max`, zero if a surface blocks it.
+// Surfaces without material only switch to the medium behind them
+pub fn transmittance<'a>(
+    scene: &'a Scene,
+    ray: &Ray,
+    t_max: f32,
+    medium: Option<&'a MediumPtr>,
+) -> Spectrum {
+    if !scene.has_media {
+        return if scene.intersect_p(ray, 0.001, t_max) {
+            Spectrum::zeros()
+        } else {
+            vec3::scalar(1.0)
+        };
+    }
+
+    let mut ray = ray.clone();
+    let mut t_max = t_max;
+    let mut medium = medium;
+    let mut tr = vec3::scalar(1.0);
+    loop {
+        let si = scene.intersect(&ray, 0.001, t_max);
+        if let Some(medium) = medium {
+            let t_hit = si.as_ref().map_or(t_max, |si| si.t_hit);
+            tr = vec3::elementwise_mult(&tr, &medium.tr(&ray, t_hit));
+        }
+
+        let si = match si {
+            Some(si) => si,
+            None => return tr,
+        };
+        if si.material.is_some() || vec3::is_black(&tr) {
+            return Spectrum::zeros();
+        }
+
+        medium = si.medium(&ray.direction(), medium);
+        t_max -= si.t_hit;
+        ray = Ray::new(si.point, ray.direction(), ray.time());
+    }
+}
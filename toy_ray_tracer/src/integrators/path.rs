@@ -0,0 +1,369 @@
+use crate::core::{
+    vec3, Color3, Integrator, Light, MisHeuristic, Point3f, Ray, Scene, Settings, Spectrum, Splat,
+    Vec3f,
+};
+use crate::stats;
+use crate::utils::random;
+
+use super::{sample_direct_light, sample_direct_light_medium};
+
+pub struct PathIntegrator {}
+
+impl PathIntegrator {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Integrator for PathIntegrator {
+    fn li(&self, ray: &Ray, scene: &Scene, settings: &Settings) -> Spectrum {
+        match settings.mis_heuristic {
+            MisHeuristic::Mixture => self.li_mixture(ray, scene, settings),
+            _ => self.li_nee(ray, scene, settings).0,
+        }
+    }
+
+    fn li_direct(
+        &self,
+        ray: &Ray,
+        scene: &Scene,
+        settings: &Settings,
+        _splats: &mut Vec<Splat>,
+    ) -> (Spectrum, Option<Spectrum>) {
+        match settings.mis_heuristic {
+            MisHeuristic::Mixture => (self.li_mixture(ray, scene, settings), None),
+            _ => {
+                let (l, direct) = self.li_nee(ray, scene, settings);
+                (l, Some(direct))
+            }
+        }
+    }
+
+    fn splits_direct(&self, settings: &Settings) -> bool {
+        settings.mis_heuristic != MisHeuristic::Mixture
+    }
+
+    fn supports_spectral(&self) -> bool {
+        true
+    }
+
+    // the mixture sampling looks through media and subsurface materials
+    fn supports_media(&self, settings: &Settings) -> bool {
+        settings.mis_heuristic != MisHeuristic::Mixture
+    }
+
+    fn supports_subsurface(&self, settings: &Settings) -> bool {
+        settings.mis_heuristic != MisHeuristic::Mixture
+    }
+}
+
+impl PathIntegrator {
+    // next event estimation at every non-delta vertex, combined with bsdf sampling by MIS.
+    // Rays through media sample a free-flight distance and may scatter inside them. Also
+    // returns the direct part: emission seen by the camera, light sampled at the first vertex
+    // and emission the first bounce hits
+    fn li_nee(&self, ray: &Ray, scene: &Scene, settings: &Settings) -> (Spectrum, Spectrum) {
+        let mut ray = ray.clone();
+        let mut color = Color3::zeros();
+        // the color before the light sampling at the second vertex
+        let mut direct = None;
+        let mut beta = Vec3f::new(1.0, 1.0, 1.0);
+
+        let lights = &scene.lights;
+        let mut medium = scene.camera.medium();
+
+        // emission found by the camera ray or after a delta bounce is not covered by light sampling
+        let mut specular_bounce = true;
+        let mut prev_point = Point3f::zeros();
+        let mut prev_bsdf_pdf = 0.0;
+
+        let mut bounce = 0;
+        while bounce < settings.max_depth {
+            let si = scene.intersect(&ray, 0.001, f32::MAX);
+
+            if let Some(m) = medium {
+                let t_max = si.as_ref().map_or(f32::MAX, |si| si.t_hit);
+                let ms = m.sample(&ray, t_max);
+                beta = vec3::elementwise_mult(&beta, &ms.weight);
+                if vec3::is_black(&beta) {
+                    break;
+                }
+
+                if let Some(t) = ms.t {
+                    // scattered inside the medium, continue with a direction from the phase function
+                    if bounce == 1 && direct.is_none() {
+                        direct = Some(color);
+                    }
+                    let point = ray.point_at_parameter(t);
+                    let wo = -ray.direction().normalize();
+                    let direct = sample_direct_light_medium(
+                        &point,
+                        &wo,
+                        &ms.phase,
+                        scene,
+                        settings,
+                        ray.time(),
+                        medium,
+                    );
+                    color += vec3::elementwise_mult(&beta, &direct);
+
+                    let (wi, pdf) = ms.phase.sample_p(&wo);
+                    specular_bounce = false;
+                    prev_point = point;
+                    prev_bsdf_pdf = pdf;
+                    ray = Ray::new(point, wi, ray.time());
+
+                    if !self.russian_roulette(&mut beta, bounce) {
+                        break;
+                    }
+                    bounce += 1;
+                    continue;
+                }
+            }
+
+            let si = match si {
+                Some(si) => si,
+                None => {
+                    let background = lights.background_l(&ray);
+                    let weight = if specular_bounce {
+                        1.0
+                    } else {
+                        let light_pdf = lights.pdf_li(&prev_point, &ray.direction(), f32::INFINITY);
+                        settings.mis_heuristic.weight(prev_bsdf_pdf, light_pdf)
+                    };
+                    color += vec3::elementwise_mult(&beta, &background) * weight;
+                    break;
+                }
+            };
+
+            // surfaces without material only bound media, pass through them
+            let material = match si.material {
+                Some(material) => material,
+                None => {
+                    medium = si.medium(&ray.direction(), medium);
+                    ray = Ray::new(si.point, ray.direction(), ray.time());
+                    continue;
+                }
+            };
+
+            let emission = material.emission(&si);
+            if !vec3::is_black(&emission) {
+                let weight = if specular_bounce {
+                    1.0
+                } else {
+                    let distance = (si.point - prev_point).norm();
+                    let light_pdf = lights.pdf_li(&prev_point, &ray.direction(), distance);
+                    settings.mis_heuristic.weight(prev_bsdf_pdf, light_pdf)
+                };
+                color += vec3::elementwise_mult(&beta, &emission) * weight;
+            }
+            if bounce == 1 && direct.is_none() {
+                direct = Some(color);
+            }
+
+            let bsdf = match material.compute_bsdf(&si) {
+                Some(bsdf) => bsdf,
+                None => break,
+            };
+            let wo = &si.wo;
+
+            if !bsdf.is_delta() {
+                let direct = sample_direct_light(&si, &bsdf, scene, settings, ray.time(), medium);
+                color += vec3::elementwise_mult(&beta, &direct);
+            }
+
+            let wi = bsdf.sample_wi(wo);
+            if vec3::is_near_zero(&wi) {
+                break;
+            }
+
+            let pdf = bsdf.sample_pdf(&wi, wo);
+            let bsdf_value = bsdf.f_cos(&wi, wo);
+            if vec3::is_black(&bsdf_value) || pdf < f32::EPSILON {
+                break;
+            }
+
+            beta = vec3::elementwise_mult(&beta, &bsdf_value) / pdf;
+
+            // refracted into a subsurface material: walk below the surface and continue from
+            // where the walk leaves it, like from a diffuse surface
+            let (si, bsdf, wi, pdf) = match material.bssrdf(&si) {
+                Some(bssrdf) if si.front_face && wi.dot(&si.normal) < 0.0 => {
+                    let (exit, weight) = match bssrdf.walk(&si, &wi, scene, ray.time()) {
+                        Some(walk) => walk,
+                        None => break,
+                    };
+                    beta = vec3::elementwise_mult(&beta, &weight);
+
+                    let bsdf = bssrdf.exit_bsdf(&exit);
+                    let direct =
+                        sample_direct_light(&exit, &bsdf, scene, settings, ray.time(), medium);
+                    color += vec3::elementwise_mult(&beta, &direct);
+
+                    let wi = bsdf.sample_wi(&exit.wo);
+                    let pdf = bsdf.sample_pdf(&wi, &exit.wo);
+                    let bsdf_value = bsdf.f_cos(&wi, &exit.wo);
+                    if vec3::is_near_zero(&wi) || vec3::is_black(&bsdf_value) || pdf < f32::EPSILON
+                    {
+                        break;
+                    }
+                    beta = vec3::elementwise_mult(&beta, &bsdf_value) / pdf;
+                    (exit, bsdf, wi, pdf)
+                }
+                _ => (si, bsdf, wi, pdf),
+            };
+
+            specular_bounce = bsdf.is_delta();
+            prev_point = si.point;
+            prev_bsdf_pdf = pdf;
+            medium = si.medium(&wi, medium);
+            ray = Ray::new(si.point, wi.normalize(), ray.time());
+
+            if beta == Vec3f::zeros() {
+                break;
+            }
+
+            if !self.russian_roulette(&mut beta, bounce) {
+                break;
+            }
+            bounce += 1;
+        }
+
+        stats::add_path_length(bounce as usize);
+        (color, direct.unwrap_or(color))
+    }
+
+    // after a few bounces, stop paths with low throughput and boost the surviving ones
+    fn russian_roulette(&self, beta: &mut Vec3f, bounce: i32) -> bool {
+        if bounce > 3 {
+            let rr_prob = f32::min(0.99, beta.max());
+            if random::f32() >= rr_prob {
+                return false;
+            }
+            *beta *= 1.0 / rr_prob;
+        }
+        true
+    }
+
+    // one-sample mixture of bsdf and light sampling, weighted by `settings.mis_weight`
+    fn li_mixture(&self, ray: &Ray, scene: &Scene, settings: &Settings) -> Spectrum {
+        let mut ray = ray.clone();
+        let mut color = Color3::zeros();
+        let mut beta = Vec3f::new(1.0, 1.0, 1.0);
+
+        let lights = &scene.lights;
+
+        // bounces taken before the path ended
+        let mut bounces = 0;
+        for bounce in 0..settings.max_depth {
+            if let Some(si) = scene.intersect(&ray, 0.001, f32::MAX) {
+                let material = match si.material {
+                    Some(material) => material,
+                    None => {
+                        ray = Ray::new(si.point, ray.direction(), ray.time());
+                        continue;
+                    }
+                };
+                // TODO: support single sided light
+                let emission = if si.wo.dot(&si.normal) > 0.0 {
+                    material.emission(&si)
+                } else {
+                    // Color3::zeros()
+                    material.emission(&si)
+                };
+
+                color += vec3::elementwise_mult(&beta, &emission);
+
+                if let Some(bsdf) = material.compute_bsdf(&si) {
+                    let wo = &si.wo;
+
+                    // delta 分布 brdf，如 镜面反射
+                    if bsdf.is_delta() {
+                        let wi = bsdf.sample_wi(wo);
+                        let pdf = bsdf.sample_pdf(&wi, wo);
+                        let bsdf_value = bsdf.f_cos(&wi, wo);
+
+                        ray = Ray::new(si.point, wi, ray.time());
+                        beta = vec3::elementwise_mult(&beta, &bsdf_value) / pdf;
+                    } else {
+                        // 非 delta 分布 brdf，如 漫反射
+
+                        // bsdf and light sampling weight
+                        let mis_weight = settings.mis_weight;
+
+                        let wi = if random::f32() < mis_weight {
+                            bsdf.sample_wi(wo)
+                        } else {
+                            lights.sample_wi(&si.point)
+                        };
+
+                        // no light ray
+                        if vec3::is_near_zero(&wi) {
+                            break;
+                        }
+
+                        let pdf = mis_weight * bsdf.sample_pdf(&wi, wo)
+                            + (1.0 - mis_weight) * lights.sample_pdf(&si.point, &wi);
+
+                        let bsdf_value = bsdf.f_cos(&wi, wo);
+
+                        // black, stop trace
+                        if vec3::is_black(&bsdf_value) || pdf < f32::EPSILON {
+                            break;
+                        }
+
+                        ray = Ray::new(si.point, wi, ray.time());
+                        let old_beta = beta;
+                        beta = vec3::elementwise_mult(&beta, &bsdf_value) / pdf;
+
+                        if log::max_level() >= log::Level::Trace {
+                            log::trace!("wi: {:?}, wo: {:?}, normal: {:?}", &wi, &wo, &si.normal);
+                            log::trace!(
+                                "wi.norm(): {:?}, wo.norm(): {:?}, normal.norm(): {:?}",
+                                &wi.norm(),
+                                &wo.norm(),
+                                &si.normal.norm()
+                            );
+
+                            log::trace!(
+                                "pdf: {:?}, bsdf_value: {:?}, beta: {:?}, old_beta: {:?}",
+                                pdf,
+                                bsdf_value,
+                                beta,
+                                old_beta,
+                            );
+                        }
+                    }
+                } else {
+                    // no bsdf for material, stop trace
+                    break;
+                }
+            } else {
+                // no hit, return environment lights
+                color += vec3::elementwise_mult(&beta, &lights.background_l(&ray));
+                break;
+            }
+
+            if beta == Vec3f::zeros() {
+                break;
+            }
+
+            if bounce > 3 {
+                // prop 0.99 or beta
+                let rr_prob = f32::min(0.99, beta.max());
+
+                // rr_prop stop trace
+                if random::f32() >= rr_prob {
+                    break;
+                }
+
+                // (1 - rr_prob) continue to trace
+                beta *= 1.0 / rr_prob;
+            }
+            bounces = bounce + 1;
+        }
+
+        stats::add_path_length(bounces as usize);
+        color
+    }
+}
@@ -0,0 +1,378 @@
+use std::f32::consts::PI;
+use std::sync::{Arc, Mutex};
+
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+
+use crate::core::{
+    vec3, Integrator, PixelSampler, Ray, Scene, Settings, Spectrum, Vec3List, Vec3f,
+};
+use crate::utils::{random, ExecutionTimer};
+
+use super::PathIntegrator;
+
+// chains run in parallel before their splats are merged, per thread
+const CHAINS_PER_THREAD: usize = 4;
+
+// largest f32 below 1
+const ONE_MINUS_EPSILON: f32 = 1.0 - f32::EPSILON / 2.0;
+
+// Primary sample space Metropolis light transport (Kelemen et al. 2002). A path is the
+// vector of random numbers the path tracer draws for it, the film position first. Markov
+// chains wander through these vectors, either replacing all numbers (large step) or
+// moving them a little (small step), and stay where the path carries much light. Once a
+// chain found a path through a narrow gap it explores its neighbors instead of searching
+// again. The chains start from paths of a bootstrap pass, which also estimates the
+// brightness of the image the visited paths are scaled to.
+pub struct PssmltIntegrator {
+    path: PathIntegrator,
+    bootstrap_samples: usize,
+    chains: usize,
+    large_step_probability: f32,
+    sigma: f32,
+}
+
+impl PssmltIntegrator {
+    pub fn new(
+        bootstrap_samples: usize,
+        chains: usize,
+        large_step_probability: f32,
+        sigma: f32,
+    ) -> Self {
+        Self {
+            path: PathIntegrator::new(),
+            bootstrap_samples,
+            chains,
+            large_step_probability,
+            sigma,
+        }
+    }
+
+    // the pixel and radiance of the path the current numbers of the sampler describe
+    fn trace(&self, scene: &Scene, settings: &Settings) -> (usize, Spectrum) {
+        let (width, height) = (settings.width, settings.height);
+        let (u, v) = random::f32_2d();
+        let ray = scene.camera.get_ray(u, v);
+        let l = self.path.li(&ray, scene, settings);
+        // rows count from the top, `v` from the bottom
+        let i = ((u * width as f32) as usize).min(width - 1);
+        let j = ((v * height as f32) as usize).min(height - 1);
+        ((height - j - 1) * width + i, l)
+    }
+
+    // runs a chain of `mutations` steps from bootstrap path `start`, returns the splats and
+    // the number of accepted steps
+    fn run_chain(
+        &self,
+        scene: &Scene,
+        settings: &Settings,
+        start: usize,
+        rng: fastrand::Rng,
+        mutations: u64,
+    ) -> (Vec<(usize, Spectrum)>, u64) {
+        let sampler = Arc::new(Mutex::new(MltSampler::new(
+            stream(settings.seed, start as u64),
+            self.sigma,
+            self.large_step_probability,
+        )));
+        random::set_sampler(Some(Box::new(SharedSampler(sampler.clone()))));
+
+        // replays the bootstrap path, then mutates with numbers of its own, chains starting
+        // from the same path part ways
+        let (mut pixel, mut l) = self.trace(scene, settings);
+        let mut y = contribution(&l);
+        sampler.lock().unwrap().rng = rng;
+
+        let mut splats = Vec::with_capacity(2 * mutations as usize);
+        let mut accepted = 0;
+        for _ in 0..mutations {
+            sampler.lock().unwrap().start_iteration();
+            let (proposed_pixel, proposed_l) = self.trace(scene, settings);
+            let proposed_y = contribution(&proposed_l);
+            let accept = if y > 0.0 {
+                (proposed_y / y).min(1.0)
+            } else {
+                1.0
+            };
+
+            // both paths are splatted by their chance to be the next state, which
+            // keeps the light of rejected proposals
+            if accept > 0.0 && proposed_y > 0.0 {
+                splats.push((proposed_pixel, proposed_l * (accept / proposed_y)));
+            }
+            if accept < 1.0 {
+                splats.push((pixel, l * ((1.0 - accept) / y)));
+            }
+
+            let mut sampler = sampler.lock().unwrap();
+            if sampler.rng.f32() < accept {
+                sampler.accept();
+                (pixel, l, y) = (proposed_pixel, proposed_l, proposed_y);
+                accepted += 1;
+            } else {
+                sampler.reject();
+            }
+        }
+
+        random::set_sampler(None);
+        (splats, accepted)
+    }
+}
+
+impl Integrator for PssmltIntegrator {
+    // the chains need the whole image, see `render`. A single ray is the path it mutates
+    fn li(&self, ray: &Ray, scene: &Scene, settings: &Settings) -> Spectrum {
+        self.path.li(ray, scene, settings)
+    }
+
+    // the mutated paths are those of the path integrator
+    fn supports_media(&self, settings: &Settings) -> bool {
+        self.path.supports_media(settings)
+    }
+
+    fn supports_subsurface(&self, settings: &Settings) -> bool {
+        self.path.supports_subsurface(settings)
+    }
+
+    fn render(&self, scene: &Scene, settings: &Settings) -> Option<Vec3List> {
+        let npixels = settings.width * settings.height;
+        let nbootstrap = self.bootstrap_samples.max(1);
+        let nchains = self.chains.max(1);
+        let total_mutations = settings.nsamples.max(1) as u64 * npixels as u64;
+
+        // the average contribution of independent paths is the brightness of the image
+        let weights: Vec<f32> = (0..nbootstrap)
+            .into_par_iter()
+            .map(|k| {
+                let sampler = MltSampler::new(
+                    stream(settings.seed, k as u64),
+                    self.sigma,
+                    self.large_step_probability,
+                );
+                let sampler = SharedSampler(Arc::new(Mutex::new(sampler)));
+                random::set_sampler(Some(Box::new(sampler)));
+                let (_, l) = self.trace(scene, settings);
+                random::set_sampler(None);
+                contribution(&l)
+            })
+            .collect();
+        let cdf: Vec<f64> = weights
+            .iter()
+            .scan(0.0, |sum, w| {
+                *sum += *w as f64;
+                Some(*sum)
+            })
+            .collect();
+        let total_weight = *cdf.last().unwrap();
+        if total_weight <= 0.0 {
+            log::warn!("no bootstrap path carries light, the image stays black");
+            return Some(vec![Vec3f::zeros(); npixels].into());
+        }
+        let b = total_weight / nbootstrap as f64;
+        log::debug!("pssmlt bootstrap done, average contribution {:.4}", b);
+
+        let mut colors = vec![Vec3f::zeros(); npixels];
+        let mut accepted = 0;
+        // chains are merged in a fixed order, floating point sums come out the same with
+        // any number of threads
+        let chains: Vec<usize> = (0..nchains).collect();
+        let wave = rayon::current_num_threads() * CHAINS_PER_THREAD;
+        for (n, wave_chains) in chains.chunks(wave).enumerate() {
+            let _timer = ExecutionTimer::new(|start_time| {
+                log::debug!(
+                    "pssmlt chains elapsed {} ms, progress={}/{}",
+                    start_time.elapsed().as_millis(),
+                    n * wave + wave_chains.len(),
+                    nchains
+                )
+            });
+
+            let results: Vec<(Vec<(usize, Spectrum)>, u64)> = wave_chains
+                .par_iter()
+                .map(|&k| {
+                    // the start is picked proportional to the bootstrap contributions
+                    let rng =
+                        fastrand::Rng::with_seed(stream(settings.seed, (nbootstrap + k) as u64));
+                    let target = rng.f64() * total_weight;
+                    let start = cdf.partition_point(|c| *c <= target).min(nbootstrap - 1);
+                    let mutations = total_mutations / nchains as u64
+                        + ((k as u64) < total_mutations % nchains as u64) as u64;
+                    self.run_chain(scene, settings, start, rng, mutations)
+                })
+                .collect();
+            for (splats, chain_accepted) in results {
+                for (idx, l) in splats {
+                    colors[idx] += l;
+                }
+                accepted += chain_accepted;
+            }
+        }
+        log::debug!(
+            "pssmlt accepted {:.1}% of the mutations",
+            accepted as f64 / total_mutations as f64 * 100.0
+        );
+
+        let scale = (b * npixels as f64 / total_mutations as f64) as f32;
+        let colors: Vec<Vec3f> = colors.iter().map(|c| c * scale).collect();
+        Some(colors.into())
+    }
+}
+
+// the scalar the chains are distributed by, broken paths carry nothing
+fn contribution(l: &Spectrum) -> f32 {
+    let y = vec3::luminance(l);
+    if y.is_finite() && y > 0.0 {
+        y
+    } else {
+        0.0
+    }
+}
+
+// seed of path `index` of the bootstrap pass, or of a chain after them
+fn stream(seed: u64, index: u64) -> u64 {
+    random::mix(random::mix(seed) ^ index)
+}
+
+#[derive(Clone, Copy)]
+struct PrimarySample {
+    value: f32,
+    // iteration of the last change, small steps in between are applied at once when the
+    // number is drawn again
+    modified: u64,
+    // the value and iteration before the change of the current iteration
+    backup: (f32, u64),
+}
+
+// The random numbers of a path with their mutations, after pbrt's MLTSampler. Numbers are
+// mutated lazily when the path draws them, numbers a shorter path doesn't need keep their
+// value.
+struct MltSampler {
+    rng: fastrand::Rng,
+    sigma: f32,
+    large_step_probability: f32,
+    samples: Vec<PrimarySample>,
+    iteration: u64,
+    large_step: bool,
+    last_large_step: u64,
+    // the next number of the path
+    dimension: usize,
+}
+
+impl MltSampler {
+    // starts with a large step, the first path has independent numbers
+    fn new(seed: u64, sigma: f32, large_step_probability: f32) -> Self {
+        Self {
+            rng: fastrand::Rng::with_seed(seed),
+            sigma,
+            large_step_probability,
+            samples: Vec::new(),
+            iteration: 0,
+            large_step: true,
+            last_large_step: 0,
+            dimension: 0,
+        }
+    }
+
+    fn start_iteration(&mut self) {
+        self.iteration += 1;
+        self.large_step = self.rng.f32() < self.large_step_probability;
+        self.dimension = 0;
+    }
+
+    fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step = self.iteration;
+        }
+    }
+
+    fn reject(&mut self) {
+        for sample in self.samples.iter_mut() {
+            if sample.modified == self.iteration {
+                (sample.value, sample.modified) = sample.backup;
+            }
+        }
+        self.iteration -= 1;
+    }
+
+    fn next(&mut self) -> f32 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        if dimension == self.samples.len() {
+            // a number no path drew so far, as if drawn at the last large step
+            let value = self.rng.f32();
+            self.samples.push(PrimarySample {
+                value,
+                modified: self.last_large_step,
+                backup: (value, self.last_large_step),
+            });
+        }
+
+        let sample = &mut self.samples[dimension];
+        if sample.modified < self.last_large_step {
+            sample.value = self.rng.f32();
+            sample.modified = self.last_large_step;
+        }
+        sample.backup = (sample.value, sample.modified);
+
+        if self.large_step {
+            sample.value = self.rng.f32();
+        } else {
+            // normal distributed offset by Box-Muller, the steps missed add up
+            let small_steps = (self.iteration - sample.modified) as f32;
+            let r = (-2.0 * (1.0 - self.rng.f32()).ln()).sqrt();
+            let normal = r * (2.0 * PI * self.rng.f32()).cos();
+            let value = sample.value + normal * self.sigma * small_steps.sqrt();
+            sample.value = (value - value.floor()).min(ONE_MINUS_EPSILON);
+        }
+        sample.modified = self.iteration;
+        sample.value
+    }
+}
+
+// the thread's random numbers come from the sampler of the chain while it traces
+struct SharedSampler(Arc<Mutex<MltSampler>>);
+
+impl PixelSampler for SharedSampler {
+    fn start_sample(&mut self, _pixel: u64, _index: u64) {}
+
+    fn get_1d(&mut self) -> f32 {
+        self.0.lock().unwrap().next()
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let mut sampler = self.0.lock().unwrap();
+        (sampler.next(), sampler.next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MltSampler;
+
+    // rejected steps restore the numbers, accepted large steps replace all of them
+    #[test]
+    fn test_mlt_sampler() {
+        let mut sampler = MltSampler::new(1, 0.01, 0.0);
+        let first: Vec<f32> = (0..4).map(|_| sampler.next()).collect();
+
+        sampler.start_iteration();
+        let small: Vec<f32> = (0..4).map(|_| sampler.next()).collect();
+        for (a, b) in first.iter().zip(&small) {
+            let d = (a - b).abs();
+            assert!(d.min(1.0 - d) < 0.1 && d > 0.0);
+        }
+        sampler.reject();
+        sampler.start_iteration();
+        sampler.large_step = true;
+        let large: Vec<f32> = (0..2).map(|_| sampler.next()).collect();
+        sampler.accept();
+        assert_eq!(sampler.samples[2].value, first[2]);
+        assert!(sampler.samples[2].modified < sampler.last_large_step);
+
+        // a number untouched since the large step is drawn anew
+        sampler.start_iteration();
+        let after: Vec<f32> = (0..3).map(|_| sampler.next()).collect();
+        assert!(after.iter().all(|v| (0.0..1.0).contains(v)));
+        assert_ne!(after[2], first[2]);
+        assert!((after[0] - large[0]).abs() < 0.1 || (after[0] - large[0]).abs() > 0.9);
+    }
+}
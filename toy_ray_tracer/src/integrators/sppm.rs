@@ -0,0 +1,366 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::sync::Once;
+
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefMutIterator, ParallelIterator,
+};
+
+use crate::core::{
+    vec3, Bsdf, Integrator, Light, Point3f, Ray, Scene, Settings, Spectrum, Vec3List, Vec3f, AABB,
+};
+use crate::utils::{random, ExecutionTimer};
+
+use super::{sample_direct_bsdf, sample_direct_light};
+
+// photons traced by one task, their deposits are kept until the pass is merged
+const PHOTON_CHUNK_SIZE: usize = 4096;
+
+// Stochastic progressive photon mapping. Every pass traces one camera path per pixel
+// to its first non-specular hit (the visible point), shoots photons from the lights and
+// gathers those landing within the radius of the visible points. The radius shrinks
+// after each pass, so the estimate converges, and caustics seen through glass are found
+// by photons instead of by chance. Direct lighting at the visible points uses NEE.
+pub struct SppmIntegrator {
+    world_bound: AABB,
+    world_radius: f32,
+}
+
+impl SppmIntegrator {
+    pub fn new(world_bound: AABB) -> Self {
+        let (_, world_radius) = world_bound.bounding_sphere();
+        Self {
+            world_bound,
+            world_radius,
+        }
+    }
+}
+
+struct VisiblePoint {
+    point: Point3f,
+    wo: Vec3f,
+    bsdf: Bsdf,
+    // throughput from the camera
+    beta: Spectrum,
+}
+
+struct SppmPixel {
+    radius: f32,
+    // accumulated photon count, reduced by `alpha` each pass
+    n: f32,
+    // flux within `radius`
+    tau: Spectrum,
+    // direct and specular light found by the camera paths, summed over passes
+    ld: Spectrum,
+    vp: Option<VisiblePoint>,
+}
+
+// flux and number of photons gathered at a pixel in the current pass
+#[derive(Clone, Copy)]
+struct Gathered {
+    phi: Spectrum,
+    m: usize,
+}
+
+// uniform grid hashing the visible points by the cells their gather sphere overlaps
+struct HashGrid {
+    cell_size: f32,
+    cells: HashMap<[i32; 3], Vec<usize>>,
+}
+
+impl HashGrid {
+    fn new(pixels: &[SppmPixel]) -> Self {
+        let max_radius = pixels
+            .iter()
+            .filter(|pixel| pixel.vp.is_some())
+            .fold(0.0f32, |r, pixel| r.max(pixel.radius));
+        let cell_size = (2.0 * max_radius).max(f32::EPSILON);
+
+        let mut grid = Self {
+            cell_size,
+            cells: HashMap::new(),
+        };
+
+        for (idx, pixel) in pixels.iter().enumerate() {
+            let vp = match &pixel.vp {
+                Some(vp) => vp,
+                None => continue,
+            };
+
+            let r = vec3::scalar(pixel.radius);
+            let min = grid.cell(&(vp.point - r));
+            let max = grid.cell(&(vp.point + r));
+            for x in min[0]..=max[0] {
+                for y in min[1]..=max[1] {
+                    for z in min[2]..=max[2] {
+                        grid.cells.entry([x, y, z]).or_default().push(idx);
+                    }
+                }
+            }
+        }
+
+        grid
+    }
+
+    fn cell(&self, p: &Point3f) -> [i32; 3] {
+        let p = p / self.cell_size;
+        [p.x.floor() as i32, p.y.floor() as i32, p.z.floor() as i32]
+    }
+
+    // pixels whose gather sphere may contain `p`
+    fn candidates(&self, p: &Point3f) -> &[usize] {
+        self.cells.get(&self.cell(p)).map_or(&[], |v| v.as_slice())
+    }
+}
+
+impl SppmIntegrator {
+    // follow specular bounces from the camera, collecting the light found on the way
+    fn trace_camera(&self, ray: Ray, scene: &Scene, settings: &Settings, pixel: &mut SppmPixel) {
+        let mut ray = ray;
+        let mut beta = vec3::scalar(1.0);
+        pixel.vp = None;
+
+        for _ in 0..settings.max_depth {
+            let si = match scene.intersect(&ray, 0.001, f32::MAX) {
+                Some(si) => si,
+                None => {
+                    pixel.ld += vec3::elementwise_mult(&beta, &scene.lights.background_l(&ray));
+                    break;
+                }
+            };
+
+            let material = match si.material {
+                Some(material) => material,
+                None => {
+                    ray = Ray::new(si.point, ray.direction(), ray.time());
+                    continue;
+                }
+            };
+            pixel.ld += vec3::elementwise_mult(&beta, &material.emission(&si));
+
+            let bsdf = match material.compute_bsdf(&si) {
+                Some(bsdf) => bsdf,
+                None => break,
+            };
+            let wo = si.wo;
+
+            if !bsdf.is_delta() {
+                let direct = sample_direct_light(&si, &bsdf, scene, settings, ray.time(), None)
+                    + sample_direct_bsdf(&si, &bsdf, scene, settings, ray.time(), None);
+                pixel.ld += vec3::elementwise_mult(&beta, &direct);
+                pixel.vp = Some(VisiblePoint {
+                    point: si.point,
+                    wo,
+                    bsdf,
+                    beta,
+                });
+                break;
+            }
+
+            let wi = bsdf.sample_wi(&wo);
+            let pdf = bsdf.sample_pdf(&wi, &wo);
+            let bsdf_value = bsdf.f_cos(&wi, &wo);
+            if vec3::is_near_zero(&wi) || vec3::is_black(&bsdf_value) || pdf < f32::EPSILON {
+                break;
+            }
+
+            beta = vec3::elementwise_mult(&beta, &bsdf_value) / pdf;
+            ray = Ray::new(si.point, wi.normalize(), ray.time());
+        }
+    }
+
+    fn trace_photon(
+        &self,
+        scene: &Scene,
+        settings: &Settings,
+        pixels: &[SppmPixel],
+        grid: &HashGrid,
+        deposits: &mut Vec<(usize, Spectrum)>,
+    ) {
+        let emission = match scene.lights.sample_le(&self.world_bound, 0.0) {
+            Some(emission) => emission,
+            None => return,
+        };
+        if emission.pdf_pos == 0.0 || emission.pdf_dir == 0.0 || vec3::is_black(&emission.le) {
+            return;
+        }
+
+        let mut ray = emission.ray;
+        let mut beta = emission.le * emission.normal.dot(&ray.direction()).abs()
+            / (emission.pdf_pos * emission.pdf_dir);
+
+        let mut depth = 0;
+        while depth < settings.max_depth {
+            let si = match scene.intersect(&ray, 0.001, f32::MAX) {
+                Some(si) => si,
+                None => break,
+            };
+
+            let material = match si.material {
+                Some(material) => material,
+                None => {
+                    ray = Ray::new(si.point, ray.direction(), ray.time());
+                    continue;
+                }
+            };
+
+            // the first hit is direct lighting, which the visible points sample themselves
+            if depth > 0 {
+                let wi = -ray.direction();
+                for &idx in grid.candidates(&si.point) {
+                    let pixel = &pixels[idx];
+                    let vp = pixel.vp.as_ref().unwrap();
+                    if (vp.point - si.point).norm_squared() > pixel.radius * pixel.radius {
+                        continue;
+                    }
+
+                    deposits.push((idx, vec3::elementwise_mult(&beta, &vp.bsdf.f(&wi, &vp.wo))));
+                }
+            }
+            depth += 1;
+
+            let bsdf = match material.compute_bsdf(&si) {
+                Some(bsdf) => bsdf,
+                None => break,
+            };
+            let wo = si.wo;
+
+            let wi = bsdf.sample_wi(&wo);
+            let pdf = bsdf.sample_pdf(&wi, &wo);
+            let bsdf_value = bsdf.f_cos(&wi, &wo);
+            if vec3::is_near_zero(&wi) || vec3::is_black(&bsdf_value) || pdf < f32::EPSILON {
+                break;
+            }
+
+            // russian roulette keeps the photon power roughly constant
+            let beta_new = vec3::elementwise_mult(&beta, &bsdf_value) / pdf;
+            let q = f32::max(0.0, 1.0 - beta_new.max() / beta.max());
+            if random::f32() < q {
+                break;
+            }
+            beta = beta_new / (1.0 - q);
+            ray = Ray::new(si.point, wi.normalize(), ray.time());
+        }
+    }
+}
+
+impl Integrator for SppmIntegrator {
+    // photons can't be traced per camera ray, see `render`. A single ray only finds the
+    // direct and specular light, the indirect light photons would bring is missing
+    fn li(&self, ray: &Ray, scene: &Scene, settings: &Settings) -> Spectrum {
+        static NO_PHOTONS: Once = Once::new();
+        NO_PHOTONS.call_once(|| {
+            log::warn!("sppm traces no photons per camera ray, indirect light is missing")
+        });
+
+        let mut pixel = SppmPixel {
+            radius: 0.0,
+            n: 0.0,
+            tau: Spectrum::zeros(),
+            ld: Spectrum::zeros(),
+            vp: None,
+        };
+        self.trace_camera(ray.clone(), scene, settings, &mut pixel);
+        pixel.ld
+    }
+
+    fn render(&self, scene: &Scene, settings: &Settings) -> Option<Vec3List> {
+        let width = settings.width;
+        let height = settings.height;
+        let passes = settings.nsamples.max(1) as usize;
+        let opts = &settings.photon_mapping;
+        let camera = &scene.camera;
+
+        let initial_radius = opts.initial_radius.unwrap_or(self.world_radius * 0.01);
+        let mut pixels: Vec<SppmPixel> = (0..width * height)
+            .map(|_| SppmPixel {
+                radius: initial_radius,
+                n: 0.0,
+                tau: Spectrum::zeros(),
+                ld: Spectrum::zeros(),
+                vp: None,
+            })
+            .collect();
+
+        for pass in 0..passes {
+            let _timer = ExecutionTimer::new(|start_time| {
+                log::debug!(
+                    "sppm pass elapsed {} ms, progress={}/{}",
+                    start_time.elapsed().as_millis(),
+                    pass + 1,
+                    passes
+                )
+            });
+
+            pixels.par_iter_mut().enumerate().for_each(|(idx, pixel)| {
+                random::set_sampler(Some(settings.sampler.build(settings.seed, passes as u32)));
+                random::start_sample(idx as u64, pass as u64);
+                let (i, j) = (idx % width, height - idx / width - 1);
+                let (dx, dy) = random::f32_2d();
+                let u = (i as f32 + dx) / width as f32;
+                let v = (j as f32 + dy) / height as f32;
+                self.trace_camera(camera.get_ray(u, v), scene, settings, pixel);
+                random::set_sampler(None);
+            });
+
+            let grid = HashGrid::new(&pixels);
+            let empty = Gathered {
+                phi: Spectrum::zeros(),
+                m: 0,
+            };
+            // photons get their own streams after the pixels ones, their deposits are
+            // summed in photon order so that the result doesn't depend on the threads
+            let starts: Vec<usize> = (0..opts.photons_per_pass)
+                .step_by(PHOTON_CHUNK_SIZE)
+                .collect();
+            let chunks: Vec<Vec<(usize, Spectrum)>> = starts
+                .into_par_iter()
+                .map(|start| {
+                    let end = (start + PHOTON_CHUNK_SIZE).min(opts.photons_per_pass);
+                    let mut deposits = Vec::new();
+                    random::set_sampler(Some(settings.sampler.build(settings.seed, passes as u32)));
+                    for k in start..end {
+                        random::start_sample((width * height + k) as u64, pass as u64);
+                        self.trace_photon(scene, settings, &pixels, &grid, &mut deposits);
+                    }
+                    random::set_sampler(None);
+                    deposits
+                })
+                .collect();
+
+            let mut gathered = vec![empty; width * height];
+            for (idx, phi) in chunks.into_iter().flatten() {
+                gathered[idx].phi += phi;
+                gathered[idx].m += 1;
+            }
+
+            // keep a fraction `alpha` of the new photons and shrink the radius to match
+            for (pixel, g) in pixels.iter_mut().zip(gathered.iter()) {
+                let vp = match &pixel.vp {
+                    Some(vp) => vp,
+                    None => continue,
+                };
+                if g.m == 0 {
+                    continue;
+                }
+
+                let n = pixel.n + opts.alpha * g.m as f32;
+                let radius = pixel.radius * (n / (pixel.n + g.m as f32)).sqrt();
+                let phi = vec3::elementwise_mult(&vp.beta, &g.phi);
+                pixel.tau = (pixel.tau + phi) * (radius * radius) / (pixel.radius * pixel.radius);
+                pixel.n = n;
+                pixel.radius = radius;
+            }
+        }
+
+        let nphotons = (passes * opts.photons_per_pass) as f32;
+        let colors: Vec<Vec3f> = pixels
+            .iter()
+            .map(|pixel| {
+                let indirect = pixel.tau / (nphotons * PI * pixel.radius * pixel.radius);
+                pixel.ld / passes as f32 + indirect
+            })
+            .collect();
+        Some(colors.into())
+    }
+}
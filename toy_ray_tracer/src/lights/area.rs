@@ -1,6 +1,11 @@
+use std::f32::consts::FRAC_1_PI;
+
 use crate::core::{
-    light::LightTypeFlags, Light, LightType, Point3f, PrimitivePtr, Spectrum, Vec3f,
+    light::LightTypeFlags, vec3, Light, LightEmission, LightSample, LightType, Point3f,
+    PrimitivePtr, Ray, Spectrum, Vec3f, AABB,
 };
+use crate::math::ONB;
+use crate::utils::random;
 
 pub struct AreaLight {
     primitive: PrimitivePtr,
@@ -10,6 +15,15 @@ impl AreaLight {
     pub fn new(primitive: PrimitivePtr) -> Self {
         Self { primitive }
     }
+
+    // normal of the light if it is first hit at `distance` when looking from `point` along `wi`
+    fn hit_normal_at(&self, point: &Point3f, wi: &Vec3f, distance: f32) -> Option<Vec3f> {
+        let prim = self.primitive.as_ref();
+        match prim.intersect(&Ray::new(*point, *wi, 0.0), 0.001, f32::MAX) {
+            Some(si) if (si.t_hit - distance).abs() <= 1e-3 * distance.max(1.0) => Some(si.normal),
+            _ => None,
+        }
+    }
 }
 
 impl Light for AreaLight {
@@ -30,4 +44,68 @@ impl Light for AreaLight {
         let prim = self.primitive.as_ref();
         prim.sample_pdf(point, &wi)
     }
+
+    fn sample_li(&self, point: &Point3f) -> Option<LightSample> {
+        let prim = self.primitive.as_ref();
+        let wi = prim.sample_wi(point).normalize();
+
+        // find the sampled point on the light to get its emission and distance
+        let si = prim.intersect(&Ray::new(*point, wi, 0.0), 0.001, f32::MAX)?;
+        let li = si.material?.emission(&si);
+        let pdf = prim.sample_pdf(point, &wi);
+
+        Some(LightSample {
+            wi,
+            pdf,
+            li,
+            distance: si.t_hit,
+            normal: si.normal,
+        })
+    }
+
+    fn pdf_li(&self, point: &Point3f, wi: &Vec3f, distance: f32) -> f32 {
+        let wi = wi.normalize();
+
+        // only the light actually seen along `wi` could have been sampled
+        match self.hit_normal_at(point, &wi, distance) {
+            Some(_) => self.primitive.sample_pdf(point, &wi),
+            None => 0.0,
+        }
+    }
+
+    fn sample_le(&self, _world_bound: &AABB, time: f32) -> Option<LightEmission> {
+        let prim = self.primitive.as_ref();
+        let si = prim.sample_area();
+        let le = si.material?.emission(&si);
+
+        // lights emit on both sides, pick one and sample a cosine weighted direction
+        let normal = if random::f32() < 0.5 {
+            si.normal
+        } else {
+            -si.normal
+        };
+        let dir = ONB::build_form_w(&normal).local(vec3::random_cosine_direction());
+
+        Some(LightEmission {
+            ray: Ray::new(si.point, dir.normalize(), time),
+            normal: si.normal,
+            le,
+            pdf_pos: 1.0 / prim.area(),
+            pdf_dir: dir.normalize().dot(&normal).abs() * FRAC_1_PI * 0.5,
+        })
+    }
+
+    fn pdf_le(&self, from: &Point3f, point: &Point3f) -> (f32, f32) {
+        let w = point - from;
+        let distance = w.norm();
+        let w = w / distance;
+
+        match self.hit_normal_at(from, &w, distance) {
+            Some(normal) => (
+                1.0 / self.primitive.area(),
+                normal.dot(&w).abs() * FRAC_1_PI * 0.5,
+            ),
+            None => (0.0, 0.0),
+        }
+    }
 }
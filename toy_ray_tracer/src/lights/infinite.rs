@@ -1,6 +1,10 @@
 use std::f32::consts::PI;
 
-use crate::core::{light::LightTypeFlags, vec3, Light, LightType, Point3f, Ray, Spectrum, Vec3f};
+use crate::core::{
+    light::LightTypeFlags, spectral, vec3, Light, LightEmission, LightSample, LightType, Point3f,
+    Ray, Spectrum, Vec3f, AABB,
+};
+use crate::math::ONB;
 
 pub struct EnvironmentLight {
     pub background: Spectrum,
@@ -14,7 +18,7 @@ impl EnvironmentLight {
 
 impl Light for EnvironmentLight {
     fn background_l(&self, _r: &Ray) -> Spectrum {
-        self.background
+        spectral::from_rgb(&self.background)
     }
 
     fn get_flags(&self) -> LightTypeFlags {
@@ -28,4 +32,45 @@ impl Light for EnvironmentLight {
     fn sample_pdf(&self, _point: &Point3f, _wi: &crate::core::Vec3f) -> f32 {
         1.0 / (4.0 * PI)
     }
+
+    fn sample_li(&self, _point: &Point3f) -> Option<LightSample> {
+        Some(LightSample {
+            wi: vec3::random_env_sphere(),
+            pdf: 1.0 / (4.0 * PI),
+            li: spectral::from_rgb(&self.background),
+            distance: f32::INFINITY,
+            normal: Vec3f::zeros(),
+        })
+    }
+
+    fn pdf_li(&self, _point: &Point3f, _wi: &Vec3f, distance: f32) -> f32 {
+        // reachable only when nothing is hit
+        if distance.is_infinite() {
+            1.0 / (4.0 * PI)
+        } else {
+            0.0
+        }
+    }
+
+    fn sample_le(&self, world_bound: &AABB, time: f32) -> Option<LightEmission> {
+        let (center, radius) = world_bound.bounding_sphere();
+        let dir = -vec3::random_env_sphere();
+
+        // parallel rays starting from a disk outside of the scene, facing `dir`
+        let uvw = ONB::build_form_w(&dir);
+        let disk = vec3::random_in_unit_disk() * radius;
+        let origin = center - dir * radius + uvw.u() * disk.x + uvw.v() * disk.y;
+
+        Some(LightEmission {
+            ray: Ray::new(origin, dir, time),
+            normal: dir,
+            le: spectral::from_rgb(&self.background),
+            pdf_pos: 1.0 / (PI * radius * radius),
+            pdf_dir: 1.0 / (4.0 * PI),
+        })
+    }
+
+    fn pdf_le(&self, _from: &Point3f, _point: &Point3f) -> (f32, f32) {
+        (0.0, 0.0)
+    }
 }
@@ -1,7 +1,8 @@
 use crate::utils::random;
 
 use crate::core::{
-    light::LightTypeFlags, Light, LightPtr, LightType, Point3f, Ray, Spectrum, Vec3f,
+    light::LightTypeFlags, Light, LightEmission, LightPtr, LightSample, LightType, Point3f, Ray,
+    Spectrum, Vec3f, AABB,
 };
 
 pub struct LightList {
@@ -42,6 +43,16 @@ impl LightList {
 
         self.lights.push(item.clone());
     }
+
+    // pick one light uniformly, returns it with the probability of picking it
+    pub fn pick(&self) -> Option<(&LightPtr, f32)> {
+        if self.lights.is_empty() {
+            return None;
+        }
+
+        let idx = random::index(self.lights.len());
+        Some((&self.lights[idx], 1.0 / self.lights.len() as f32))
+    }
 }
 
 impl Light for LightList {
@@ -67,7 +78,7 @@ impl Light for LightList {
             return Vec3f::zeros();
         }
 
-        let idx = random::usize(0..self.lights.len());
+        let idx = random::index(self.lights.len());
         let light = &self.lights[idx];
 
         light.sample_wi(point)
@@ -83,4 +94,45 @@ impl Light for LightList {
         pdf /= lights.len() as f32;
         pdf
     }
+
+    fn sample_li(&self, point: &Point3f) -> Option<LightSample> {
+        let (light, pick_pdf) = self.pick()?;
+        let mut sample = light.sample_li(point)?;
+        sample.pdf *= pick_pdf;
+        Some(sample)
+    }
+
+    fn pdf_li(&self, point: &Point3f, wi: &Vec3f, distance: f32) -> f32 {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+
+        let pdf: f32 = self
+            .lights
+            .iter()
+            .map(|light| light.pdf_li(point, wi, distance))
+            .sum();
+        pdf / self.lights.len() as f32
+    }
+
+    fn sample_le(&self, world_bound: &AABB, time: f32) -> Option<LightEmission> {
+        let (light, pick_pdf) = self.pick()?;
+        let mut emission = light.sample_le(world_bound, time)?;
+        emission.pdf_pos *= pick_pdf;
+        Some(emission)
+    }
+
+    // pdf_pos includes the probability of picking the light, pdf_dir does not
+    fn pdf_le(&self, from: &Point3f, point: &Point3f) -> (f32, f32) {
+        if self.lights.is_empty() {
+            return (0.0, 0.0);
+        }
+
+        let (pdf_pos, pdf_dir) = self
+            .lights
+            .iter()
+            .map(|light| light.pdf_le(from, point))
+            .fold((0.0, 0.0), |acc, pdf| (acc.0 + pdf.0, acc.1 + pdf.1));
+        (pdf_pos / self.lights.len() as f32, pdf_dir)
+    }
 }
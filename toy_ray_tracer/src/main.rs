@@ -1,25 +1,39 @@
 mod accelerators;
 mod bxdfs;
+mod compare;
 pub mod core;
+mod denoise;
 mod engine;
+mod filters;
+mod golden;
+mod integrators;
 mod lights;
 mod materials;
 mod math;
+mod media;
 mod primitives;
+mod samplers;
 mod scene_builder;
 mod shapes;
+mod stats;
 mod textures;
 mod utils;
 
 use crate::scene_builder::{load_project_config, AssetsManager, Builder};
-use crate::{engine::Engine, utils::ExecutionTimer};
-use anyhow::Ok;
+use crate::{
+    core::{Film, OutputFormat},
+    engine::Engine,
+    stats::Phase,
+    utils::ExecutionTimer,
+};
+use anyhow::{ensure, Ok};
 use clap::{Args, Parser, Subcommand};
 use log::{debug, info};
-use scene_builder::types::ProjectConfig;
+use scene_builder::types::{IntegratorConfig, ProjectConfig};
 use schemars::schema_for;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 #[derive(Args, Debug)]
 struct RenderCmdArgs {
@@ -35,15 +49,95 @@ struct RenderCmdArgs {
 
     #[clap(long, short = 'o', help = "assets dir", default_value_t = String::from("./assets"))]
     assets_dir: String,
+
+    #[clap(long, help = "checkpoint to continue, e.g. with more samples")]
+    resume: Option<String>,
+
+    #[clap(
+        long,
+        help = "seconds between checkpoints saved to <output_dir>/<name>.ckpt, 0 to disable",
+        default_value_t = 60
+    )]
+    checkpoint_interval: u64,
+
+    #[clap(
+        long,
+        help = "image file to write instead of <output_dir>/<name>.<format>, the extension picks the format (png, exr, pfm, hdr)"
+    )]
+    output: Option<String>,
 }
 
 #[derive(Args, Debug)]
 struct GenerateCmdArgs {}
 
+#[derive(Args, Debug)]
+struct CompareCmdArgs {
+    #[clap(
+        help = "image to measure, png or another 8 bit format (sRGB) or exr, pfm, hdr (linear)"
+    )]
+    image: String,
+
+    #[clap(help = "reference image")]
+    reference: String,
+
+    #[clap(
+        long,
+        help = "false color FLIP error image to write, <image>_diff.png by default"
+    )]
+    diff: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct GoldenCmdArgs {
+    #[clap(long, help = "assets dir", default_value_t = String::from("./assets"))]
+    assets_dir: String,
+
+    #[clap(
+        long,
+        help = "dir of cases.json and the reference images, <assets_dir>/golden by default"
+    )]
+    golden_dir: Option<String>,
+
+    #[clap(long, help = "only the cases whose name contains this")]
+    filter: Option<String>,
+
+    #[clap(
+        long,
+        help = "write the renders as the new references instead of comparing"
+    )]
+    update: bool,
+
+    #[clap(
+        long,
+        help = "dir for the renders and FLIP error images of the cases",
+        default_value_t = String::from("./output/golden")
+    )]
+    report_dir: String,
+}
+
+#[derive(Args, Debug)]
+struct BxdfTestCmdArgs {
+    #[clap(long, help = "only the bxdfs whose name contains this")]
+    filter: Option<String>,
+
+    #[clap(long, help = "samples per bxdf and angle", default_value_t = 1_000_000)]
+    nsamples: usize,
+
+    #[clap(
+        long,
+        help = "dir for the histograms of the sampled directions and the integrated pdfs",
+        default_value_t = String::from("./output/bxdf")
+    )]
+    output_dir: String,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     Render(RenderCmdArgs),
     Generate(GenerateCmdArgs),
+    Compare(CompareCmdArgs),
+    Golden(GoldenCmdArgs),
+    BxdfTest(BxdfTestCmdArgs),
 }
 
 #[derive(Parser)]
@@ -67,6 +161,9 @@ fn main() -> anyhow::Result<()> {
     match cli.command {
         Commands::Render(args) => run_render(args),
         Commands::Generate(args) => run_generate(args),
+        Commands::Compare(args) => run_compare(args),
+        Commands::Golden(args) => run_golden(args),
+        Commands::BxdfTest(args) => run_bxdf_test(args),
     }
 }
 
@@ -76,6 +173,95 @@ fn run_generate(_args: GenerateCmdArgs) -> anyhow::Result<()> {
     Ok(())
 }
 
+fn run_compare(args: CompareCmdArgs) -> anyhow::Result<()> {
+    let image = compare::load_linear(&args.image)?;
+    let reference = compare::load_linear(&args.reference)?;
+    let (metrics, errors) = compare::compare(&image, &reference)?;
+
+    println!("MSE     {:.6e}", metrics.mse);
+    println!("RMSE    {:.6e}", metrics.rmse);
+    println!("relMSE  {:.6e}", metrics.rel_mse);
+    println!("PSNR    {:.3} dB", metrics.psnr);
+    println!("SSIM    {:.5}", metrics.ssim);
+    println!("FLIP    {:.5}", metrics.flip);
+
+    let diff_path = match &args.diff {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let path = Path::new(&args.image);
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            path.with_file_name(format!("{}_diff.png", stem))
+        }
+    };
+    compare::error_image(&errors, image.width(), image.height()).save_to_png(&diff_path)?;
+    info!(
+        "FLIP error image has been written to {}",
+        diff_path.display()
+    );
+
+    Ok(())
+}
+
+fn run_golden(args: GoldenCmdArgs) -> anyhow::Result<()> {
+    let assets_dir = Path::new(&args.assets_dir);
+    let golden_dir = match &args.golden_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => assets_dir.join("golden"),
+    };
+    let harness = golden::GoldenHarness::new(assets_dir, &golden_dir);
+    let harness = if args.update {
+        harness
+    } else {
+        harness.with_report_dir(&args.report_dir)
+    };
+
+    let results = harness.run(args.filter.as_deref(), args.update)?;
+    for result in &results {
+        println!("{}", golden::format_result(result));
+    }
+
+    let failed = results.iter().filter(|r| !r.passed()).count();
+    ensure!(
+        failed == 0,
+        "{} of {} golden cases failed, see {}",
+        failed,
+        results.len(),
+        args.report_dir
+    );
+    Ok(())
+}
+
+fn run_bxdf_test(args: BxdfTestCmdArgs) -> anyhow::Result<()> {
+    let output_dir = Path::new(&args.output_dir);
+    std::fs::create_dir_all(output_dir)?;
+
+    let reports = bxdfs::validation::run(args.filter.as_deref(), args.nsamples);
+    let significance = bxdfs::validation::significance(reports.len());
+    for report in &reports {
+        println!("{}", report.format(significance));
+
+        if let Some(chi_square) = &report.chi_square {
+            let name = format!("{}_{:.2}", report.name, report.cos_wo);
+            chi_square
+                .histogram_image()
+                .save_to_png(output_dir.join(format!("{}_histogram.png", name)))?;
+            chi_square
+                .pdf_image()
+                .save_to_png(output_dir.join(format!("{}_pdf.png", name)))?;
+        }
+    }
+
+    let failed = reports.iter().filter(|r| !r.passed(significance)).count();
+    ensure!(
+        failed == 0,
+        "{} of {} bxdf tests failed, p-values below {:.2e} fail chi-square",
+        failed,
+        reports.len(),
+        significance
+    );
+    Ok(())
+}
+
 fn run_render(args: RenderCmdArgs) -> anyhow::Result<()> {
     debug!("use args={:#?}", args);
 
@@ -92,9 +278,24 @@ fn run_render(args: RenderCmdArgs) -> anyhow::Result<()> {
     let assets_dir = Path::new(&args.assets_dir);
     let assets_manager = Rc::new(AssetsManager::new(assets_dir, project_dir));
 
+    let build_start = Instant::now();
     let project = Builder::new(assets_manager).build_project(&project_config)?;
+    stats::add_time(Phase::SceneBuild, build_start.elapsed());
 
-    let engine = Engine::new();
+    let film = match &args.resume {
+        Some(path) => {
+            ensure!(
+                !matches!(
+                    project_config.integrator,
+                    IntegratorConfig::Sppm {} | IntegratorConfig::Pssmlt { .. }
+                ),
+                "sppm and pssmlt render the whole image at once and can't be resumed"
+            );
+            info!("resume from checkpoint {}", path);
+            Some(Film::load(path)?)
+        }
+        None => None,
+    };
 
     let opt = project.settings();
     let output_dir = Path::new(&opt.output_dir);
@@ -107,7 +308,37 @@ fn run_render(args: RenderCmdArgs) -> anyhow::Result<()> {
         std::fs::create_dir_all(output_dir)?;
     }
 
-    let output_path = output_dir.join(format!("{}.png", project.name()));
+    let (output_path, output_format) = match &args.output {
+        Some(path) => (PathBuf::from(path), OutputFormat::from_path(path)?),
+        None => (
+            output_dir.join(format!(
+                "{}.{}",
+                project.name(),
+                opt.output_format.extension()
+            )),
+            opt.output_format,
+        ),
+    };
+
+    let mut engine = Engine::new();
+    if args.checkpoint_interval > 0 {
+        engine = engine.with_checkpoint(
+            output_dir.join(format!("{}.ckpt", project.name())),
+            Duration::from_secs(args.checkpoint_interval),
+        );
+    }
+    if opt.progressive.is_some() {
+        let (path, format, settings) = (output_path.clone(), output_format, opt.clone());
+        engine = engine.with_preview(move |film| {
+            // written next to the image and renamed, viewers never see a half written file
+            let tmp_path = path.with_extension("preview.tmp");
+            let mut image = film.to_hdr_image();
+            image.transform(&settings.working_space.output_matrix());
+            image.save(&tmp_path, format, &settings)?;
+            std::fs::rename(&tmp_path, &path)?;
+            Ok(())
+        });
+    }
 
     {
         let _timer = ExecutionTimer::new(|start_time| {
@@ -124,8 +355,60 @@ fn run_render(args: RenderCmdArgs) -> anyhow::Result<()> {
             opt.height,
             opt.nsamples
         );
-        let img = engine.render(&project);
-        img.save_to_png(&output_path)?;
+        let render_start = Instant::now();
+        let film = engine.render(&project, film)?;
+        stats::add_time(Phase::Render, render_start.elapsed());
+        let output_matrix = opt.working_space.output_matrix();
+        let mut image = film.to_hdr_image();
+        if let Some(settings) = &opt.denoise {
+            match denoise::denoise_film(&film, settings) {
+                Some(denoised) => {
+                    if settings.keep_noisy {
+                        let noisy_path =
+                            sibling_path(&output_path, "noisy", output_format.extension());
+                        image.transform(&output_matrix);
+                        image.save(&noisy_path, output_format, opt)?;
+                        info!("noisy image has been written to {}", noisy_path.display());
+                    }
+                    image = denoised;
+                }
+                None => log::warn!("the film has no albedo and normals to denoise with"),
+            }
+        }
+        image.transform(&output_matrix);
+        // the features of the denoiser are only written if asked for
+        let mut aovs = film.to_aov_images();
+        aovs.retain(|(aov, _)| opt.aovs.contains(aov));
+        for (aov, aov_image) in aovs.iter_mut() {
+            if aov.is_color() {
+                aov_image.transform(&output_matrix);
+            }
+        }
+
+        // exr keeps the aovs as layers of the image, the other formats get a file per aov
+        if output_format == OutputFormat::Exr {
+            image.save_exr_with_aovs(&output_path, &aovs)?;
+        } else {
+            image.save(&output_path, output_format, opt)?;
+            for (aov, aov_image) in &aovs {
+                let aov_path = sibling_path(&output_path, aov.name(), output_format.extension());
+                aov_image.save_aov(&aov_path, *aov, output_format, opt)?;
+                info!(
+                    "aov {} has been written to {}",
+                    aov.name(),
+                    aov_path.display()
+                );
+            }
+        }
+
+        if matches!(&opt.adaptive, Some(adaptive) if adaptive.heatmap) {
+            let heatmap_path = output_dir.join(format!("{}_samples.png", project.name()));
+            film.to_heatmap().save_to_png(&heatmap_path)?;
+            info!(
+                "sample count heatmap has been written to {}",
+                heatmap_path.display()
+            );
+        }
     }
 
     info!(
@@ -133,5 +416,23 @@ fn run_render(args: RenderCmdArgs) -> anyhow::Result<()> {
         output_path.display().to_string(),
     );
 
+    let report = stats::report();
+    if report.nan_samples + report.inf_samples > 0 {
+        log::warn!(
+            "{} samples were NaN and {} infinite, the integrator or the scene is broken",
+            report.nan_samples,
+            report.inf_samples
+        );
+    }
+    let stats_path = sibling_path(&output_path, "stats", "json");
+    std::fs::write(&stats_path, serde_json::to_string_pretty(&report)?)?;
+    info!("render stats have been written to {}", stats_path.display());
+
     Ok(())
 }
+
+// <stem>_<suffix>.<extension> next to `path`
+fn sibling_path(path: &Path, suffix: &str, extension: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}_{}.{}", stem, suffix, extension))
+}
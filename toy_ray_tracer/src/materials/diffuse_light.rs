@@ -1,4 +1,4 @@
-use crate::core::{Material, Spectrum, TexturePtr};
+use crate::core::{spectral, Material, Spectrum, TexturePtr};
 
 pub struct DiffuseLight {
     emission: TexturePtr<Spectrum>,
@@ -12,7 +12,7 @@ impl DiffuseLight {
 
 impl Material for DiffuseLight {
     fn emission(&self, si: &crate::core::SurfaceInteraction) -> Spectrum {
-        self.emission.evaluate(si)
+        spectral::from_rgb(&self.emission.evaluate(si))
     }
 
     fn compute_bsdf(&self, _si: &crate::core::SurfaceInteraction) -> Option<crate::core::Bsdf> {
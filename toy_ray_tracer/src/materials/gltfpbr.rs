@@ -1,6 +1,6 @@
 use crate::{
     bxdfs::GltfPbrBxdf,
-    core::{Bsdf, Color3, Material, Spectrum, SurfaceInteraction, TexturePtr},
+    core::{spectral, Bsdf, Color3, Material, Spectrum, SurfaceInteraction, TexturePtr},
 };
 
 use super::clamp_roughness;
@@ -33,13 +33,13 @@ impl GltfPbrMaterial {
 
 impl Material for GltfPbrMaterial {
     fn emission(&self, si: &crate::core::SurfaceInteraction) -> Color3 {
-        self.emission.evaluate(si)
+        spectral::from_rgb(&self.emission.evaluate(si))
     }
 
     fn compute_bsdf(&self, si: &SurfaceInteraction) -> Option<Bsdf> {
         let mut bsdf = Bsdf::new(si.normal);
 
-        let base_color = self.base_color.evaluate(si);
+        let base_color = spectral::from_rgb(&self.base_color.evaluate(si));
         let roughness = self.roughness.evaluate(si);
         let metallic = self.metallic.evaluate(si);
 
@@ -49,4 +49,8 @@ impl Material for GltfPbrMaterial {
 
         Some(bsdf)
     }
+
+    fn albedo(&self, si: &SurfaceInteraction) -> Spectrum {
+        spectral::from_rgb(&self.base_color.evaluate(si))
+    }
 }
@@ -1,11 +1,13 @@
 mod diffuse_light;
 mod gltfpbr;
 mod naive;
+mod subsurface;
 mod transparent;
 
 pub use diffuse_light::DiffuseLight;
 pub use gltfpbr::GltfPbrMaterial;
-pub use naive::{Lambertian, Metal, Dielectric};
+pub use naive::{Dielectric, Lambertian, Metal};
+pub use subsurface::SubsurfaceMaterial;
 pub use transparent::Transparent;
 
 // utiltiy functions
@@ -1,6 +1,6 @@
 use crate::{
     bxdfs::{LambertianReflection, NaiveDielectric, NaiveSpecularReflection},
-    core::{Bsdf, Material, Spectrum, SurfaceInteraction, TexturePtr},
+    core::{spectral, vec3, Bsdf, Ior, Material, Spectrum, SurfaceInteraction, TexturePtr},
 };
 
 pub struct Lambertian {
@@ -17,11 +17,15 @@ impl Material for Lambertian {
     fn compute_bsdf(&self, si: &SurfaceInteraction) -> Option<Bsdf> {
         let mut bsdf = Bsdf::new(si.normal);
 
-        let albedo = self.albedo.evaluate(si);
+        let albedo = spectral::from_rgb(&self.albedo.evaluate(si));
         bsdf.set_raw(LambertianReflection::new(albedo));
 
         Some(bsdf)
     }
+
+    fn albedo(&self, si: &SurfaceInteraction) -> Spectrum {
+        spectral::from_rgb(&self.albedo.evaluate(si))
+    }
 }
 
 pub struct Metal {
@@ -40,20 +44,24 @@ impl Material for Metal {
         let mut bsdf = Bsdf::new(si.normal);
 
         bsdf.set_raw(NaiveSpecularReflection::new(
-            self.albedo.evaluate(si),
+            spectral::from_rgb(&self.albedo.evaluate(si)),
             self.fuzz,
         ));
 
         Some(bsdf)
     }
+
+    fn albedo(&self, si: &SurfaceInteraction) -> Spectrum {
+        spectral::from_rgb(&self.albedo.evaluate(si))
+    }
 }
 
 pub struct Dielectric {
-    ir: f32,
+    ir: Ior,
 }
 
 impl Dielectric {
-    pub fn new(ir: f32) -> Self {
+    pub fn new(ir: Ior) -> Self {
         Dielectric { ir }
     }
 }
@@ -62,13 +70,14 @@ impl Material for Dielectric {
     fn compute_bsdf(&self, si: &crate::core::SurfaceInteraction) -> Option<Bsdf> {
         let mut bsdf = Bsdf::new(si.normal);
 
-        let ni_over_nt = if si.front_face {
-            1.0 / self.ir
-        } else {
-            self.ir
-        };
+        let ir = self.ir.eta();
+        let ni_over_nt = if si.front_face { 1.0 / ir } else { ir };
 
         bsdf.set_raw(NaiveDielectric::new(ni_over_nt));
         Some(bsdf)
     }
+
+    fn albedo(&self, _si: &SurfaceInteraction) -> Spectrum {
+        vec3::scalar(1.0)
+    }
 }
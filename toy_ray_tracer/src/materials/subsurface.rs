@@ -0,0 +1,51 @@
+use crate::{
+    bxdfs::NaiveDielectric,
+    core::{spectral, Bsdf, Bssrdf, Material, Spectrum, SurfaceInteraction, TexturePtr},
+};
+
+// skin, wax, marble: a smooth dielectric surface above a scattering medium, see `Bssrdf`
+pub struct SubsurfaceMaterial {
+    albedo: TexturePtr<Spectrum>,
+    mean_free_path: Spectrum,
+    eta: f32,
+    g: f32,
+}
+
+impl SubsurfaceMaterial {
+    pub fn new(albedo: TexturePtr<Spectrum>, mean_free_path: Spectrum, eta: f32, g: f32) -> Self {
+        Self {
+            albedo,
+            mean_free_path,
+            eta,
+            g,
+        }
+    }
+}
+
+impl Material for SubsurfaceMaterial {
+    fn compute_bsdf(&self, si: &SurfaceInteraction) -> Option<Bsdf> {
+        let mut bsdf = Bsdf::new(si.normal);
+
+        let ni_over_nt = if si.front_face {
+            1.0 / self.eta
+        } else {
+            self.eta
+        };
+
+        bsdf.set_raw(NaiveDielectric::new(ni_over_nt));
+        Some(bsdf)
+    }
+
+    fn albedo(&self, si: &SurfaceInteraction) -> Spectrum {
+        spectral::from_rgb(&self.albedo.evaluate(si))
+    }
+
+    fn bssrdf(&self, si: &SurfaceInteraction) -> Option<Bssrdf> {
+        Some(Bssrdf::new(
+            &spectral::from_rgb(&self.albedo.evaluate(si)),
+            &spectral::from_rgb(&self.mean_free_path),
+            self.eta,
+            self.g,
+        ))
+    }
+}
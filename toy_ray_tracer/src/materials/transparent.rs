@@ -1,16 +1,16 @@
 use crate::{
     bxdfs::{DeltaTransparentTransmission, TransparentTransmission},
-    core::{Bsdf, Material, Spectrum, TexturePtr},
+    core::{spectral, Bsdf, Ior, Material, Spectrum, TexturePtr},
 };
 
 pub struct Transparent {
-    pub eta: f32,
+    pub eta: Ior,
     pub roughness: TexturePtr<f32>,
     pub albedo: TexturePtr<Spectrum>,
 }
 
 impl Transparent {
-    pub fn new(eta: f32, roughness: TexturePtr<f32>, albedo: TexturePtr<Spectrum>) -> Self {
+    pub fn new(eta: Ior, roughness: TexturePtr<f32>, albedo: TexturePtr<Spectrum>) -> Self {
         Self {
             eta,
             roughness,
@@ -23,15 +23,20 @@ impl Material for Transparent {
     fn compute_bsdf(&self, si: &crate::core::SurfaceInteraction) -> Option<crate::core::Bsdf> {
         let mut bsdf = Bsdf::new(si.normal);
 
-        let albedo = self.albedo.evaluate(si);
+        let albedo = spectral::from_rgb(&self.albedo.evaluate(si));
         let roughness = self.roughness.evaluate(si);
+        let eta = self.eta.eta();
 
         if roughness < f32::EPSILON {
-            bsdf.set_raw(DeltaTransparentTransmission::new(self.eta, albedo));
+            bsdf.set_raw(DeltaTransparentTransmission::new(eta, albedo));
         } else {
-            bsdf.set_raw(TransparentTransmission::new(self.eta, roughness, albedo));
+            bsdf.set_raw(TransparentTransmission::new(eta, roughness, albedo));
         }
 
         Some(bsdf)
     }
+
+    fn albedo(&self, si: &crate::core::SurfaceInteraction) -> Spectrum {
+        spectral::from_rgb(&self.albedo.evaluate(si))
+    }
 }
@@ -0,0 +1,157 @@
+use std::path::Path;
+
+use anyhow::{ensure, Context, Result};
+
+use crate::core::Point3f;
+
+use super::DensityField;
+
+// Density values on a regular voxel grid, stored in a small binary file (all little endian):
+//
+//   magic   4 bytes   b"DGRD"
+//   nx      u32
+//   ny      u32
+//   nz      u32
+//   data    nx * ny * nz f32, x varies fastest, then y, then z
+//
+// The grid spans the bounds of its medium, voxel centers sit at ((i + 0.5) / nx, ...)
+pub struct DensityGrid {
+    nx: usize,
+    ny: usize,
+    nz: usize,
+    data: Vec<f32>,
+    max: f32,
+}
+
+const MAGIC: &[u8; 4] = b"DGRD";
+const HEADER_SIZE: usize = 16;
+
+impl DensityGrid {
+    pub fn new(nx: usize, ny: usize, nz: usize, data: Vec<f32>) -> Result<Self> {
+        ensure!(nx > 0 && ny > 0 && nz > 0, "density grid must not be empty");
+        ensure!(
+            data.len() == nx * ny * nz,
+            "density grid expects {} values, got {}",
+            nx * ny * nz,
+            data.len()
+        );
+        ensure!(
+            data.iter().all(|d| d.is_finite() && *d >= 0.0),
+            "density grid values must be finite and not negative"
+        );
+
+        let max = data.iter().fold(0.0f32, |m, d| m.max(*d));
+        Ok(Self {
+            nx,
+            ny,
+            nz,
+            data,
+            max,
+        })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let bytes = std::fs::read(path).context("failed to load density grid")?;
+        Self::parse(&bytes)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        ensure!(
+            bytes.len() >= HEADER_SIZE && &bytes[0..4] == MAGIC,
+            "not a density grid file"
+        );
+
+        let read_u32 = |offset: usize| {
+            u32::from_le_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ]) as usize
+        };
+        let (nx, ny, nz) = (read_u32(4), read_u32(8), read_u32(12));
+
+        let body = &bytes[HEADER_SIZE..];
+        let size = nx
+            .checked_mul(ny)
+            .and_then(|n| n.checked_mul(nz))
+            .and_then(|n| n.checked_mul(4));
+        ensure!(
+            size == Some(body.len()),
+            "density grid of {}x{}x{} has {} bytes of data",
+            nx,
+            ny,
+            nz,
+            body.len()
+        );
+        let data = body
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+
+        Self::new(nx, ny, nz, data)
+    }
+
+    // voxel value, the grid is extended by its border voxels
+    fn voxel(&self, x: i64, y: i64, z: i64) -> f32 {
+        let x = x.clamp(0, self.nx as i64 - 1) as usize;
+        let y = y.clamp(0, self.ny as i64 - 1) as usize;
+        let z = z.clamp(0, self.nz as i64 - 1) as usize;
+        self.data[x + self.nx * (y + self.ny * z)]
+    }
+}
+
+impl DensityField for DensityGrid {
+    // trilinear interpolation between the voxel centers
+    fn density(&self, p: &Point3f) -> f32 {
+        let g = Point3f::new(
+            p.x * self.nx as f32 - 0.5,
+            p.y * self.ny as f32 - 0.5,
+            p.z * self.nz as f32 - 0.5,
+        );
+        let (x, y, z) = (g.x.floor(), g.y.floor(), g.z.floor());
+        let (dx, dy, dz) = (g.x - x, g.y - y, g.z - z);
+        let (x, y, z) = (x as i64, y as i64, z as i64);
+
+        let lerp = |t: f32, a: f32, b: f32| (1.0 - t) * a + t * b;
+        let d00 = lerp(dx, self.voxel(x, y, z), self.voxel(x + 1, y, z));
+        let d10 = lerp(dx, self.voxel(x, y + 1, z), self.voxel(x + 1, y + 1, z));
+        let d01 = lerp(dx, self.voxel(x, y, z + 1), self.voxel(x + 1, y, z + 1));
+        let d11 = lerp(
+            dx,
+            self.voxel(x, y + 1, z + 1),
+            self.voxel(x + 1, y + 1, z + 1),
+        );
+        lerp(dz, lerp(dy, d00, d10), lerp(dy, d01, d11))
+    }
+
+    fn max_density(&self) -> f32 {
+        self.max
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DensityField, DensityGrid};
+    use crate::core::Point3f;
+
+    #[test]
+    fn test_parse_grid() {
+        let mut bytes = b"DGRD".to_vec();
+        for n in [2u32, 1, 1] {
+            bytes.extend_from_slice(&n.to_le_bytes());
+        }
+        for d in [0.0f32, 2.0] {
+            bytes.extend_from_slice(&d.to_le_bytes());
+        }
+        let grid = DensityGrid::parse(&bytes).unwrap();
+        assert_eq!(grid.max_density(), 2.0);
+
+        // halfway between the two voxel centers
+        let d = grid.density(&Point3f::new(0.5, 0.5, 0.5));
+        assert!((d - 1.0).abs() < 1e-6);
+
+        assert!(DensityGrid::parse(b"DGRD").is_err());
+        assert!(DensityGrid::new(2, 2, 1, vec![1.0; 3]).is_err());
+    }
+}
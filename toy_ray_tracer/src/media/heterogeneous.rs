@@ -0,0 +1,136 @@
+use crate::core::{
+    spectral, vec3, HenyeyGreenstein, Medium, MediumSample, Point3f, Ray, Spectrum, Transform, AABB,
+};
+use crate::utils::random;
+
+// spatially varying density, looked up with points normalized to [0, 1]^3 of the medium bounds
+pub trait DensityField {
+    fn density(&self, p: &Point3f) -> f32;
+
+    // upper bound of `density`, the majorant for delta and ratio tracking
+    fn max_density(&self) -> f32;
+}
+
+pub type DensityFieldPtr = Box<dyn DensityField + Sync + Send>;
+
+// medium whose extinction is `sigma_t * density(p)` inside `bounds` and zero elsewhere,
+// scattering `albedo` of the extinguished light. Distances are sampled with delta tracking
+// and transmittance is estimated with ratio tracking against the maximum density
+pub struct HeterogeneousMedium {
+    density: DensityFieldPtr,
+    // bounds in medium space
+    bounds: AABB,
+    world_to_medium: Transform,
+    sigma_t: f32,
+    albedo: Spectrum,
+    phase: HenyeyGreenstein,
+    inv_max_sigma_t: f32,
+}
+
+impl HeterogeneousMedium {
+    pub fn new(
+        density: DensityFieldPtr,
+        bounds: AABB,
+        medium_to_world: Transform,
+        sigma_t: f32,
+        albedo: Spectrum,
+        g: f32,
+    ) -> Self {
+        let max_sigma_t = sigma_t * density.max_density();
+        Self {
+            density,
+            bounds,
+            world_to_medium: medium_to_world.inverse(),
+            sigma_t,
+            albedo,
+            phase: HenyeyGreenstein::new(g),
+            inv_max_sigma_t: if max_sigma_t > 0.0 {
+                1.0 / max_sigma_t
+            } else {
+                0.0
+            },
+        }
+    }
+
+    fn density(&self, p: &Point3f) -> f32 {
+        let extent = self.bounds.max - self.bounds.min;
+        let p = (p - self.bounds.min).component_div(&extent);
+        self.density.density(&p)
+    }
+
+    // `ray` in medium space with a world space unit length direction, so that ray
+    // parameters are world distances, and the range of it within the bounds
+    fn clip(&self, ray: &Ray, t_max: f32) -> Option<(Ray, f32, f32, f32)> {
+        let length = ray.direction().norm();
+        if self.inv_max_sigma_t == 0.0 || !(length > 0.0 && length.is_finite()) {
+            return None;
+        }
+
+        let ray = Ray::new(ray.origin(), ray.direction() / length, ray.time());
+        let ray = self.world_to_medium.transform_ray(&ray);
+        let (t_min, t_max) = self
+            .bounds
+            .hit_range(&ray, 0.0, (t_max * length).min(f32::MAX))?;
+        Some((ray, length, t_min, t_max))
+    }
+}
+
+impl Medium for HeterogeneousMedium {
+    // ratio tracking
+    fn tr(&self, ray: &Ray, t_max: f32) -> Spectrum {
+        let (ray, _, t_min, t_max) = match self.clip(ray, t_max) {
+            Some(clipped) => clipped,
+            None => return vec3::scalar(1.0),
+        };
+
+        let mut tr = 1.0;
+        let mut t = t_min;
+        loop {
+            t -= (1.0 - random::f32()).ln() * self.inv_max_sigma_t;
+            if t >= t_max {
+                break;
+            }
+            let density = self.density(&ray.point_at_parameter(t)) * self.sigma_t;
+            tr *= 1.0 - (density * self.inv_max_sigma_t).clamp(0.0, 1.0);
+
+            // russian roulette once little light is left
+            if tr < 0.1 {
+                let q = 1.0 - tr;
+                if random::f32() < q {
+                    return Spectrum::zeros();
+                }
+                tr /= 1.0 - q;
+            }
+        }
+        vec3::scalar(tr)
+    }
+
+    // delta tracking
+    fn sample(&self, ray: &Ray, t_max: f32) -> MediumSample {
+        let passed = MediumSample {
+            t: None,
+            weight: vec3::scalar(1.0),
+            phase: self.phase,
+        };
+        let (medium_ray, length, t_min, t_max) = match self.clip(ray, t_max) {
+            Some(clipped) => clipped,
+            None => return passed,
+        };
+
+        let mut t = t_min;
+        loop {
+            t -= (1.0 - random::f32()).ln() * self.inv_max_sigma_t;
+            if t >= t_max {
+                return passed;
+            }
+            let density = self.density(&medium_ray.point_at_parameter(t)) * self.sigma_t;
+            if density * self.inv_max_sigma_t > random::f32() {
+                return MediumSample {
+                    t: Some(t / length),
+                    weight: spectral::from_rgb(&self.albedo),
+                    phase: self.phase,
+                };
+            }
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use crate::core::{
+    beer_lambert, sample_homogeneous, spectral, vec3, HenyeyGreenstein, Medium, MediumSample, Ray,
+    Spectrum,
+};
+
+// medium with constant absorption and scattering coefficients, in units of 1 / distance
+pub struct HomogeneousMedium {
+    sigma_a: Spectrum,
+    sigma_s: Spectrum,
+    phase: HenyeyGreenstein,
+}
+
+impl HomogeneousMedium {
+    pub fn new(sigma_a: Spectrum, sigma_s: Spectrum, g: f32) -> Self {
+        Self {
+            sigma_a,
+            sigma_s,
+            phase: HenyeyGreenstein::new(g),
+        }
+    }
+
+    // scattering and extinction coefficients at the wavelengths being traced
+    fn coefficients(&self) -> (Spectrum, Spectrum) {
+        let sigma_s = spectral::from_rgb(&self.sigma_s);
+        (sigma_s, spectral::from_rgb(&self.sigma_a) + sigma_s)
+    }
+}
+
+impl Medium for HomogeneousMedium {
+    fn tr(&self, ray: &Ray, t_max: f32) -> Spectrum {
+        let distance = (t_max * ray.direction().norm()).min(f32::MAX);
+        beer_lambert(&self.coefficients().1, distance)
+    }
+
+    fn sample(&self, ray: &Ray, t_max: f32) -> MediumSample {
+        let channels = vec3::scalar(1.0);
+        let (sigma_s, sigma_t) = self.coefficients();
+        let (t, weight) = sample_homogeneous(&sigma_s, &sigma_t, &channels, ray, t_max);
+        MediumSample {
+            t,
+            weight,
+            phase: self.phase,
+        }
+    }
+}
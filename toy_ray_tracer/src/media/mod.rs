@@ -0,0 +1,9 @@
+mod grid;
+mod heterogeneous;
+mod homogeneous;
+mod noise;
+
+pub use grid::DensityGrid;
+pub use heterogeneous::{DensityField, DensityFieldPtr, HeterogeneousMedium};
+pub use homogeneous::HomogeneousMedium;
+pub use noise::NoiseDensity;
@@ -0,0 +1,117 @@
+use crate::core::Point3f;
+
+use super::DensityField;
+
+// improved Perlin noise with a permutation table shuffled by `seed`
+pub struct PerlinNoise {
+    perm: Vec<u8>,
+}
+
+impl PerlinNoise {
+    pub fn new(seed: u64) -> Self {
+        let rng = fastrand::Rng::with_seed(seed);
+        let mut perm: Vec<u8> = (0..=255).collect();
+        rng.shuffle(&mut perm);
+        perm.extend_from_within(..);
+        Self { perm }
+    }
+
+    // smooth noise in about [-1, 1], zero at integer lattice points
+    pub fn noise(&self, p: &Point3f) -> f32 {
+        let (x, y, z) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (dx, dy, dz) = (p.x - x, p.y - y, p.z - z);
+        let (x, y, z) = (
+            (x as i64 & 255) as usize,
+            (y as i64 & 255) as usize,
+            (z as i64 & 255) as usize,
+        );
+
+        let perm = &self.perm;
+        let hash = |i: usize, j: usize, k: usize| {
+            perm[perm[perm[x + i] as usize + y + j] as usize + z + k]
+        };
+        let grad = |i: usize, j: usize, k: usize| {
+            gradient(hash(i, j, k), dx - i as f32, dy - j as f32, dz - k as f32)
+        };
+
+        let (u, v, w) = (fade(dx), fade(dy), fade(dz));
+        let lerp = |t: f32, a: f32, b: f32| (1.0 - t) * a + t * b;
+        let x00 = lerp(u, grad(0, 0, 0), grad(1, 0, 0));
+        let x10 = lerp(u, grad(0, 1, 0), grad(1, 1, 0));
+        let x01 = lerp(u, grad(0, 0, 1), grad(1, 0, 1));
+        let x11 = lerp(u, grad(0, 1, 1), grad(1, 1, 1));
+        lerp(w, lerp(v, x00, x10), lerp(v, x01, x11))
+    }
+
+    // fractal sum of `octaves` noise layers, each one twice the frequency and half the amplitude
+    pub fn fbm(&self, p: &Point3f, octaves: u32) -> f32 {
+        let mut sum = 0.0;
+        let mut amplitude = 1.0;
+        let mut total = 0.0;
+        let mut p = *p;
+        for _ in 0..octaves.max(1) {
+            sum += amplitude * self.noise(&p);
+            total += amplitude;
+            amplitude *= 0.5;
+            p *= 2.0;
+        }
+        sum / total
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+// dot product with one of the 12 cube edge directions
+fn gradient(hash: u8, x: f32, y: f32, z: f32) -> f32 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+// procedural cloud density in [0, 1]: fbm noise remapped so that values below `cutoff`
+// are empty space
+pub struct NoiseDensity {
+    noise: PerlinNoise,
+    // noise cells across the medium bounds
+    frequency: f32,
+    octaves: u32,
+    cutoff: f32,
+    // fade the noise out towards the ellipsoid inscribed in the bounds
+    ellipsoid: bool,
+}
+
+impl NoiseDensity {
+    pub fn new(seed: u64, frequency: f32, octaves: u32, cutoff: f32, ellipsoid: bool) -> Self {
+        Self {
+            noise: PerlinNoise::new(seed),
+            frequency,
+            octaves,
+            cutoff: cutoff.clamp(0.0, 0.99),
+            ellipsoid,
+        }
+    }
+}
+
+impl DensityField for NoiseDensity {
+    fn density(&self, p: &Point3f) -> f32 {
+        let mut n = self.noise.fbm(&(p * self.frequency), self.octaves) * 0.5 + 0.5;
+        if self.ellipsoid {
+            let r = (p * 2.0 - Point3f::new(1.0, 1.0, 1.0)).norm_squared();
+            n *= (1.0 - r).max(0.0);
+        }
+        ((n - self.cutoff) / (1.0 - self.cutoff)).clamp(0.0, 1.0)
+    }
+
+    fn max_density(&self) -> f32 {
+        1.0
+    }
+}
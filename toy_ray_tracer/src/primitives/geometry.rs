@@ -1,28 +1,63 @@
+use std::collections::HashMap;
+
 use crate::core::{
-    MaterialPtr, Point3f, Primitive, Ray, ShapePtr, SurfaceInteraction, Vec3f, AABB,
+    Material, MaterialPtr, MediumInterface, Point3f, Primitive, PrimitiveIds, Ray, ShapePtr,
+    SurfaceInteraction, Vec3f, AABB,
 };
 
 pub struct GeometricPrimitive {
     pub shape: ShapePtr,
-    pub material: MaterialPtr,
+    // None if the shape is only the boundary of a medium
+    pub material: Option<MaterialPtr>,
+    pub medium_interface: Option<MediumInterface>,
+    pub ids: PrimitiveIds,
 }
 
 impl GeometricPrimitive {
     pub fn new(shape: ShapePtr, material: MaterialPtr) -> Self {
-        Self { shape, material }
+        Self {
+            shape,
+            material: Some(material),
+            medium_interface: None,
+            ids: PrimitiveIds::default(),
+        }
+    }
+
+    pub fn new_with_medium(
+        shape: ShapePtr,
+        material: Option<MaterialPtr>,
+        medium_interface: Option<MediumInterface>,
+    ) -> Self {
+        Self {
+            shape,
+            material,
+            medium_interface,
+            ids: PrimitiveIds::default(),
+        }
+    }
+
+    pub fn with_ids(mut self, ids: PrimitiveIds) -> Self {
+        self.ids = ids;
+        self
+    }
+
+    fn attach<'a>(&'a self, si: &mut SurfaceInteraction<'a>) {
+        si.material = self.material.as_ref().map(|m| m.as_ref() as &dyn Material);
+        si.medium_interface = self.medium_interface.as_ref();
+        si.ids = self.ids;
     }
 }
 
 impl Primitive for GeometricPrimitive {
     fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<SurfaceInteraction> {
-        let si = self.shape.intersect(&ray, t_min, t_max);
-
-        let si = si.map(|mut si| {
-            si.material = Some(self.material.as_ref());
+        self.shape.intersect(ray, t_min, t_max).map(|mut si| {
+            self.attach(&mut si);
             si
-        });
+        })
+    }
 
-        si
+    fn intersect_p(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        self.shape.intersect_p(ray, t_min, t_max)
     }
 
     fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
@@ -36,4 +71,39 @@ impl Primitive for GeometricPrimitive {
     fn sample_wi(&self, point: &Vec3f) -> Vec3f {
         self.shape.sample_wi(&point)
     }
+
+    fn area(&self) -> f32 {
+        self.shape.area()
+    }
+
+    fn sample_area(&self) -> SurfaceInteraction<'_> {
+        let mut si = self.shape.sample_area();
+        self.attach(&mut si);
+        si
+    }
+}
+
+// hands out the ids of the primitives in the order they are built, primitives with equal
+// material keys, e.g. the same material config, share the material id
+#[derive(Default)]
+pub struct IdAllocator {
+    primitives: u32,
+    materials: HashMap<String, u32>,
+}
+
+impl IdAllocator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // `material` is None for primitives without material
+    pub fn next(&mut self, material: Option<String>) -> PrimitiveIds {
+        self.primitives += 1;
+        let next = self.materials.len() as u32 + 1;
+        let material = material.map_or(0, |key| *self.materials.entry(key).or_insert(next));
+        PrimitiveIds {
+            primitive: self.primitives,
+            material,
+        }
+    }
 }
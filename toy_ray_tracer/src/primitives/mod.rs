@@ -2,6 +2,6 @@ mod geometry;
 mod transform;
 mod primitive_list;
 
-pub use geometry::GeometricPrimitive;
+pub use geometry::{GeometricPrimitive, IdAllocator};
 pub use transform::FlipFacePrimitive;
 pub use primitive_list::PrimitiveList;
\ No newline at end of file
@@ -2,8 +2,9 @@ use std::sync::Arc;
 
 use derive_new::new;
 
-use crate::core::{Primitive, PrimitiveContainer, PrimitivePtr, SurfaceInteraction};
+use crate::core::{sample, Primitive, PrimitiveContainer, PrimitivePtr, SurfaceInteraction};
 use crate::core::{Ray, AABB};
+use crate::stats::{self, Counter};
 use crate::utils::random;
 
 #[derive(new)]
@@ -39,6 +40,13 @@ impl PrimitiveList {
         hit_anything
     }
 
+    #[inline]
+    fn intersect_p_impl(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        self.items
+            .iter()
+            .any(|item| item.intersect_p(ray, t_min, t_max))
+    }
+
     #[inline]
     fn bounding_box_impl(&self, t0: f32, t1: f32) -> Option<AABB> {
         let bbox = self.items.iter().fold(None, |acc, item| {
@@ -53,6 +61,10 @@ impl Primitive for PrimitiveList {
         self.intersect_impl(ray, t_min, t_max)
     }
 
+    fn intersect_p(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        self.intersect_p_impl(ray, t_min, t_max)
+    }
+
     fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
         self.bounding_box_impl(t0, t1)
     }
@@ -70,16 +82,37 @@ impl Primitive for PrimitiveList {
     }
 
     fn sample_wi(&self, origin: &crate::core::Vec3f) -> crate::core::Vec3f {
-        let idx = random::usize(0..self.items.len());
+        let idx = random::index(self.items.len());
         return self.items[idx].sample_wi(origin);
     }
+
+    fn area(&self) -> f32 {
+        self.items.iter().map(|item| item.area()).sum()
+    }
+
+    fn sample_area(&self) -> SurfaceInteraction<'_> {
+        let idx = sample::sample_by_area(&self.items, |item| item.area());
+        self.items[idx].sample_area()
+    }
 }
 
 impl PrimitiveContainer for PrimitiveList {
     fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<SurfaceInteraction> {
+        stats::add(Counter::PrimitiveTests, self.items.len() as u64);
         self.intersect_impl(ray, t_min, t_max)
     }
 
+    fn intersect_p(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        // stops at the first hit
+        let hit = self
+            .items
+            .iter()
+            .position(|item| item.intersect_p(ray, t_min, t_max));
+        let tested = hit.map_or(self.items.len(), |idx| idx + 1);
+        stats::add(Counter::PrimitiveTests, tested as u64);
+        hit.is_some()
+    }
+
     fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
         self.bounding_box_impl(t0, t1)
     }
@@ -26,6 +26,11 @@ impl Primitive for FlipFacePrimitive {
         rec
     }
 
+    // flipping the normal does not change occlusion
+    fn intersect_p(&self, ray: &crate::core::Ray, t_min: f32, t_max: f32) -> bool {
+        self.primitive.intersect_p(ray, t_min, t_max)
+    }
+
     fn bounding_box(&self, t0: f32, t1: f32) -> Option<crate::core::AABB> {
         self.primitive.bounding_box(t0, t1)
     }
@@ -37,4 +42,14 @@ impl Primitive for FlipFacePrimitive {
     fn sample_wi(&self, origin: &crate::core::Vec3f) -> crate::core::Vec3f {
         self.primitive.sample_wi(origin)
     }
+
+    fn area(&self) -> f32 {
+        self.primitive.area()
+    }
+
+    fn sample_area(&self) -> crate::core::SurfaceInteraction<'_> {
+        let mut si = self.primitive.sample_area();
+        si.flip_normal();
+        si
+    }
 }
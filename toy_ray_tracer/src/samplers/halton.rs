@@ -0,0 +1,74 @@
+use crate::{core::PixelSampler, utils::random};
+
+use super::{hash, permute, to_unit, ONE_MINUS_EPSILON};
+
+// bases of the dimensions, the ones past them are drawn at random
+const PRIMES: [u64; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+// The Halton sequence, dimension d is the radical inverse of the sample index in the d-th
+// prime base. The digits are Owen scrambled per pixel and dimension: neighbouring pixels don't
+// repeat the same pattern, and the few first samples of the large bases don't bunch up
+pub struct HaltonSampler {
+    seed: u64,
+    pixel: u64,
+    index: u64,
+    dim: u64,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel: 0,
+            index: 0,
+            dim: 0,
+        }
+    }
+}
+
+// the digits of `a` in `base` mirrored at the decimal point, each digit permuted depending on
+// the digits before it. Leading zero digits are permuted too, up to the precision of f32
+fn scrambled_radical_inverse(base: u64, mut a: u64, h: u64) -> f32 {
+    let inv_base = 1.0 / base as f64;
+    let mut reversed = 0u64;
+    let mut inv_base_n = 1.0;
+    while inv_base_n > 1e-8 {
+        let next = a / base;
+        let digit = (a - next * base) as u32;
+        let digit = permute(digit, base as u32, hash(&[h, reversed]) as u32);
+        reversed = reversed * base + digit as u64;
+        inv_base_n *= inv_base;
+        a = next;
+    }
+    ((reversed as f64 * inv_base_n) as f32).min(ONE_MINUS_EPSILON)
+}
+
+impl PixelSampler for HaltonSampler {
+    fn start_sample(&mut self, pixel: u64, index: u64) {
+        // numbers drawn from fastrand directly are repeatable too
+        random::seed_stream(self.seed, pixel, index);
+        self.pixel = pixel;
+        self.index = index;
+        self.dim = 0;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let dim = self.dim;
+        self.dim += 1;
+        let h = hash(&[self.seed, self.pixel, dim]);
+        match PRIMES.get(dim as usize) {
+            Some(base) => scrambled_radical_inverse(*base, self.index, h),
+            None => to_unit(hash(&[h, self.index]) as u32),
+        }
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let x = self.get_1d();
+        (x, self.get_1d())
+    }
+}
@@ -0,0 +1,26 @@
+use crate::{core::PixelSampler, utils::random};
+
+// plain random numbers, every sample draws from its own stream
+pub struct IndependentSampler {
+    seed: u64,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+}
+
+impl PixelSampler for IndependentSampler {
+    fn start_sample(&mut self, pixel: u64, index: u64) {
+        random::seed_stream(self.seed, pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        fastrand::f32()
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (fastrand::f32(), fastrand::f32())
+    }
+}
@@ -0,0 +1,120 @@
+mod halton;
+mod independent;
+mod sobol;
+mod stratified;
+
+pub use halton::HaltonSampler;
+pub use independent::IndependentSampler;
+pub use sobol::SobolSampler;
+pub use stratified::StratifiedSampler;
+
+use crate::utils::random;
+
+// largest f32 below 1
+const ONE_MINUS_EPSILON: f32 = 1.0 - f32::EPSILON / 2.0;
+
+// unrelated bits for every combination of values, e.g. a seed, pixel and dimension
+fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0, |h, v| random::mix(h ^ v))
+}
+
+// [0, 1) from the high bits
+fn to_unit(bits: u32) -> f32 {
+    ((bits >> 8) as f32 / (1u32 << 24) as f32).min(ONE_MINUS_EPSILON)
+}
+
+// i-th element of a random permutation of 0..n chosen by `p`, Kensler 2013,
+// "Correlated Multi-Jittered Sampling"
+fn permute(mut i: u32, n: u32, p: u32) -> u32 {
+    let mut w = n - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    // cycle walking, values outside of 0..n are permuted again
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < n {
+            return (i + p) % n;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{permute, HaltonSampler, SobolSampler, StratifiedSampler};
+    use crate::core::PixelSampler;
+
+    #[test]
+    fn test_permute() {
+        for n in [1, 5, 16, 100] {
+            let mut seen = vec![false; n as usize];
+            for i in 0..n {
+                seen[permute(i, n, 0x1234_5678) as usize] = true;
+            }
+            assert!(seen.iter().all(|s| *s));
+        }
+    }
+
+    // every 1 / n stratum holds one of n samples, and every cell of a `nx` by `ny` grid in 2D
+    // at most one
+    fn assert_stratified_grid(sampler: &mut dyn PixelSampler, n: u64, nx: usize, ny: usize) {
+        for dim in 0..3 {
+            let mut strata = vec![0; n as usize];
+            let mut cells = vec![0; nx * ny];
+            for index in 0..n {
+                sampler.start_sample(7, index);
+                for _ in 0..dim {
+                    sampler.get_1d();
+                }
+                let u = sampler.get_1d();
+                let (x, y) = sampler.get_2d();
+                assert!([u, x, y].iter().all(|v| (0.0..1.0).contains(v)));
+                strata[(u * n as f32) as usize] += 1;
+                cells[(y * ny as f32) as usize * nx + (x * nx as f32) as usize] += 1;
+            }
+            assert!(strata.iter().all(|s| *s == 1), "dimension {}", dim);
+            assert!(cells.iter().all(|c| *c <= 1), "dimension {}", dim);
+        }
+    }
+
+    fn assert_stratified(sampler: &mut dyn PixelSampler, n: u64) {
+        let grid = (n as f32).sqrt() as usize;
+        assert_stratified_grid(sampler, n, grid, grid);
+    }
+
+    #[test]
+    fn test_samplers_are_stratified() {
+        assert_stratified(&mut StratifiedSampler::new(3, 16), 16);
+        // 10 samples on a 4 by 3 grid, 12 on a 4 by 3 one
+        assert_stratified_grid(&mut StratifiedSampler::new(3, 10), 10, 4, 3);
+        assert_stratified_grid(&mut StratifiedSampler::new(3, 12), 12, 4, 3);
+        assert_stratified(&mut SobolSampler::new(3), 16);
+        // the first dimension has base 2
+        let mut halton = HaltonSampler::new(3);
+        let mut strata = [0; 8];
+        for index in 0..8 {
+            halton.start_sample(7, index);
+            strata[(halton.get_1d() * 8.0) as usize] += 1;
+        }
+        assert_eq!(strata, [1; 8]);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{core::PixelSampler, utils::random};
+
+use super::{hash, to_unit};
+
+// Owen scrambled Sobol points, Burley 2020, "Practical Hash-based Owen Scrambling". Every
+// dimension pair draws the first two Sobol dimensions, scrambled and shuffled by its own
+// seed, so that any number of dimensions is well distributed within the pairs
+pub struct SobolSampler {
+    seed: u64,
+    pixel: u64,
+    index: u64,
+    dim: u64,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel: 0,
+            index: 0,
+            dim: 0,
+        }
+    }
+
+    // the two coordinates of the current sample for the dimension pair starting at `dim`
+    fn point(&mut self, dim: u64) -> (u32, u32) {
+        let h = hash(&[self.seed, self.pixel, dim]);
+        let h2 = hash(&[h]);
+        let index = nested_uniform_scramble(self.index as u32, h2 as u32);
+        let x = nested_uniform_scramble(index.reverse_bits(), h as u32);
+        let y = nested_uniform_scramble(sobol_1(index), (h >> 32) as u32);
+        (x, y)
+    }
+}
+
+// the second Sobol dimension, its generator matrix is Pascal's triangle mod 2
+fn sobol_1(mut index: u32) -> u32 {
+    let mut v = 1u32 << 31;
+    let mut result = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+    result
+}
+
+// a random permutation of the bits of `x` that only depends on the bits below each bit
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+// Owen scrambling of the digits of `x` read from the highest bit
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+impl PixelSampler for SobolSampler {
+    fn start_sample(&mut self, pixel: u64, index: u64) {
+        // numbers drawn from fastrand directly are repeatable too
+        random::seed_stream(self.seed, pixel, index);
+        self.pixel = pixel;
+        self.index = index;
+        self.dim = 0;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let dim = self.dim;
+        self.dim += 1;
+        to_unit(self.point(dim).0)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let dim = self.dim;
+        self.dim += 1;
+        let (x, y) = self.point(dim);
+        (to_unit(x), to_unit(y))
+    }
+}
@@ -0,0 +1,76 @@
+use crate::{core::PixelSampler, utils::random};
+
+use super::{hash, permute, to_unit, ONE_MINUS_EPSILON};
+
+// Jittered strata over the `nsamples` samples of a pixel: in every dimension each sample gets
+// its own stratum of width 1 / nsamples, shuffled differently per dimension so that the
+// dimensions don't correlate. Pairs of dimensions are stratified on a 2D grid of at least
+// `nsamples` cells, each sample in its own one, some cells stay empty if `nsamples` is not a
+// square. Samples past `nsamples` start another round of strata
+pub struct StratifiedSampler {
+    seed: u64,
+    nsamples: u32,
+    pixel: u64,
+    index: u64,
+    dim: u64,
+}
+
+impl StratifiedSampler {
+    pub fn new(seed: u64, nsamples: u32) -> Self {
+        Self {
+            seed,
+            nsamples: nsamples.max(1),
+            pixel: 0,
+            index: 0,
+            dim: 0,
+        }
+    }
+
+    // the stratum of the current sample among `strata` ones, no fewer than `nsamples`, and a
+    // hash for its jitter
+    fn stratum(&mut self, strata: u32) -> (u32, u64) {
+        let n = self.nsamples as u64;
+        let round = self.index / n;
+        let h = hash(&[self.seed, self.pixel, self.dim, round]);
+        self.dim += 1;
+        let i = (self.index % n) as u32;
+        (permute(i, strata, h as u32), hash(&[h, i as u64]))
+    }
+
+    // the smallest grid with a cell for every sample, as square as possible
+    fn grid(&self) -> (u32, u32) {
+        let n = self.nsamples;
+        let mut nx = (n as f32).sqrt() as u32;
+        if nx * nx < n {
+            nx += 1;
+        }
+        (nx, n.div_ceil(nx))
+    }
+}
+
+impl PixelSampler for StratifiedSampler {
+    fn start_sample(&mut self, pixel: u64, index: u64) {
+        // numbers drawn from fastrand directly are repeatable too
+        random::seed_stream(self.seed, pixel, index);
+        self.pixel = pixel;
+        self.index = index;
+        self.dim = 0;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let n = self.nsamples;
+        let (stratum, h) = self.stratum(n);
+        ((stratum as f32 + to_unit(h as u32)) / n as f32).min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let (nx, ny) = self.grid();
+        let (cell, h) = self.stratum(nx * ny);
+        let x = (cell % nx) as f32 + to_unit(h as u32);
+        let y = (cell / nx) as f32 + to_unit((h >> 32) as u32);
+        (
+            (x / nx as f32).min(ONE_MINUS_EPSILON),
+            (y / ny as f32).min(ONE_MINUS_EPSILON),
+        )
+    }
+}
@@ -1,8 +1,9 @@
-use anyhow::{Ok, Result};
+use anyhow::{Context, Ok, Result};
 use std::path::{Path, PathBuf};
 use url::Url;
 
-use crate::core::Image;
+use crate::core::HdrImage;
+use crate::media::DensityGrid;
 
 pub struct AssetsManager {
     assets_dir: PathBuf,
@@ -51,13 +52,22 @@ impl AssetsManager {
         Ok(path)
     }
 
-    pub fn load_image(&self, uri: &str) -> Result<Image> {
+    pub fn load_image(&self, uri: &str) -> Result<HdrImage> {
         let path = self.load_path(uri)?;
 
-        let image = Image::load_png(path)?;
+        let image = HdrImage::load(path)?;
 
         Ok(image)
     }
+
+    pub fn load_density_grid(&self, uri: &str) -> Result<DensityGrid> {
+        let path = self.load_path(uri)?;
+
+        let grid = DensityGrid::load(&path)
+            .with_context(|| format!("failed to load density grid {}", path.display()))?;
+
+        Ok(grid)
+    }
 }
 
 #[cfg(test)]
@@ -1,27 +1,42 @@
-use std::{rc::Rc, sync::Arc};
+use std::{path::Path, rc::Rc, sync::Arc, time::Instant};
 
+use crate::stats::{self, Phase};
+use crate::utils::random;
 use crate::{
     accelerators::BVHAccel,
     core::{
-        vec3, Camera, CameraOpt, MaterialPtr, PrimitiveContainerPtr, PrimitivePtr, Project, Scene,
-        SceneBundle, Settings, ShapePtr, TexturePtr, Transform, Vec2f, Vec3f,
+        vec3, Camera, CameraOpt, FilterPtr, IntegratorPtr, Ior, MaterialPtr, MediumInterface,
+        MediumPtr, PrimitiveContainerPtr, PrimitivePtr, Project, Scene, SceneBundle, Settings,
+        ShapePtr, TexturePtr, Transform, Vec2f, Vec3f, WorkingSpace, AABB,
+    },
+    filters::{BoxFilter, GaussianFilter, LanczosFilter, MitchellFilter, TentFilter},
+    integrators::{
+        AlbedoIntegrator, AmbientOcclusionIntegrator, BdptIntegrator, DepthIntegrator,
+        DirectLightingIntegrator, NormalIntegrator, PathIntegrator, PssmltIntegrator,
+        SppmIntegrator, UvIntegrator,
     },
     lights::{AreaLight, EnvironmentLight},
-    materials::{Dielectric, DiffuseLight, GltfPbrMaterial, Lambertian, Metal, Transparent},
-    primitives::{FlipFacePrimitive, GeometricPrimitive, PrimitiveList},
+    materials::{
+        Dielectric, DiffuseLight, GltfPbrMaterial, Lambertian, Metal, SubsurfaceMaterial,
+        Transparent,
+    },
+    media::{DensityFieldPtr, HeterogeneousMedium, HomogeneousMedium, NoiseDensity},
+    primitives::{FlipFacePrimitive, GeometricPrimitive, IdAllocator, PrimitiveList},
     shapes::{
         Cube, Cylinder, Disk, Pyramid, Rect, RegularPolygon, Sphere, Triangle, TriangleMeshStorage,
     },
     textures::{CheckerTexture, ConstantTexture, ImageTexture, ImageTextureParams},
 };
 use anyhow::{ensure, Context, Ok, Result};
+use nalgebra::Matrix3;
 
 use super::{
     loaders::{load_gltf_scenes, MeshLoader},
     types::{
-        AcceleratorConfig, AorB, CameraConfig, JVec2f, JVec3f, MaterialConfig, PrimitiveConfig,
-        ProjectConfig, SceneConfig, SceneCustomConfig, ShapeConfig, TextureConfig, TextureOrConst,
-        TransformConfig, UriConfig,
+        AcceleratorConfig, AorB, CameraConfig, DensityMediumConfig, FilterConfig, IntegratorConfig,
+        IorConfig, IorOrConst, JVec2f, JVec3f, MaterialConfig, MediumConfig, MediumInterfaceConfig,
+        PrimitiveConfig, ProjectConfig, SceneConfig, SceneCustomConfig, ShapeConfig,
+        TextureColorSpace, TextureConfig, TextureOrConst, TransformConfig, UriConfig,
     },
     AssetsManager,
 };
@@ -50,6 +65,7 @@ pub struct Builder {
     transforms_stack: Vec<Transform>,
     cur_transform: Transform,
     assets_manager: Rc<AssetsManager>,
+    ids: IdAllocator,
 }
 
 impl Builder {
@@ -59,6 +75,7 @@ impl Builder {
             transforms_stack: Vec::new(),
             cur_transform: Transform::identity(),
             assets_manager,
+            ids: IdAllocator::new(),
         }
     }
 
@@ -85,32 +102,178 @@ impl Builder {
     fn get_settings(&self) -> &Settings {
         return self.settings.as_ref().unwrap();
     }
+
+    // a rec709 color of the config in the working space
+    fn color(&self, c: &JVec3f) -> Vec3f {
+        let c: Vec3f = c.into();
+        self.get_settings().working_space.input_matrix() * c
+    }
 }
 
 impl Builder {
     pub fn build_project(&mut self, conf: &ProjectConfig) -> Result<Project> {
-        self.settings = Some(conf.settings.clone());
+        let mut settings = conf.settings.clone();
+        // spectra are made from rec709 colors
+        if settings.spectral && settings.working_space != WorkingSpace::Rec709 {
+            log::warn!("spectral rendering works in rec709, settings.working_space is ignored");
+            settings.working_space = WorkingSpace::Rec709;
+        }
+        self.settings = Some(settings.clone());
+        // the bvh splits along random axes
+        random::seed(conf.settings.seed);
 
         let scene_bundle = self.build_scenes(&conf.scenes)?;
         let camera = scene_bundle.camera.clone().context("camera is not set")?;
 
         let world = self.build_accelerator(&conf.accelerator, &scene_bundle.primitives)?;
+        let integrator = self.build_integrator(&conf.integrator, &world, &camera)?;
+        let filter = self.build_filter(&conf.filter)?;
+        if conf.settings.spectral && !integrator.supports_spectral() {
+            log::warn!("the integrator renders RGB, settings.spectral is ignored");
+        }
+        // whole-image integrators keep every sample in the pixel it was taken in
+        let whole_image = matches!(
+            conf.integrator,
+            IntegratorConfig::Sppm {} | IntegratorConfig::Pssmlt { .. }
+        );
+        let pixel_box = matches!(conf.filter, FilterConfig::Box { radius } if radius == 0.5);
+        if whole_image && !pixel_box {
+            log::warn!("the integrator doesn't filter its samples, the filter is ignored");
+        }
+        let split = settings.aovs.iter().any(|aov| aov.is_split());
+        if split && !integrator.splits_direct(&settings) {
+            log::warn!("the integrator mixes direct and indirect light, their aovs are ignored");
+            settings.aovs.retain(|aov| !aov.is_split());
+        }
+
+        let has_participating_media =
+            scene_bundle.has_participating_media || camera.medium().is_some();
+        if has_participating_media && !integrator.supports_media(&settings) {
+            log::warn!("the integrator doesn't sample media, they are looked through");
+        }
+        if scene_bundle.has_subsurface && !integrator.supports_subsurface(&settings) {
+            log::warn!(
+                "the integrator doesn't walk below subsurface materials, they render as dielectrics"
+            );
+        }
 
-        let scene = Scene::new(camera, world, scene_bundle.lights);
+        let has_media = scene_bundle.has_media || camera.medium().is_some();
+        let scene = Scene::new(camera, world, scene_bundle.lights, has_media);
 
-        let project = Project::new(conf.name.clone(), conf.settings.clone(), scene);
+        let project = Project::new(conf.name.clone(), settings, scene, integrator, filter);
         Ok(project)
     }
 
+    fn build_integrator(
+        &self,
+        conf: &IntegratorConfig,
+        world: &PrimitiveContainerPtr,
+        camera: &Camera,
+    ) -> Result<IntegratorPtr> {
+        let integrator: IntegratorPtr = match conf {
+            IntegratorConfig::Path {} => Arc::new(PathIntegrator::new()),
+            IntegratorConfig::DirectLighting {} => Arc::new(DirectLightingIntegrator::new()),
+            IntegratorConfig::Bdpt {} => {
+                let bbox = world
+                    .bounding_box(0.0, 1.0)
+                    .context("world has no bounds")?;
+                Arc::new(BdptIntegrator::new(bbox))
+            }
+            IntegratorConfig::Sppm {} => {
+                let bbox = world
+                    .bounding_box(0.0, 1.0)
+                    .context("world has no bounds")?;
+                Arc::new(SppmIntegrator::new(bbox))
+            }
+            IntegratorConfig::Pssmlt {
+                bootstrap_samples,
+                chains,
+                large_step_probability,
+                sigma,
+            } => {
+                ensure!(
+                    (0.0..=1.0).contains(large_step_probability),
+                    "pssmlt requires large_step_probability in [0, 1]"
+                );
+                ensure!(*sigma > 0.0, "pssmlt requires sigma > 0");
+                Arc::new(PssmltIntegrator::new(
+                    *bootstrap_samples,
+                    *chains,
+                    *large_step_probability,
+                    *sigma,
+                ))
+            }
+            IntegratorConfig::AmbientOcclusion {
+                nsamples,
+                max_distance,
+            } => Arc::new(AmbientOcclusionIntegrator::new(
+                *nsamples,
+                max_distance.unwrap_or(f32::MAX),
+            )),
+            IntegratorConfig::Normal {} => Arc::new(NormalIntegrator::new()),
+            IntegratorConfig::Uv {} => Arc::new(UvIntegrator::new()),
+            IntegratorConfig::Albedo {} => Arc::new(AlbedoIntegrator::new()),
+            IntegratorConfig::Depth { near, far } => {
+                let bbox = world
+                    .bounding_box(0.0, 1.0)
+                    .context("world has no bounds")?;
+                let origin = camera.origin();
+                // nearest point of the world bounds, zero if the camera is inside
+                let near = near.unwrap_or_else(|| {
+                    let d = vec3::max(&(bbox.min - origin), &(origin - bbox.max));
+                    vec3::max(&d, &Vec3f::zeros()).norm()
+                });
+                // farthest corner of the world bounds
+                let far = far.unwrap_or_else(|| {
+                    vec3::max(&(bbox.min - origin).abs(), &(bbox.max - origin).abs()).norm()
+                });
+                ensure!(
+                    far > near,
+                    "depth integrator requires far > near, got near={} far={}",
+                    near,
+                    far
+                );
+                Arc::new(DepthIntegrator::new(near, far))
+            }
+        };
+        Ok(integrator)
+    }
+
+    fn build_filter(&self, conf: &FilterConfig) -> Result<FilterPtr> {
+        let filter: FilterPtr = match conf {
+            FilterConfig::Box { radius } => Arc::new(BoxFilter::new(*radius)),
+            FilterConfig::Tent { radius } => Arc::new(TentFilter::new(*radius)),
+            FilterConfig::Gaussian { radius, sigma } => {
+                ensure!(*sigma > 0.0, "gaussian filter requires sigma > 0");
+                Arc::new(GaussianFilter::new(*radius, *sigma))
+            }
+            FilterConfig::Mitchell { radius, b, c } => {
+                Arc::new(MitchellFilter::new(*radius, *b, *c))
+            }
+            FilterConfig::Lanczos { radius, tau } => {
+                ensure!(*tau > 0.0, "lanczos filter requires tau > 0");
+                Arc::new(LanczosFilter::new(*radius, *tau))
+            }
+        };
+        ensure!(
+            filter.radius() > 0.0 && filter.radius().is_finite(),
+            "filter radius must be positive, got {}",
+            filter.radius()
+        );
+        Ok(filter)
+    }
+
     fn build_accelerator(
         &self,
         conf: &AcceleratorConfig,
         prims: &[PrimitivePtr],
     ) -> Result<PrimitiveContainerPtr> {
+        let start = Instant::now();
         let primitive: PrimitiveContainerPtr = match conf {
             AcceleratorConfig::Nop {} => Arc::new(PrimitiveList::from(prims)),
             AcceleratorConfig::Bvh {} => Arc::new(BVHAccel::new(prims.to_vec(), 0.0, 1.0)),
         };
+        stats::add_time(Phase::BvhBuild, start.elapsed());
         Ok(primitive)
     }
 
@@ -119,13 +282,32 @@ impl Builder {
 
         for conf in confs {
             let bundle = match conf {
-                SceneConfig::Uri { uri, transforms } => {
+                SceneConfig::Uri {
+                    uri,
+                    transforms,
+                    material,
+                } => {
                     let gltf_path = self.assets_manager.load_path(uri)?;
 
                     let transform = self.build_transforms(&transforms)?;
-                    let bundles = load_gltf_scenes(&gltf_path, transform)?;
-
-                    let mut acc_bundle = SceneBundle::default();
+                    let subsurface = matches!(material, Some(MaterialConfig::Subsurface { .. }));
+                    let material = match material {
+                        Some(material) => Some(self.build_material(material)?),
+                        None => None,
+                    };
+                    let working_space = self.get_settings().working_space;
+                    let bundles = load_gltf_scenes(
+                        &gltf_path,
+                        transform,
+                        material,
+                        working_space,
+                        &mut self.ids,
+                    )?;
+
+                    let mut acc_bundle = SceneBundle {
+                        has_subsurface: subsurface,
+                        ..Default::default()
+                    };
                     for bundle in bundles {
                         acc_bundle.union_assign(bundle);
                     }
@@ -156,7 +338,7 @@ impl Builder {
         }
 
         for env in &conf.environments {
-            let light = Arc::new(EnvironmentLight::new(env.l.into()));
+            let light = Arc::new(EnvironmentLight::new(self.color(&env.l)));
             bundle.lights.push(light);
         }
 
@@ -172,17 +354,25 @@ impl Builder {
             self.get_settings().get_aspect()
         };
 
-        Ok(Arc::new(Camera::new(CameraOpt {
-            look_from: conf.look_from.into(),
-            look_at: conf.look_at.into(),
-            view_up: conf.view_up.into(),
-            vertical_fov: conf.vertical_fov,
-            aspect,
-            aperture: conf.aperture,
-            focus_dist: conf.focus_dist,
-            time0: conf.time0,
-            time1: conf.time1,
-        })))
+        let medium = match &conf.medium {
+            Some(medium) => Some(self.build_medium(medium)?),
+            None => None,
+        };
+
+        Ok(Arc::new(
+            Camera::new(CameraOpt {
+                look_from: conf.look_from.into(),
+                look_at: conf.look_at.into(),
+                view_up: conf.view_up.into(),
+                vertical_fov: conf.vertical_fov,
+                aspect,
+                aperture: conf.aperture,
+                focus_dist: conf.focus_dist,
+                time0: conf.time0,
+                time1: conf.time1,
+            })
+            .with_medium(medium),
+        ))
     }
 
     fn build_world(&mut self, confs: &[PrimitiveConfig]) -> Result<SceneBundle> {
@@ -198,13 +388,41 @@ impl Builder {
                     material,
                     area_light,
                     flip_face,
+                    medium,
                 } => {
+                    ensure!(
+                        material.is_some() || area_light.is_none(),
+                        "area light requires a material"
+                    );
+
                     let shapes = self.build_shapes(shape, self.get_current_transform())?;
-                    let material = self.build_material(material)?;
+                    // equal configs are built into separate materials, they share the id
+                    let material_key = material.as_ref().map(serde_json::to_string).transpose()?;
+                    let subsurface = matches!(material, Some(MaterialConfig::Subsurface { .. }));
+                    let material = match material {
+                        Some(material) => Some(self.build_material(material)?),
+                        None => None,
+                    };
+                    let medium_interface = match medium {
+                        Some(medium) => Some(self.build_medium_interface(medium)?),
+                        None => None,
+                    };
+                    if material.is_none() || medium_interface.is_some() {
+                        bundle.has_media = true;
+                    }
+                    bundle.has_participating_media |= medium_interface.is_some();
+                    bundle.has_subsurface |= subsurface;
+                    let ids = self.ids.next(material_key);
 
                     for shape in shapes {
-                        let prim: PrimitivePtr =
-                            Arc::new(GeometricPrimitive::new(shape, material.clone()));
+                        let prim: PrimitivePtr = Arc::new(
+                            GeometricPrimitive::new_with_medium(
+                                shape,
+                                material.clone(),
+                                medium_interface.clone(),
+                            )
+                            .with_ids(ids),
+                        );
 
                         let prim = if *flip_face {
                             Arc::new(FlipFacePrimitive::new(prim))
@@ -228,6 +446,31 @@ impl Builder {
                     let children_bundle = self.build_world(&children)?;
                     bundle.union_assign(children_bundle);
                 }
+                PrimitiveConfig::Volume {
+                    transforms: _,
+                    medium,
+                } => {
+                    let (p_min, p_max) = medium
+                        .bounds()
+                        .context("volume requires a bounded medium, e.g. grid or noise")?;
+                    let medium = self.build_medium(medium)?;
+
+                    // material-less box around the medium, it puts the medium into the bvh
+                    let shape = Arc::new(Cube::new(
+                        p_min.into(),
+                        p_max.into(),
+                        self.get_current_transform(),
+                    ));
+                    bundle
+                        .primitives
+                        .push(Arc::new(GeometricPrimitive::new_with_medium(
+                            shape,
+                            None,
+                            Some(MediumInterface::new(Some(medium), None)),
+                        )));
+                    bundle.has_media = true;
+                    bundle.has_participating_media = true;
+                }
             }
 
             self.exit_transform();
@@ -358,6 +601,83 @@ impl Builder {
         Ok(shapes)
     }
 
+    fn build_medium_interface(&self, conf: &MediumInterfaceConfig) -> Result<MediumInterface> {
+        let build = |conf: &Option<MediumConfig>| match conf {
+            Some(conf) => self.build_medium(conf).map(Some),
+            None => Ok(None),
+        };
+        Ok(MediumInterface::new(
+            build(&conf.inside)?,
+            build(&conf.outside)?,
+        ))
+    }
+
+    fn build_medium(&self, conf: &MediumConfig) -> Result<MediumPtr> {
+        let medium: MediumPtr = match conf {
+            MediumConfig::Homogeneous {
+                sigma_a,
+                sigma_s,
+                scale,
+                g,
+            } => {
+                let sigma_a = self.color(sigma_a);
+                let sigma_s = self.color(sigma_s);
+                ensure!(
+                    sigma_a.min() >= 0.0 && sigma_s.min() >= 0.0 && *scale >= 0.0,
+                    "medium coefficients must not be negative"
+                );
+                Arc::new(HomogeneousMedium::new(
+                    sigma_a * *scale,
+                    sigma_s * *scale,
+                    *g,
+                ))
+            }
+            MediumConfig::Grid { uri, density } => {
+                let grid = self.assets_manager.load_density_grid(uri)?;
+                self.build_density_medium(Box::new(grid), density)?
+            }
+            MediumConfig::Noise {
+                seed,
+                frequency,
+                octaves,
+                cutoff,
+                ellipsoid,
+                density,
+            } => {
+                let noise = NoiseDensity::new(*seed, *frequency, *octaves, *cutoff, *ellipsoid);
+                self.build_density_medium(Box::new(noise), density)?
+            }
+        };
+        Ok(medium)
+    }
+
+    fn build_density_medium(
+        &self,
+        field: DensityFieldPtr,
+        conf: &DensityMediumConfig,
+    ) -> Result<MediumPtr> {
+        let p_min: Vec3f = (&conf.p_min).into();
+        let p_max: Vec3f = (&conf.p_max).into();
+        let albedo = self.color(&conf.albedo);
+        ensure!(
+            (p_max - p_min).min() > 0.0,
+            "medium bounds must not be empty"
+        );
+        ensure!(
+            conf.scale >= 0.0 && albedo.min() >= 0.0 && albedo.max() <= 1.0,
+            "medium scale must not be negative and albedo must be in [0, 1]"
+        );
+
+        Ok(Arc::new(HeterogeneousMedium::new(
+            field,
+            AABB::new(p_min, p_max),
+            self.get_current_transform(),
+            conf.scale,
+            albedo,
+            conf.g,
+        )))
+    }
+
     fn build_material(&self, conf: &MaterialConfig) -> Result<MaterialPtr> {
         let material: MaterialPtr = match conf {
             MaterialConfig::Lambertian { albedo } => {
@@ -367,7 +687,7 @@ impl Builder {
             MaterialConfig::Metal { albedo, fuzz } => {
                 Arc::new(Metal::new(self.build_texture_or_vec3f(&albedo)?, *fuzz))
             }
-            MaterialConfig::Dielectric { ir } => Arc::new(Dielectric::new(*ir)),
+            MaterialConfig::Dielectric { ir } => Arc::new(Dielectric::new(self.build_ior(ir)?)),
             MaterialConfig::DiffuseLight { emit } => {
                 Arc::new(DiffuseLight::new(self.build_texture_or_vec3f(&emit)?))
             }
@@ -376,7 +696,7 @@ impl Builder {
                 roughness,
                 albedo,
             } => Arc::new(Transparent::new(
-                *eta,
+                self.build_ior(eta)?,
                 self.build_texture_or_f32(&roughness)?,
                 self.build_texture_or_vec3f(&albedo)?,
             )),
@@ -393,15 +713,48 @@ impl Builder {
                 self.build_texture_or_f32(roughness)?,
                 self.build_texture_or_vec3f(emit)?,
             )),
+            MaterialConfig::Subsurface {
+                albedo,
+                mean_free_path,
+                eta,
+                g,
+            } => {
+                let mean_free_path = self.color(mean_free_path);
+                ensure!(
+                    mean_free_path.min() > 0.0,
+                    "subsurface mean free path must be positive"
+                );
+                Arc::new(SubsurfaceMaterial::new(
+                    self.build_texture_or_vec3f(albedo)?,
+                    mean_free_path,
+                    *eta,
+                    *g,
+                ))
+            }
         };
 
         Ok(material)
     }
 
+    fn build_ior(&self, conf: &IorOrConst) -> Result<Ior> {
+        let ior = match conf.clone() {
+            AorB::A(IorConfig::Cauchy { a, b }) => Ior::Cauchy { a, b },
+            AorB::A(IorConfig::Sellmeier { b, c }) => {
+                ensure!(
+                    !b.is_empty() && b.len() == c.len(),
+                    "sellmeier ior needs as many b as c coefficients"
+                );
+                Ior::Sellmeier { b, c }
+            }
+            AorB::B(eta) => Ior::Constant(eta),
+        };
+        Ok(ior)
+    }
+
     fn build_texture_or_vec3f(&self, conf: &TextureOrConst<JVec3f>) -> Result<TexturePtr<Vec3f>> {
         let texture: TexturePtr<Vec3f> = match conf.clone() {
             AorB::A(conf) => self.build_texture_vec3f(&conf)?,
-            AorB::B(value) => Arc::new(ConstantTexture::new(value.into())),
+            AorB::B(value) => Arc::new(ConstantTexture::new(self.color(value))),
         };
 
         Ok(texture)
@@ -410,16 +763,20 @@ impl Builder {
     fn build_texture_vec3f(&self, conf: &TextureConfig<JVec3f>) -> Result<TexturePtr<Vec3f>> {
         Ok(match conf {
             TextureConfig::ConstantTexture { value } => {
-                Arc::new(ConstantTexture::new(value.into()))
+                Arc::new(ConstantTexture::new(self.color(value)))
             }
-            TextureConfig::ImageTexture { uri } => {
+            TextureConfig::ImageTexture { uri, color_space } => {
                 let image = self.assets_manager.load_image(&uri)?;
-                Arc::new(ImageTexture::from_image(
+                let color_space = color_space.unwrap_or_else(|| {
+                    let ext = Path::new(uri).extension().and_then(|ext| ext.to_str());
+                    match ext.map(|ext| ext.to_lowercase()).as_deref() {
+                        Some("exr") | Some("hdr") => TextureColorSpace::Linear,
+                        _ => TextureColorSpace::Srgb,
+                    }
+                });
+                Arc::new(ImageTexture::<Vec3f>::from_image(
                     image,
-                    ImageTextureParams {
-                        scale: vec3::scalar(1.0),
-                        flip: true,
-                    },
+                    self.image_texture_params(color_space),
                 ))
             }
             TextureConfig::CheckerTexture { odd, even } => {
@@ -443,9 +800,13 @@ impl Builder {
     fn build_texture_f32(&self, conf: &TextureConfig<f32>) -> Result<TexturePtr<f32>> {
         Ok(match conf {
             TextureConfig::ConstantTexture { value } => Arc::new(ConstantTexture::new(*value)),
-            TextureConfig::ImageTexture { uri } => {
-                let _image = self.assets_manager.load_image(&uri)?;
-                todo!()
+            TextureConfig::ImageTexture { uri, color_space } => {
+                let image = self.assets_manager.load_image(&uri)?;
+                let color_space = color_space.unwrap_or(TextureColorSpace::Raw);
+                Arc::new(ImageTexture::<f32>::from_image(
+                    image,
+                    self.image_texture_params(color_space),
+                ))
             }
             TextureConfig::CheckerTexture { odd, even } => {
                 let odd = self.build_texture_or_f32(odd.as_ref())?;
@@ -455,6 +816,19 @@ impl Builder {
         })
     }
 
+    fn image_texture_params(&self, color_space: TextureColorSpace) -> ImageTextureParams {
+        let color_transform = match color_space {
+            TextureColorSpace::Raw => Matrix3::identity(),
+            _ => self.get_settings().working_space.input_matrix(),
+        };
+        ImageTextureParams {
+            scale: vec3::scalar(1.0),
+            flip: true,
+            srgb: color_space == TextureColorSpace::Srgb,
+            color_transform,
+        }
+    }
+
     fn build_transforms(&self, confs: &[TransformConfig]) -> Result<Transform> {
         let transforms = confs.iter().map(|conf| match *conf {
             TransformConfig::Translate { offset } => Transform::translate(offset.into()),
@@ -2,29 +2,56 @@ use anyhow::{Context, Result};
 use std::{path::Path, sync::Arc};
 
 use crate::{
-    core::{vec3, MaterialPtr, Point2f, SceneBundle, Spectrum, TexturePtr, Transform, Vec3f},
+    core::{
+        vec3, MaterialPtr, Point2f, SceneBundle, Spectrum, TexturePtr, Transform, Vec3f,
+        WorkingSpace,
+    },
     lights::AreaLight,
     materials::{DiffuseLight, GltfPbrMaterial},
-    primitives::GeometricPrimitive,
+    primitives::{GeometricPrimitive, IdAllocator},
     shapes::{ShapeList, Triangle, TriangleMeshStorage},
     textures::{ConstantTexture, ImageTexture, ImageTextureParams},
 };
 
-pub fn load_gltf_scenes<P: AsRef<Path>>(path: P, transform: Transform) -> Result<Vec<SceneBundle>> {
+// `material` replaces the materials of models which don't emit light. Colors are taken from
+// rec709 into `working_space`
+pub fn load_gltf_scenes<P: AsRef<Path>>(
+    path: P,
+    transform: Transform,
+    material: Option<MaterialPtr>,
+    working_space: WorkingSpace,
+    ids: &mut IdAllocator,
+) -> Result<Vec<SceneBundle>> {
+    let source = path.as_ref().display().to_string();
     let g_scenes = easy_gltf::load(path).map_err(|e| anyhow::anyhow!(e.to_string()))?;
 
     let mut scene_bundles = Vec::new();
 
     for g_scene in g_scenes.iter() {
-        let scene_bundle = load_scene(g_scene, transform.clone())?;
+        let scene_bundle = load_scene(
+            g_scene,
+            transform.clone(),
+            material.as_ref(),
+            working_space,
+            &source,
+            ids,
+        )?;
         scene_bundles.push(scene_bundle);
     }
 
     Ok(scene_bundles)
 }
 
-fn load_scene(g_scene: &easy_gltf::Scene, transform: Transform) -> Result<SceneBundle> {
+fn load_scene(
+    g_scene: &easy_gltf::Scene,
+    transform: Transform,
+    material_override: Option<&MaterialPtr>,
+    working_space: WorkingSpace,
+    source: &str,
+    ids: &mut IdAllocator,
+) -> Result<SceneBundle> {
     let mut bundle = SceneBundle::default();
+    let to_working_space = working_space.input_matrix();
 
     for g_model in g_scene.models.iter() {
         if easy_gltf::model::Mode::Triangles != g_model.mode() {
@@ -81,8 +108,8 @@ fn load_scene(g_scene: &easy_gltf::Scene, transform: Transform) -> Result<SceneB
 
         let g_material = g_model.material();
         let emissive = &g_material.emissive;
-        let emissive_factor =
-            Spectrum::new(emissive.factor.x, emissive.factor.y, emissive.factor.z);
+        let emissive_factor = to_working_space
+            * Spectrum::new(emissive.factor.x, emissive.factor.y, emissive.factor.z);
 
         let material: MaterialPtr = {
             let pbr = &g_material.pbr;
@@ -90,12 +117,14 @@ fn load_scene(g_scene: &easy_gltf::Scene, transform: Transform) -> Result<SceneB
             let roughness_factor = pbr.roughness_factor;
             let metallic_factor = pbr.metallic_factor;
 
-            let base_color_factor = Spectrum::new(
-                pbr.base_color_factor.x,
-                pbr.base_color_factor.y,
-                pbr.base_color_factor.z,
-            );
+            let base_color_factor = to_working_space
+                * Spectrum::new(
+                    pbr.base_color_factor.x,
+                    pbr.base_color_factor.y,
+                    pbr.base_color_factor.z,
+                );
 
+            // color textures of glTF are sRGB encoded, metallic and roughness are linear data
             let base_color: TexturePtr<Spectrum> = match &pbr.base_color_texture {
                 Some(g_base_color_texture) => {
                     let image_texture = ImageTexture::<Spectrum>::from_rgba_image(
@@ -103,6 +132,8 @@ fn load_scene(g_scene: &easy_gltf::Scene, transform: Transform) -> Result<SceneB
                         ImageTextureParams {
                             scale: base_color_factor,
                             flip: false,
+                            srgb: true,
+                            color_transform: to_working_space,
                         },
                     );
 
@@ -118,6 +149,8 @@ fn load_scene(g_scene: &easy_gltf::Scene, transform: Transform) -> Result<SceneB
                         ImageTextureParams {
                             scale: emissive_factor,
                             flip: false,
+                            srgb: true,
+                            color_transform: to_working_space,
                         },
                     );
 
@@ -133,6 +166,7 @@ fn load_scene(g_scene: &easy_gltf::Scene, transform: Transform) -> Result<SceneB
                         ImageTextureParams {
                             scale: vec3::scalar(metallic_factor),
                             flip: false,
+                            ..Default::default()
                         },
                     );
 
@@ -148,6 +182,7 @@ fn load_scene(g_scene: &easy_gltf::Scene, transform: Transform) -> Result<SceneB
                         ImageTextureParams {
                             scale: vec3::scalar(roughness_factor),
                             flip: false,
+                            ..Default::default()
                         },
                     );
 
@@ -178,6 +213,13 @@ fn load_scene(g_scene: &easy_gltf::Scene, transform: Transform) -> Result<SceneB
         let has_area_light: bool =
             { !vec3::is_black(&emissive_factor) && emissive.texture.is_none() };
 
+        // models of the file share the materials of easy_gltf, the override is a single one
+        let (material, material_key) = match material_override {
+            Some(material) if !has_area_light => (material.clone(), "override".to_string()),
+            _ => (material, format!("{:p}", Arc::as_ptr(&g_material))),
+        };
+        let model_ids = ids.next(Some(format!("{} {}", source, material_key)));
+
         let prims = &mut bundle.primitives;
         let lights = &mut bundle.lights;
 
@@ -197,14 +239,18 @@ fn load_scene(g_scene: &easy_gltf::Scene, transform: Transform) -> Result<SceneB
                 shapes.push(triangle)
             }
 
-            let prim = Arc::new(GeometricPrimitive::new(Arc::new(shapes), material.clone()));
+            let prim = Arc::new(
+                GeometricPrimitive::new(Arc::new(shapes), material.clone()).with_ids(model_ids),
+            );
             let area_light = Arc::new(AreaLight::new(prim.clone()));
 
             lights.push(area_light);
             prims.push(prim);
         } else {
             for triangle in triangles {
-                let prim = Arc::new(GeometricPrimitive::new(triangle, material.clone()));
+                let prim = Arc::new(
+                    GeometricPrimitive::new(triangle, material.clone()).with_ids(model_ids),
+                );
 
                 prims.push(prim);
             }
@@ -205,7 +205,7 @@ mod tests {
         match &project_config.scenes[0] {
             crate::scene_builder::types::SceneConfig::Custom(scene) => {
                 assert_eq!(scene.camera.is_some(), true);
-                let camera = scene.camera.unwrap();
+                let camera = scene.camera.as_ref().unwrap();
                 assert_eq!(camera.focus_dist, 10.0)
             }
             _ => todo!(),
@@ -29,6 +29,10 @@ pub struct ProjectConfig {
     pub scenes: Vec<SceneConfig>,
     #[serde(default)]
     pub accelerator: AcceleratorConfig,
+    #[serde(default)]
+    pub integrator: IntegratorConfig,
+    #[serde(default)]
+    pub filter: FilterConfig,
 }
 
 #[derive(JsonSchema, Serialize, Deserialize, Debug)]
@@ -44,6 +48,147 @@ impl Default for AcceleratorConfig {
     }
 }
 
+#[derive(JsonSchema, Serialize, Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum IntegratorConfig {
+    Path {},
+    DirectLighting {},
+    // bidirectional path tracing, light paths reaching the camera are splatted
+    Bdpt {},
+    // stochastic progressive photon mapping, one pass per sample, see `settings.photon_mapping`
+    Sppm {},
+    // primary sample space metropolis light transport, `nsamples` mutations per pixel
+    Pssmlt {
+        // independent paths estimating the image brightness, the chains start from them
+        #[serde(default = "default_pssmlt_bootstrap_samples")]
+        bootstrap_samples: usize,
+        #[serde(default = "default_pssmlt_chains")]
+        chains: usize,
+        // chance of a mutation to draw all numbers of the path anew
+        #[serde(default = "default_pssmlt_large_step_probability")]
+        large_step_probability: f32,
+        // standard deviation of the small steps of the numbers
+        #[serde(default = "default_pssmlt_sigma")]
+        sigma: f32,
+    },
+    AmbientOcclusion {
+        #[serde(default = "default_ao_nsamples")]
+        nsamples: usize,
+        // occluders further than this are ignored, unlimited by default
+        max_distance: Option<f32>,
+    },
+    Normal {},
+    Uv {},
+    Albedo {},
+    Depth {
+        // distance shown as black, defaults to the nearest point of the world
+        near: Option<f32>,
+        // distance shown as white, defaults to the farthest corner of the world
+        far: Option<f32>,
+    },
+}
+
+impl Default for IntegratorConfig {
+    fn default() -> Self {
+        IntegratorConfig::Path {}
+    }
+}
+
+fn default_ao_nsamples() -> usize {
+    16
+}
+
+fn default_pssmlt_bootstrap_samples() -> usize {
+    100000
+}
+
+fn default_pssmlt_chains() -> usize {
+    1000
+}
+
+fn default_pssmlt_large_step_probability() -> f32 {
+    0.3
+}
+
+fn default_pssmlt_sigma() -> f32 {
+    0.01
+}
+
+// pixel reconstruction filter, samples are weighted into all pixels within `radius` (in pixels)
+#[derive(JsonSchema, Serialize, Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FilterConfig {
+    Box {
+        #[serde(default = "FilterConfig::default_box_radius")]
+        radius: f32,
+    },
+    Tent {
+        #[serde(default = "FilterConfig::default_tent_radius")]
+        radius: f32,
+    },
+    Gaussian {
+        #[serde(default = "FilterConfig::default_gaussian_radius")]
+        radius: f32,
+        #[serde(default = "FilterConfig::default_gaussian_sigma")]
+        sigma: f32,
+    },
+    // Mitchell-Netravali, sharper than the gaussian with slight ringing
+    Mitchell {
+        #[serde(default = "FilterConfig::default_mitchell_radius")]
+        radius: f32,
+        #[serde(default = "FilterConfig::default_mitchell_bc")]
+        b: f32,
+        #[serde(default = "FilterConfig::default_mitchell_bc")]
+        c: f32,
+    },
+    // windowed sinc with `tau` lobes
+    Lanczos {
+        #[serde(default = "FilterConfig::default_lanczos_radius")]
+        radius: f32,
+        #[serde(default = "FilterConfig::default_lanczos_radius")]
+        tau: f32,
+    },
+}
+
+impl FilterConfig {
+    fn default_box_radius() -> f32 {
+        0.5
+    }
+
+    fn default_tent_radius() -> f32 {
+        1.0
+    }
+
+    fn default_gaussian_radius() -> f32 {
+        1.5
+    }
+
+    fn default_gaussian_sigma() -> f32 {
+        0.5
+    }
+
+    fn default_mitchell_radius() -> f32 {
+        2.0
+    }
+
+    fn default_mitchell_bc() -> f32 {
+        1.0 / 3.0
+    }
+
+    fn default_lanczos_radius() -> f32 {
+        3.0
+    }
+}
+
+// the box filter of one pixel, i.e. every pixel averages its own samples
+impl Default for FilterConfig {
+    fn default() -> Self {
+        FilterConfig::Box {
+            radius: Self::default_box_radius(),
+        }
+    }
+}
+
 #[derive(JsonSchema, Serialize, Deserialize, Debug)]
 #[serde(tag = "kind", rename_all = "snake_case")]
 pub enum SceneConfig {
@@ -51,6 +196,8 @@ pub enum SceneConfig {
         #[serde(default)]
         transforms: Vec<TransformConfig>,
         uri: String,
+        // replaces the materials of the models which don't emit light
+        material: Option<MaterialConfig>,
     },
     Custom(SceneCustomConfig),
 }
@@ -74,7 +221,7 @@ pub struct EnvironmentConfig {
     pub l: JVec3f,
 }
 
-#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(JsonSchema, Serialize, Deserialize, Debug)]
 #[serde(default)]
 pub struct CameraConfig {
     pub look_from: JVec3f,
@@ -86,6 +233,8 @@ pub struct CameraConfig {
     pub focus_dist: f32,
     pub time0: f32,
     pub time1: f32,
+    // medium the camera is placed in, e.g. fog filling the whole scene
+    pub medium: Option<MediumConfig>,
 }
 
 impl Default for CameraConfig {
@@ -100,6 +249,7 @@ impl Default for CameraConfig {
             focus_dist: 1.0,
             time0: 0.0,
             time1: 0.0,
+            medium: None,
         }
     }
 }
@@ -112,10 +262,12 @@ pub enum PrimitiveConfig {
         #[serde(default)]
         transforms: Vec<TransformConfig>,
         shape: ShapeConfig,
-        material: MaterialConfig,
+        // without material the shape is invisible and only bounds its media
+        material: Option<MaterialConfig>,
         area_light: Option<AreaLightConfig>,
         #[serde(default)]
         flip_face: bool,
+        medium: Option<Box<MediumInterfaceConfig>>,
     },
     // Container brach node, contains either Container or Geom
     Container {
@@ -124,16 +276,118 @@ pub enum PrimitiveConfig {
 
         children: Vec<PrimitiveConfig>,
     },
+    // box of a bounded medium (`grid` or `noise`), invisible except for the medium inside
+    Volume {
+        #[serde(default)]
+        transforms: Vec<TransformConfig>,
+        medium: MediumConfig,
+    },
 }
 
 #[derive(JsonSchema, Serialize, Deserialize, Debug)]
 pub struct AreaLightConfig {}
 
+// media inside and outside of a closed shape, inside is opposite to the shape normals
+#[derive(JsonSchema, Serialize, Deserialize, Debug)]
+pub struct MediumInterfaceConfig {
+    pub inside: Option<MediumConfig>,
+    pub outside: Option<MediumConfig>,
+}
+
+#[derive(JsonSchema, Serialize, Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MediumConfig {
+    Homogeneous {
+        // absorption and scattering coefficients, per unit of distance
+        sigma_a: JVec3f,
+        sigma_s: JVec3f,
+        // multiplies both coefficients
+        #[serde(default = "default_medium_scale")]
+        scale: f32,
+        // Henyey-Greenstein asymmetry in (-1, 1), 0 scatters uniformly
+        #[serde(default)]
+        g: f32,
+    },
+    // density grid file, see `DensityGrid` for the format
+    Grid {
+        uri: String,
+        #[serde(flatten)]
+        density: DensityMediumConfig,
+    },
+    // procedural fbm noise density in [0, 1]
+    Noise {
+        #[serde(default)]
+        seed: u64,
+        // noise cells across the bounds
+        #[serde(default = "default_noise_frequency")]
+        frequency: f32,
+        #[serde(default = "default_noise_octaves")]
+        octaves: u32,
+        // noise values below `cutoff` in [0, 1) are empty space
+        #[serde(default)]
+        cutoff: f32,
+        // fade the density out towards the ellipsoid inscribed in the bounds, for clouds
+        #[serde(default)]
+        ellipsoid: bool,
+        #[serde(flatten)]
+        density: DensityMediumConfig,
+    },
+}
+
+// parameters of media with varying density, which fill the box `p_min` to `p_max`
+#[derive(JsonSchema, Serialize, Deserialize, Debug)]
+pub struct DensityMediumConfig {
+    #[serde(default = "default_medium_p_min")]
+    pub p_min: JVec3f,
+    #[serde(default = "default_medium_p_max")]
+    pub p_max: JVec3f,
+    // extinction coefficient per unit of distance at density 1
+    #[serde(default = "default_medium_scale")]
+    pub scale: f32,
+    // fraction of the extinction which is scattering
+    pub albedo: JVec3f,
+    #[serde(default)]
+    pub g: f32,
+}
+
+fn default_medium_scale() -> f32 {
+    1.0
+}
+
+fn default_medium_p_min() -> JVec3f {
+    JVec3f::new(0.0, 0.0, 0.0)
+}
+
+fn default_medium_p_max() -> JVec3f {
+    JVec3f::new(1.0, 1.0, 1.0)
+}
+
+fn default_noise_frequency() -> f32 {
+    4.0
+}
+
+fn default_noise_octaves() -> u32 {
+    4
+}
+
+impl MediumConfig {
+    // bounds of the region with non-zero density, None for unbounded media
+    pub fn bounds(&self) -> Option<(&JVec3f, &JVec3f)> {
+        match self {
+            MediumConfig::Homogeneous { .. } => None,
+            MediumConfig::Grid { density, .. } | MediumConfig::Noise { density, .. } => {
+                Some((&density.p_min, &density.p_max))
+            }
+        }
+    }
+}
+
 impl PrimitiveConfig {
     pub fn get_transforms(&self) -> &[TransformConfig] {
         match self {
             PrimitiveConfig::Geom { transforms, .. } => transforms,
             PrimitiveConfig::Container { transforms, .. } => transforms,
+            PrimitiveConfig::Volume { transforms, .. } => transforms,
         }
     }
 }
@@ -220,6 +474,20 @@ pub enum AorB<A, B> {
 
 pub type TextureOrConst<T> = AorB<TextureConfig<T>, T>;
 
+// index of refraction varying with the wavelength, see `settings.spectral`. Without sampled
+// wavelengths the index at 587.6 nm is used
+#[derive(JsonSchema, Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum IorConfig {
+    // a + b / lambda^2, lambda in um, e.g. a = 1.5046, b = 0.0042 for BK7 glass
+    Cauchy { a: f32, b: f32 },
+    // 1 + sum of b_i lambda^2 / (lambda^2 - c_i) squared, lambda in um, e.g. diamond has
+    // b = [0.3306, 4.3356], c = [0.030625, 0.011236]
+    Sellmeier { b: Vec<f32>, c: Vec<f32> },
+}
+
+pub type IorOrConst = AorB<IorConfig, f32>;
+
 #[derive(JsonSchema, Deserialize, Serialize, Debug)]
 #[serde(tag = "kind", rename_all = "snake_case")]
 pub enum MaterialConfig {
@@ -231,13 +499,13 @@ pub enum MaterialConfig {
         fuzz: f32,
     },
     Dielectric {
-        ir: f32,
+        ir: IorOrConst,
     },
     DiffuseLight {
         emit: TextureOrConst<JVec3f>,
     },
     Transparent {
-        eta: f32,
+        eta: IorOrConst,
         roughness: TextureOrConst<f32>,
         albedo: TextureOrConst<JVec3f>,
     },
@@ -250,6 +518,21 @@ pub enum MaterialConfig {
         #[serde(default = "default_texture_vec3f")]
         emit: TextureOrConst<JVec3f>,
     },
+    // random walk subsurface scattering below a smooth dielectric surface
+    Subsurface {
+        // color of a thick block of the material
+        albedo: TextureOrConst<JVec3f>,
+        // average distance between scattering events per channel, in scene units
+        mean_free_path: JVec3f,
+        #[serde(default = "default_subsurface_eta")]
+        eta: f32,
+        #[serde(default)]
+        g: f32,
+    },
+}
+
+fn default_subsurface_eta() -> f32 {
+    1.33
 }
 
 fn default_gltf_pbr_eta() -> f32 {
@@ -268,9 +551,23 @@ pub enum TextureConfig<T> {
     },
     ImageTexture {
         uri: String,
+        // by default raw for scalar textures, linear for exr and hdr images and srgb for others
+        color_space: Option<TextureColorSpace>,
     },
     CheckerTexture {
         odd: Box<TextureOrConst<T>>,
         even: Box<TextureOrConst<T>>,
     },
 }
+
+// how the values of an image texture become colors of the working space
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TextureColorSpace {
+    // sRGB encoded rec709 colors, like most png and jpeg images
+    Srgb,
+    // linear rec709 colors
+    Linear,
+    // data used as is, e.g. roughness
+    Raw,
+}
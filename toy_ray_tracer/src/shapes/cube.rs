@@ -47,9 +47,8 @@ impl Shape for Cube {
         self.triangles.bounding_box(t0, t1)
     }
 
-    fn intersect_p(&self, ray: &Ray) -> bool {
-        // naive implementation
-        self.triangles.intersect_p(ray)
+    fn intersect_p(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        self.triangles.intersect_p(ray, t_min, t_max)
     }
 
     fn sample_pdf(&self, point: &crate::core::Point3f, wi: &Vec3f) -> f32 {
@@ -59,4 +58,12 @@ impl Shape for Cube {
     fn sample_wi(&self, point: &crate::core::Point3f) -> Vec3f {
         self.triangles.sample_wi(point)
     }
+
+    fn area(&self) -> f32 {
+        self.triangles.area()
+    }
+
+    fn sample_area(&self) -> crate::core::SurfaceInteraction<'_> {
+        self.triangles.sample_area()
+    }
 }
@@ -1,7 +1,10 @@
+use std::f32::consts::PI;
+
 use crate::{
     core::AABB,
     core::{vec3, Point2f, Ray, Transform, Vec3f},
     core::{Shape, SurfaceInteraction},
+    utils::random,
 };
 
 use super::Plane;
@@ -41,17 +44,41 @@ impl Shape for Cylinder {
         si
     }
 
-    fn intersect_p(&self, ray: &Ray) -> bool {
+    fn intersect_p(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
         let ray = self.world_to_object.transform_ray(ray);
-        self.cylinder.intersect_p(&ray)
+        self.cylinder.intersect_p(&ray, t_min, t_max)
+    }
+
+    // a direction reaches up to two points of the surface, sampling either of them by area
+    // picks it
+    fn sample_pdf(&self, point: &crate::core::Point3f, wi: &Vec3f) -> f32 {
+        let ray = Ray::new(*point, wi.normalize(), 0.0);
+        let area = self.area();
+        let mut pdf = 0.0;
+        let mut t_min = 0.001;
+        while let Some(si) = self.intersect(&ray, t_min, f32::MAX) {
+            let cosine = ray.direction().dot(&si.normal).abs();
+            if cosine > 0.0 {
+                pdf += si.t_hit * si.t_hit / (cosine * area);
+            }
+            t_min = si.t_hit + 0.001;
+        }
+        pdf
     }
 
-    fn sample_pdf(&self, _point: &crate::core::Point3f, _wi: &Vec3f) -> f32 {
-        unimplemented!()
+    fn sample_wi(&self, point: &crate::core::Point3f) -> Vec3f {
+        self.sample_area().point - point
     }
 
-    fn sample_wi(&self, _point: &crate::core::Point3f) -> Vec3f {
-        unimplemented!()
+    fn area(&self) -> f32 {
+        // TODO: Support transform
+        self.cylinder.area()
+    }
+
+    fn sample_area(&self) -> SurfaceInteraction<'_> {
+        let mut si = self.cylinder.sample_area();
+        self.object_to_world.transform_surface_iteraction(&mut si);
+        si
     }
 }
 
@@ -118,101 +145,113 @@ impl AACylinder {
     }
 }
 
-impl Shape for AACylinder {
-    fn intersect(
-        &self,
-        ray: &crate::core::Ray,
-        t_min: f32,
-        t_max: f32,
-    ) -> Option<SurfaceInteraction> {
-        let (axis_a, axis_b, axis_c) = match self.plane {
+impl AACylinder {
+    // the two axes across the cylinder and the one along it
+    fn axes(&self) -> (usize, usize, usize) {
+        match self.plane {
             Plane::YZ => (1, 2, 0),
             Plane::ZX => (2, 0, 1),
             Plane::XY => (0, 1, 2),
-        };
+        }
+    }
 
-        // z^2 + x^2 = r^2
+    // the nearest of the sides and the two bases within [t_min, t_max], with the normal
+    // there, not yet facing the ray
+    fn hit_t(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<(f32, Vec3f)> {
+        let (axis_a, axis_b, axis_c) = self.axes();
         let rd = ray.direction();
         let ro = ray.origin();
+        let in_range = |t: f32| t >= t_min && t <= t_max;
+        let r2 = self.radius * self.radius;
+        let (c_min, c_max) = (self.center0[axis_c], self.center1[axis_c]);
+        let mut nearest: Option<(f32, Vec3f)> = None;
 
-        let oc = ray.origin() - self.center0;
+        // z^2 + x^2 = r^2
+        let oc = ro - self.center0;
         let a = rd[axis_a] * rd[axis_a] + rd[axis_b] * rd[axis_b];
         let b = 2.0 * (rd[axis_a] * oc[axis_a] + rd[axis_b] * oc[axis_b]);
-        let c = oc[axis_a] * oc[axis_a] + oc[axis_b] * oc[axis_b] - self.radius * self.radius;
-
+        let c = oc[axis_a] * oc[axis_a] + oc[axis_b] * oc[axis_b] - r2;
         let discriminant = b * b - 4.0 * a * c;
-        if discriminant < 0.0 {
-            return None;
+        if a > 0.0 && discriminant >= 0.0 {
+            let sqrt_discr = discriminant.sqrt();
+            // the roots are in increasing order, the first one in range is the nearest
+            for t in [(-b - sqrt_discr) / (2.0 * a), (-b + sqrt_discr) / (2.0 * a)] {
+                let p = ro + t * rd;
+                if in_range(t) && p[axis_c] > c_min && p[axis_c] < c_max {
+                    let mut normal = p - self.center0;
+                    normal[axis_c] = 0.0;
+                    nearest = Some((t, normal));
+                    break;
+                }
+            }
         }
 
-        let (c_min, c_max) = (self.center0[axis_c], self.center1[axis_c]);
-        let up_normal = match self.plane {
-            Plane::YZ => vec3::XUP,
-            Plane::ZX => vec3::YUP,
-            Plane::XY => vec3::ZUP,
-        };
+        if rd[axis_c] != 0.0 {
+            let mut up_normal = Vec3f::zeros();
+            up_normal[axis_c] = 1.0;
+            for (center, normal) in [(self.center0, -up_normal), (self.center1, up_normal)] {
+                let t = (center[axis_c] - ro[axis_c]) / rd[axis_c];
+                let q = ro + t * rd - center;
+                let nearer = nearest.is_none_or(|(t_nearest, _)| t < t_nearest);
+                if in_range(t) && nearer && q.dot(&q) < r2 {
+                    nearest = Some((t, normal));
+                }
+            }
+        }
 
-        // base first
-        let t_normal: Option<(f32, Vec3f)> = (move || {
-            // check hit the base
-            let (center, normal) = if ray.direction()[axis_c] < 0.0 {
-                (self.center1, up_normal)
-            } else {
-                (self.center0, -up_normal)
-            };
+        nearest
+    }
+}
 
-            let oc = ray.origin() - center;
-            let t = -normal.dot(&oc) / ray.direction().dot(&normal);
-            let q = oc + ray.direction() * t;
+impl Shape for AACylinder {
+    fn intersect(
+        &self,
+        ray: &crate::core::Ray,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<SurfaceInteraction> {
+        let (t, normal) = self.hit_t(ray, t_min, t_max)?;
+        let p = ray.origin() + t * ray.direction();
 
-            if q.dot(&q) < self.radius * self.radius {
-                return Some((t, normal));
-            }
+        // TODO: uv
+        let rec = SurfaceInteraction::new(t, p, Point2f::new(0.0, 0.0), -ray.direction(), normal);
+        Some(rec)
+    }
 
-            // check hit the sides
-            let sqrt_discr = discriminant.sqrt();
-            let root1 = (-b - sqrt_discr) / (2.0 * a);
-            let p1 = ro[axis_c] + root1 * rd[axis_c];
+    // any of the two sides and the two bases within [t_min, t_max], no surface is built
+    fn intersect_p(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        let (axis_a, axis_b, axis_c) = self.axes();
+        let rd = ray.direction();
+        let ro = ray.origin();
+        let in_range = |t: f32| t >= t_min && t <= t_max;
+        let r2 = self.radius * self.radius;
+        let (c_min, c_max) = (self.center0[axis_c], self.center1[axis_c]);
 
-            let rooto: Option<f32> = if p1 > c_min && p1 < c_max {
-                Some(root1)
-            } else {
-                let root2 = (-b + sqrt_discr) / (2.0 * a);
-                let p2 = ro[axis_c] + root2 * rd[axis_c];
-                if p2 > c_min && p2 < c_max {
-                    Some(root2)
-                } else {
-                    None
+        let oc = ro - self.center0;
+        let a = rd[axis_a] * rd[axis_a] + rd[axis_b] * rd[axis_b];
+        let b = 2.0 * (rd[axis_a] * oc[axis_a] + rd[axis_b] * oc[axis_b]);
+        let c = oc[axis_a] * oc[axis_a] + oc[axis_b] * oc[axis_b] - r2;
+        let discriminant = b * b - 4.0 * a * c;
+        if a > 0.0 && discriminant >= 0.0 {
+            let sqrt_discr = discriminant.sqrt();
+            for t in [(-b - sqrt_discr) / (2.0 * a), (-b + sqrt_discr) / (2.0 * a)] {
+                let p = ro[axis_c] + t * rd[axis_c];
+                if in_range(t) && p > c_min && p < c_max {
+                    return true;
                 }
-            };
-
-            if let Some(t) = rooto {
-                let p = ray.origin() + t * ray.direction();
-                // hit sides
-                let mut normal = p - self.center0;
-                normal[axis_c] = p[axis_c];
-                let normal = normal.normalize();
-
-                return Some((t, normal));
             }
+        }
 
-            return None;
-        })();
-
-        if let Some((t, normal)) = t_normal {
-            if t < t_min || t > t_max {
-                return None;
+        if rd[axis_c] != 0.0 {
+            for center in [self.center0, self.center1] {
+                let t = (center[axis_c] - ro[axis_c]) / rd[axis_c];
+                let q = ro + t * rd - center;
+                if in_range(t) && q.dot(&q) < r2 {
+                    return true;
+                }
             }
-
-            let p = ray.origin() + t * ray.direction();
-            // TODO: uv
-
-            let rec =
-                SurfaceInteraction::new(t, p, Point2f::new(0.0, 0.0), -ray.direction(), normal);
-            return Some(rec);
-        };
-
-        return None;
+        }
+        false
     }
 
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
@@ -226,4 +265,106 @@ impl Shape for AACylinder {
             vec3::max(&(self.center0 + e), &(self.center1 + e)),
         ));
     }
+
+    fn area(&self) -> f32 {
+        let (_, _, axis_c) = self.axes();
+        let height = self.center1[axis_c] - self.center0[axis_c];
+        2.0 * PI * self.radius * (height + self.radius)
+    }
+
+    // the side or one of the two bases, by their areas
+    fn sample_area(&self) -> SurfaceInteraction<'_> {
+        let (axis_a, axis_b, axis_c) = self.axes();
+        let height = self.center1[axis_c] - self.center0[axis_c];
+        let phi = random::f32_r(0.0, 2.0 * PI);
+        let (sin_phi, cos_phi) = phi.sin_cos();
+
+        let mut normal = Vec3f::zeros();
+        let p = if random::f32() * (height + self.radius) < height {
+            normal[axis_a] = cos_phi;
+            normal[axis_b] = sin_phi;
+            let mut p = self.center0 + self.radius * normal;
+            p[axis_c] += random::f32() * height;
+            p
+        } else {
+            let (mut p, sign) = if random::f32() < 0.5 {
+                (self.center0, -1.0)
+            } else {
+                (self.center1, 1.0)
+            };
+            normal[axis_c] = sign;
+            let r = self.radius * random::f32().sqrt();
+            p[axis_a] += r * cos_phi;
+            p[axis_b] += r * sin_phi;
+            p
+        };
+
+        SurfaceInteraction::new(0.0, p, Point2f::new(0.0, 0.0), normal, normal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::AACylinder;
+    use crate::core::{Ray, Shape, Vec3f};
+    use crate::utils::random;
+
+    #[test]
+    fn test_sample_area() {
+        let cylinder = AACylinder::new(Vec3f::new(0.0, 0.0, 1.0), Vec3f::new(3.0, 0.0, 1.0), 0.5);
+        assert!((cylinder.area() - 2.0 * PI * 0.5 * 3.5).abs() < 1e-4);
+
+        // the points are on the surface, as many on the bases as their share of the area
+        random::seed(0);
+        let n = 10000;
+        let mut bases = 0;
+        for _ in 0..n {
+            let si = cylinder.sample_area();
+            let radial = (si.point.y.powi(2) + (si.point.z - 1.0).powi(2)).sqrt();
+            if si.normal.x != 0.0 {
+                bases += 1;
+                assert!(si.point.x == 0.0 || si.point.x == 3.0);
+                assert!(radial <= 0.5 + 1e-5);
+            } else {
+                assert!((radial - 0.5).abs() < 1e-5);
+                assert!(si.point.x >= 0.0 && si.point.x <= 3.0);
+            }
+        }
+        assert!((bases as f32 / n as f32 - 0.5 / 3.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_intersect_p() {
+        let cylinder = AACylinder::new(Vec3f::zeros(), Vec3f::new(0.0, 2.0, 0.0), 1.0);
+        // through the side at t = 4, from inside to the far side at t = 1 and through a cap
+        let side = Ray::new(Vec3f::new(-5.0, 1.0, 0.0), Vec3f::new(1.0, 0.0, 0.0), 0.0);
+        assert!(cylinder.intersect_p(&side, 0.001, 10.0));
+        assert!(!cylinder.intersect_p(&side, 0.001, 3.9));
+        let inside = Ray::new(Vec3f::new(0.0, 1.0, 0.0), Vec3f::new(0.0, 0.0, 1.0), 0.0);
+        assert!(cylinder.intersect_p(&inside, 0.001, 1.5));
+        assert!(!cylinder.intersect_p(&inside, 0.001, 0.5));
+        let hit = cylinder.intersect(&inside, 0.001, 1.5).unwrap();
+        assert!((hit.t_hit - 1.0).abs() < 1e-5);
+        let cap = Ray::new(Vec3f::new(0.2, 5.0, 0.0), Vec3f::new(0.0, -1.0, 0.0), 0.0);
+        assert!(cylinder.intersect_p(&cap, 0.001, 3.5));
+        assert!(!cylinder.intersect_p(&cap, 0.001, 2.5));
+        assert!((cylinder.intersect(&cap, 0.001, 10.0).unwrap().t_hit - 3.0).abs() < 1e-5);
+        let miss = Ray::new(Vec3f::new(-5.0, 3.0, 0.0), Vec3f::new(1.0, 0.0, 0.0), 0.0);
+        assert!(!cylinder.intersect_p(&miss, 0.001, 10.0));
+
+        // shadow rays have to agree with the closest hit
+        random::seed(0);
+        let point = || Vec3f::new(random::f32(), random::f32(), random::f32()) * 6.0;
+        for _ in 0..10000 {
+            let origin = point() - Vec3f::new(3.0, 2.0, 3.0);
+            let ray = Ray::new(origin, point() - Vec3f::new(3.0, 3.0, 3.0), 0.0);
+            let t_max = random::f32() * 5.0;
+            assert_eq!(
+                cylinder.intersect_p(&ray, 0.001, t_max),
+                cylinder.intersect(&ray, 0.001, t_max).is_some()
+            );
+        }
+    }
 }
@@ -22,6 +22,7 @@ pub struct Disk {
     center: Vec3f,
     radius: f32,
     normal: Vec3f,
+    plane: Plane,
     sampler: SamplerPtr,
     object_to_world: Transform,
     world_to_object: Transform,
@@ -43,6 +44,7 @@ impl Disk {
             center,
             radius,
             normal,
+            plane,
             sampler: Box::new(DiskRandomSampler::new(DiskData {
                 center,
                 radius,
@@ -55,10 +57,9 @@ impl Disk {
     }
 }
 
-impl Shape for Disk {
-    fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<SurfaceInteraction> {
-        let ray = self.world_to_object.transform_ray(ray);
-
+impl Disk {
+    // ray parameter of the hit in object space, inside (t_min, t_max)
+    fn hit_t(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<f32> {
         let o: Vec3f = ray.origin() - self.center;
         let t = -self.normal.dot(&o) / ray.direction().dot(&self.normal);
         let q: Vec3f = o + ray.direction() * t;
@@ -69,7 +70,18 @@ impl Shape for Disk {
             if t < t_min || t > t_max {
                 return None;
             }
+            return Some(t);
+        }
 
+        None
+    }
+}
+
+impl Shape for Disk {
+    fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<SurfaceInteraction> {
+        let ray = self.world_to_object.transform_ray(ray);
+
+        if let Some(t) = self.hit_t(&ray, t_min, t_max) {
             // TODO: u, v, polar coordinates like sphere ?
             let p = ray.origin() + t * ray.direction();
 
@@ -89,6 +101,11 @@ impl Shape for Disk {
         None
     }
 
+    fn intersect_p(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        let ray = self.world_to_object.transform_ray(ray);
+        self.hit_t(&ray, t_min, t_max).is_some()
+    }
+
     fn bounding_box(&self, _t0: f32, _t11: f32) -> Option<crate::core::AABB> {
         // (P - center) \cdot normal = 0
 
@@ -131,6 +148,22 @@ impl Shape for Disk {
         // TODO: Support transform
         self.sampler.sample_direction(origin).normalize()
     }
+
+    fn area(&self) -> f32 {
+        // TODO: Support transform
+        self.radius * self.radius * PI
+    }
+
+    fn sample_area(&self) -> SurfaceInteraction<'_> {
+        let theta = random::f32_r(0.0, 2.0 * PI);
+        let r = self.radius * random::f32().sqrt();
+        let p = point_on_disk(theta, r, self.center, self.plane);
+
+        let mut si =
+            SurfaceInteraction::new(0.0, p, Point2f::new(0.0, 0.0), self.normal, self.normal);
+        self.object_to_world.transform_surface_iteraction(&mut si);
+        si
+    }
 }
 
 struct DiskRandomSampler {
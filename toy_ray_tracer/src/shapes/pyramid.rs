@@ -31,6 +31,10 @@ impl Shape for Pyramid {
         self.triangles.intersect(ray, t_min, t_max)
     }
 
+    fn intersect_p(&self, ray: &crate::core::Ray, t_min: f32, t_max: f32) -> bool {
+        self.triangles.intersect_p(ray, t_min, t_max)
+    }
+
     fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
         self.triangles.bounding_box(t0, t1)
     }
@@ -42,4 +46,12 @@ impl Shape for Pyramid {
     fn sample_wi(&self, origin: &Vec3f) -> Vec3f {
         self.triangles.sample_wi(origin)
     }
+
+    fn area(&self) -> f32 {
+        self.triangles.area()
+    }
+
+    fn sample_area(&self) -> crate::core::SurfaceInteraction<'_> {
+        self.triangles.sample_area()
+    }
 }
@@ -91,7 +91,15 @@ impl Shape for Rect {
         self.triangles.sample_wi(origin)
     }
 
-    fn intersect_p(&self, ray: &Ray) -> bool {
-        self.triangles.intersect_p(ray)
+    fn area(&self) -> f32 {
+        self.triangles.area()
+    }
+
+    fn sample_area(&self) -> crate::core::SurfaceInteraction<'_> {
+        self.triangles.sample_area()
+    }
+
+    fn intersect_p(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        self.triangles.intersect_p(ray, t_min, t_max)
     }
 }
@@ -68,8 +68,8 @@ impl Shape for RegularPolygon {
         self.triangles.intersect(ray, t_min, t_max)
     }
 
-    fn intersect_p(&self, ray: &crate::core::Ray) -> bool {
-        self.triangles.intersect_p(ray)
+    fn intersect_p(&self, ray: &crate::core::Ray, t_min: f32, t_max: f32) -> bool {
+        self.triangles.intersect_p(ray, t_min, t_max)
     }
 
     fn sample_pdf(&self, point: &crate::core::Point3f, wi: &Vec3f) -> f32 {
@@ -79,4 +79,12 @@ impl Shape for RegularPolygon {
     fn sample_wi(&self, point: &crate::core::Point3f) -> Vec3f {
         self.triangles.sample_wi(point)
     }
+
+    fn area(&self) -> f32 {
+        self.triangles.area()
+    }
+
+    fn sample_area(&self) -> crate::core::SurfaceInteraction<'_> {
+        self.triangles.sample_area()
+    }
 }
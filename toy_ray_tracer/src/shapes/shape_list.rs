@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use crate::core::{Shape, ShapePtr, SurfaceInteraction, AABB};
+use crate::core::{sample, Shape, ShapePtr, SurfaceInteraction, AABB};
 use crate::utils::random;
 
 pub struct ShapeList {
@@ -55,6 +55,12 @@ impl Shape for ShapeList {
         hit_anything
     }
 
+    fn intersect_p(&self, ray: &crate::core::Ray, t_min: f32, t_max: f32) -> bool {
+        self.shapes
+            .iter()
+            .any(|shape| shape.intersect_p(ray, t_min, t_max))
+    }
+
     fn sample_pdf(&self, origin: &crate::core::Point3f, v: &crate::core::Vec3f) -> f32 {
         let weight = 1.0 / self.shapes.len() as f32;
 
@@ -68,7 +74,16 @@ impl Shape for ShapeList {
     }
 
     fn sample_wi(&self, origin: &crate::core::Vec3f) -> crate::core::Vec3f {
-        let idx = random::usize(0..self.shapes.len());
+        let idx = random::index(self.shapes.len());
         return self.shapes[idx].sample_wi(origin);
     }
+
+    fn area(&self) -> f32 {
+        self.shapes.iter().map(|shape| shape.area()).sum()
+    }
+
+    fn sample_area(&self) -> SurfaceInteraction<'_> {
+        let idx = sample::sample_by_area(&self.shapes, |shape| shape.area());
+        self.shapes[idx].sample_area()
+    }
 }
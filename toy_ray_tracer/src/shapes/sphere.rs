@@ -34,8 +34,9 @@ fn get_sphere_uv(p: &Vec3f) -> (f32, f32) {
     (u, v)
 }
 
-impl Shape for Sphere {
-    fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<SurfaceInteraction> {
+impl Sphere {
+    // nearest root of the ray-sphere equation inside (t_min, t_max)
+    fn hit_t(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<f32> {
         let oc = ray.origin() - self.center;
         let a = ray.direction().dot(&ray.direction());
         let b = 2.0 * oc.dot(&ray.direction());
@@ -49,18 +50,22 @@ impl Shape for Sphere {
         let sqrt_discriminant = discriminant.sqrt();
 
         let root1 = (-b - sqrt_discriminant) / (2.0 * a);
-        let root = if root1 > t_min && root1 < t_max {
-            Some(root1)
-        } else {
-            let root2 = (-b + sqrt_discriminant) / (2.0 * a);
-            if root2 > t_min && root2 < t_max {
-                Some(root2)
-            } else {
-                None
-            }
-        };
-
-        if let Some(t) = root {
+        if root1 > t_min && root1 < t_max {
+            return Some(root1);
+        }
+
+        let root2 = (-b + sqrt_discriminant) / (2.0 * a);
+        if root2 > t_min && root2 < t_max {
+            return Some(root2);
+        }
+
+        None
+    }
+}
+
+impl Shape for Sphere {
+    fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<SurfaceInteraction> {
+        if let Some(t) = self.hit_t(ray, t_min, t_max) {
             let p = ray.point_at_parameter(t);
             let normal = (p - self.center) / self.radius;
             let (u, v) = get_sphere_uv(&normal);
@@ -71,17 +76,8 @@ impl Shape for Sphere {
         return None;
     }
 
-    fn intersect_p(&self, ray: &Ray) -> bool {
-        let oc = ray.origin() - self.center;
-        let a = ray.direction().dot(&ray.direction());
-        let b = 2.0 * oc.dot(&ray.direction());
-        let c = oc.dot(&oc) - self.radius * self.radius;
-        let discriminant = b * b - 4.0 * a * c;
-
-        if discriminant < 0.0 {
-            return false;
-        }
-        return true;
+    fn intersect_p(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        self.hit_t(ray, t_min, t_max).is_some()
     }
 
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
@@ -93,7 +89,7 @@ impl Shape for Sphere {
 
     fn sample_pdf(&self, point: &Point3f, wi: &Vec3f) -> f32 {
         let ray = Ray::new(point.clone(), wi.clone(), 0.0);
-        if self.intersect_p(&ray) {
+        if self.intersect_p(&ray, 0.001, f32::MAX) {
             let cos_theta_max =
                 (1.0 - self.radius * self.radius / (self.center - point).norm_squared()).sqrt();
             let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
@@ -103,6 +99,17 @@ impl Shape for Sphere {
         return 0.0;
     }
 
+    fn area(&self) -> f32 {
+        4.0 * PI * self.radius * self.radius
+    }
+
+    fn sample_area(&self) -> SurfaceInteraction<'_> {
+        let normal = vec3::random_unit_vector();
+        let p = self.center + self.radius * normal;
+        let (u, v) = get_sphere_uv(&normal);
+        SurfaceInteraction::new(0.0, p, Point2f::new(u, v), normal, normal)
+    }
+
     fn sample_wi(&self, origin: &Vec3f) -> Vec3f {
         let direction = self.center - origin;
         let distance_squared = direction.norm_squared();
@@ -36,25 +36,13 @@ impl Triangle {
 
         return [p0, p1, p2];
     }
-}
 
-impl Shape for Triangle {
-    fn intersect(
-        &self,
-        ray: &crate::core::Ray,
-        t_min: f32,
-        t_max: f32,
-    ) -> Option<SurfaceInteraction> {
+    // returns (t, b1, b2) of the hit inside (t_min, t_max)
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<(f32, f32, f32)> {
         // @see https://www.scratchapixel.com/lessons/3d-basic-rendering/ray-tracing-rendering-a-triangle/moller-trumbore-ray-triangle-intersection
         // ray traingle intersection
 
-        let mesh = self.mesh.as_ref();
-
-        let idx = self.id * 3;
-        let indices = &mesh.vertex_indices[idx..(idx + 3)];
-        let p0 = mesh.positions[indices[0]];
-        let p1 = mesh.positions[indices[1]];
-        let p2 = mesh.positions[indices[2]];
+        let [p0, p1, p2] = self.get_vertices();
 
         let p0p1 = p1 - p0;
         let p0p2 = p2 - p0;
@@ -79,16 +67,34 @@ impl Shape for Triangle {
             return None;
         }
 
-        let b0 = 1.0 - b1 - b2;
-
-        let hit_p = b0 * p0 + b1 * p1 + b2 * p2;
-
         let hit_t = p0p2.dot(&qvec) * inv_det;
 
         if hit_t < t_min || hit_t > t_max {
             return None;
         }
 
+        Some((hit_t, b1, b2))
+    }
+}
+
+impl Shape for Triangle {
+    fn intersect(
+        &self,
+        ray: &crate::core::Ray,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<SurfaceInteraction> {
+        let (hit_t, b1, b2) = self.hit(ray, t_min, t_max)?;
+
+        let mesh = self.mesh.as_ref();
+
+        let idx = self.id * 3;
+        let indices = &mesh.vertex_indices[idx..(idx + 3)];
+        let [p0, p1, p2] = self.get_vertices();
+
+        let b0 = 1.0 - b1 - b2;
+        let hit_p = b0 * p0 + b1 * p1 + b2 * p2;
+
         let uvs: [Point2f; 3] = if mesh.uvs.is_empty() {
             [
                 Point2f::new(0.0, 0.0),
@@ -108,6 +114,10 @@ impl Shape for Triangle {
         return Some(si);
     }
 
+    fn intersect_p(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        self.hit(ray, t_min, t_max).is_some()
+    }
+
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
         let mesh = self.mesh.as_ref();
 
@@ -148,15 +158,36 @@ impl Shape for Triangle {
     }
 
     fn sample_wi(&self, point: &Point3f) -> Vec3f {
+        let hit_p = self.sample_area().point;
+        return (hit_p - point).normalize();
+    }
+
+    fn area(&self) -> f32 {
+        self.mesh.areas[self.id]
+    }
+
+    fn sample_area(&self) -> SurfaceInteraction<'_> {
+        let mesh = self.mesh.as_ref();
         let [p0, p1, p2] = self.get_vertices();
 
         // uniform generate point on triangle
-        let su0 = random::f32().sqrt();
-        let bx = 1.0 - su0;
-        let by = random::f32() * su0;
+        let (u0, u1) = random::f32_2d();
+        let su0 = u0.sqrt();
+        let b0 = 1.0 - su0;
+        let b1 = u1 * su0;
+        let b2 = 1.0 - b0 - b1;
+
+        let p = p0 * b0 + p1 * b1 + p2 * b2;
+        let uv = if mesh.uvs.is_empty() {
+            Point2f::new(b1 + b2, b2)
+        } else {
+            let idx = self.id * 3;
+            let indices = &mesh.vertex_indices[idx..(idx + 3)];
+            mesh.uvs[indices[0]] * b0 + mesh.uvs[indices[1]] * b1 + mesh.uvs[indices[2]] * b2
+        };
 
-        let hit_p = p0 * bx + p1 * by + p2 * (1.0 - bx - by);
-        return (hit_p - point).normalize();
+        let normal = mesh.surface_normals[self.id];
+        SurfaceInteraction::new(0.0, p, uv, normal, normal)
     }
 }
 
@@ -0,0 +1,150 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use serde::Serialize;
+use thread_local::ThreadLocal;
+
+#[derive(Clone, Copy)]
+pub enum Counter {
+    CameraRays,
+    // every query of the scene, camera rays included
+    SceneRays,
+    BvhNodeVisits,
+    PrimitiveTests,
+    NanSamples,
+    InfSamples,
+}
+
+const NCOUNTERS: usize = 6;
+
+#[derive(Clone, Copy)]
+pub enum Phase {
+    SceneBuild,
+    BvhBuild,
+    Render,
+}
+
+// every thread counts on its own, the report sums them up
+#[derive(Default)]
+struct Counters {
+    counts: [AtomicU64; NCOUNTERS],
+    // number of paths by their number of bounces
+    path_lengths: Mutex<Vec<u64>>,
+}
+
+static COUNTERS: OnceLock<ThreadLocal<Counters>> = OnceLock::new();
+static TIMES: Mutex<[Duration; 3]> = Mutex::new([Duration::ZERO; 3]);
+
+thread_local! {
+    // the counters of the current thread, looked up once
+    static LOCAL: &'static Counters = COUNTERS.get_or_init(ThreadLocal::new).get_or_default();
+}
+
+#[inline]
+pub fn add(counter: Counter, n: u64) {
+    LOCAL.with(|counters| counters.counts[counter as usize].fetch_add(n, Ordering::Relaxed));
+}
+
+pub fn add_path_length(bounces: usize) {
+    LOCAL.with(|counters| {
+        let mut lengths = counters.path_lengths.lock().unwrap();
+        if lengths.len() <= bounces {
+            lengths.resize(bounces + 1, 0);
+        }
+        lengths[bounces] += 1;
+    });
+}
+
+pub fn add_time(phase: Phase, elapsed: Duration) {
+    TIMES.lock().unwrap()[phase as usize] += elapsed;
+}
+
+// what the process has done so far, written next to the rendered image
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub camera_rays: u64,
+    // rays traced after the camera rays: bounces, shadow rays, subsurface walks and photons
+    pub secondary_rays: u64,
+    pub bvh_node_visits: u64,
+    pub primitive_tests: u64,
+    // paths of the path integrator by their number of bounces, 0 for the camera ray alone
+    pub path_lengths: Vec<u64>,
+    // samples with a NaN or infinite channel, a sign of a bug in the integrator or the scene
+    pub nan_samples: u64,
+    pub inf_samples: u64,
+    pub scene_build_seconds: f64,
+    // part of the scene build
+    pub bvh_build_seconds: f64,
+    pub render_seconds: f64,
+    // the most resident memory at any time, only known on linux
+    pub peak_memory_bytes: Option<u64>,
+}
+
+pub fn report() -> Report {
+    let mut counts = [0u64; NCOUNTERS];
+    let mut path_lengths: Vec<u64> = Vec::new();
+    if let Some(threads) = COUNTERS.get() {
+        for counters in threads.iter() {
+            for (sum, count) in counts.iter_mut().zip(&counters.counts) {
+                *sum += count.load(Ordering::Relaxed);
+            }
+            let lengths = counters.path_lengths.lock().unwrap();
+            if path_lengths.len() < lengths.len() {
+                path_lengths.resize(lengths.len(), 0);
+            }
+            for (sum, n) in path_lengths.iter_mut().zip(lengths.iter()) {
+                *sum += n;
+            }
+        }
+    }
+    let count = |counter: Counter| counts[counter as usize];
+    let times = *TIMES.lock().unwrap();
+    let seconds = |phase: Phase| times[phase as usize].as_secs_f64();
+
+    Report {
+        camera_rays: count(Counter::CameraRays),
+        secondary_rays: count(Counter::SceneRays).saturating_sub(count(Counter::CameraRays)),
+        bvh_node_visits: count(Counter::BvhNodeVisits),
+        primitive_tests: count(Counter::PrimitiveTests),
+        path_lengths,
+        nan_samples: count(Counter::NanSamples),
+        inf_samples: count(Counter::InfSamples),
+        scene_build_seconds: seconds(Phase::SceneBuild),
+        bvh_build_seconds: seconds(Phase::BvhBuild),
+        render_seconds: seconds(Phase::Render),
+        peak_memory_bytes: peak_memory(),
+    }
+}
+
+// the high-water mark of the resident set, VmHWM in /proc/self/status
+fn peak_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add, add_path_length, report, Counter};
+
+    // other tests render at the same time, the counts only grow
+    #[test]
+    fn test_report() {
+        let before = report();
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    add(Counter::InfSamples, 2);
+                    add_path_length(100);
+                });
+            }
+        });
+        let after = report();
+        assert!(after.inf_samples >= before.inf_samples + 8);
+        let long_paths = |report: &super::Report| report.path_lengths.get(100).copied();
+        assert!(long_paths(&after).unwrap() >= long_paths(&before).unwrap_or(0) + 4);
+        assert!(after.peak_memory_bytes.is_some());
+    }
+}
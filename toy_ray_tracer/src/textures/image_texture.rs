@@ -1,4 +1,6 @@
-use crate::core::{vec3, Image, Point2f, Spectrum, Texture, TextureData, Vec3f};
+use nalgebra::Matrix3;
+
+use crate::core::{srgb_eotf, vec3, HdrImage, Point2f, Spectrum, Texture, TextureData, Vec3f};
 
 struct MipMap<T> {
     data: Vec<T>,
@@ -41,6 +43,12 @@ pub struct ImageTextureParams {
     // tex_coords space has (0, 0) at lower left corner, so we need to flip face
     // but sometimes, the model loading library (such as easy-gltf) has already flipped the image
     pub flip: bool,
+
+    // the values are sRGB encoded, e.g. colors of 8 bit images
+    pub srgb: bool,
+    // applied to colors after decoding, to take them into the working space. Data like
+    // roughness or normals are left alone
+    pub color_transform: Matrix3<f32>,
 }
 
 impl Default for ImageTextureParams {
@@ -48,6 +56,8 @@ impl Default for ImageTextureParams {
         Self {
             scale: Spectrum::new(1.0, 1.0, 1.0),
             flip: true,
+            srgb: false,
+            color_transform: Matrix3::identity(),
         }
     }
 }
@@ -56,13 +66,21 @@ impl ImageTexture<f32> {
     pub fn from_gray_image(gray: &image::GrayImage, params: ImageTextureParams) -> Self {
         let width = gray.width() as usize;
         let height = gray.height() as usize;
-        let data = gray.pixels().map(|p| p[0] as f32).collect();
+        let data = gray.pixels().map(|p| f32::from(p[0]) / 255.0).collect();
+        Self::new(width, height, data, params)
+    }
+
+    // the red channel of `image`
+    pub fn from_image(image: HdrImage, params: ImageTextureParams) -> Self {
+        let width = image.width();
+        let height = image.height();
+        let data = (0..width * height).map(|i| image.get_pixel(i).x).collect();
         Self::new(width, height, data, params)
     }
 }
 
 impl ImageTexture<Spectrum> {
-    pub fn from_image(image: Image, params: ImageTextureParams) -> Self {
+    pub fn from_image(image: HdrImage, params: ImageTextureParams) -> Self {
         let width = image.width() as usize;
         let height = image.height() as usize;
         let mut data = Vec::new();
@@ -112,18 +130,34 @@ impl ImageTexture<Spectrum> {
 
 pub trait ImageTextureData {
     fn scale(&mut self, factor: &Spectrum);
+    fn decode_srgb(&mut self);
+    fn transform(&mut self, m: &Matrix3<f32>);
 }
 
 impl ImageTextureData for f32 {
     fn scale(&mut self, factor: &Spectrum) {
         *self *= factor[0];
     }
+
+    fn decode_srgb(&mut self) {
+        *self = srgb_eotf(*self);
+    }
+
+    fn transform(&mut self, _m: &Matrix3<f32>) {}
 }
 
 impl ImageTextureData for Spectrum {
     fn scale(&mut self, factor: &Spectrum) {
         *self = vec3::elementwise_mult(self, factor);
     }
+
+    fn decode_srgb(&mut self) {
+        *self = self.map(srgb_eotf);
+    }
+
+    fn transform(&mut self, m: &Matrix3<f32>) {
+        *self = m * *self;
+    }
 }
 
 impl<T: Clone + ImageTextureData> ImageTexture<T> {
@@ -134,6 +168,10 @@ impl<T: Clone + ImageTextureData> ImageTexture<T> {
             .iter()
             .map(|v| {
                 let mut v = v.clone();
+                if params.srgb {
+                    v.decode_srgb();
+                }
+                v.transform(&params.color_transform);
                 v.scale(&scale);
                 v
             })
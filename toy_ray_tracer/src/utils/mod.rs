@@ -38,17 +38,47 @@ impl<F: Fn(&Instant) -> ()> Drop for ExecutionTimer<F> {
 
 pub mod random {
     use fastrand;
-
-    pub fn new_rng() -> fastrand::Rng {
-        return fastrand::Rng::new();
-    }
+    use std::cell::RefCell;
 
     #[allow(unused_imports)]
     use std::ops::{Range, RangeBounds};
 
+    use crate::core::PixelSamplerPtr;
+
+    thread_local! {
+        // hands out the numbers of the pixel sample being rendered, fastrand does without one
+        static SAMPLER: RefCell<Option<PixelSamplerPtr>> = RefCell::new(None);
+    }
+
     #[inline(always)]
     pub fn f32() -> f32 {
-        return fastrand::f32();
+        SAMPLER.with(|sampler| match sampler.borrow_mut().as_mut() {
+            Some(sampler) => sampler.get_1d(),
+            None => fastrand::f32(),
+        })
+    }
+
+    // two numbers meant to be used together, e.g. a point on the lens or a direction
+    #[inline(always)]
+    pub fn f32_2d() -> (f32, f32) {
+        SAMPLER.with(|sampler| match sampler.borrow_mut().as_mut() {
+            Some(sampler) => sampler.get_2d(),
+            None => (fastrand::f32(), fastrand::f32()),
+        })
+    }
+
+    // draw the numbers of the current thread from `sampler`, or from fastrand again
+    pub fn set_sampler(sampler: Option<PixelSamplerPtr>) {
+        SAMPLER.with(|current| *current.borrow_mut() = sampler);
+    }
+
+    // start sample `index` of `pixel` with the sampler of the current thread
+    pub fn start_sample(pixel: u64, index: u64) {
+        SAMPLER.with(|sampler| {
+            if let Some(sampler) = sampler.borrow_mut().as_mut() {
+                sampler.start_sample(pixel, index);
+            }
+        });
     }
 
     pub fn f32_r(min: f32, max: f32) -> f32 {
@@ -56,4 +86,28 @@ pub mod random {
     }
 
     integer!(usize);
+
+    // one of `len` items picked by the next number of the sampler
+    pub fn index(len: usize) -> usize {
+        ((self::f32() * len as f32) as usize).min(len - 1)
+    }
+
+    // splitmix64 finalizer, turns nearby integers into unrelated seeds
+    pub fn mix(mut z: u64) -> u64 {
+        z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // re-seed the random numbers of the current thread
+    pub fn seed(seed: u64) {
+        fastrand::seed(mix(seed));
+    }
+
+    // re-seed the current thread for sample `sample` of stream `stream` (e.g. a pixel),
+    // what it draws afterwards doesn't depend on which thread renders it or in which order
+    pub fn seed_stream(seed: u64, stream: u64, sample: u64) {
+        fastrand::seed(mix(mix(mix(seed) ^ stream) ^ sample));
+    }
 }